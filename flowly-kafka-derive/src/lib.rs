@@ -0,0 +1,176 @@
+//! `#[derive(KafkaMessage)]` for the `KafkaMessage` trait of flowly-kafka, enabled by its
+//! `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, LitStr, Member, Type, parse_macro_input};
+
+/// Implements `KafkaMessage` for a struct sent as a whole as the value of its messages.
+///
+/// Fields can be annotated with:
+///
+/// * `#[kafka(key)]` - The key of the messages, cloned on every send. Without it messages
+///   have no key and a `Bytes` key type.
+/// * `#[kafka(timestamp)]` - The timestamp of the messages in milliseconds since the epoch,
+///   an `i64` or `Option<i64>`.
+/// * `#[kafka(header = "name")]` - A header of the messages, any `HeaderValue`, an
+///   `Option` being left out when `None`.
+///
+/// ```ignore
+/// #[derive(Serialize, KafkaMessage)]
+/// struct Order {
+///     #[kafka(key)]
+///     id: String,
+///     #[kafka(timestamp)]
+///     created_ms: i64,
+///     #[kafka(header = "tenant")]
+///     tenant: String,
+///     total: u64,
+/// }
+/// ```
+#[proc_macro_derive(KafkaMessage, attributes(kafka))]
+pub fn derive_kafka_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Role of a field given by its `#[kafka(..)]` attribute.
+enum Role {
+    Key,
+    Timestamp,
+    Header(LitStr),
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "KafkaMessage can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut key: Option<(Member, &Type)> = None;
+    let mut timestamp: Option<Member> = None;
+    let mut headers = Vec::new();
+
+    for (field, member) in fields.iter().zip(fields.members()) {
+        for role in field_roles(field)? {
+            match role {
+                Role::Key if key.is_some() => {
+                    return Err(Error::new_spanned(field, "duplicate #[kafka(key)] field"));
+                }
+                Role::Key => key = Some((member.clone(), &field.ty)),
+                Role::Timestamp if timestamp.is_some() => {
+                    return Err(Error::new_spanned(
+                        field,
+                        "duplicate #[kafka(timestamp)] field",
+                    ));
+                }
+                Role::Timestamp => timestamp = Some(member.clone()),
+                Role::Header(name) => headers.push((name, member.clone())),
+            }
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (key_ty, key_expr) = match key {
+        Some((member, ty)) => (
+            quote!(#ty),
+            quote!(::core::option::Option::Some(::core::clone::Clone::clone(&self.#member))),
+        ),
+        None => (
+            quote!(::flowly_kafka::__private::Bytes),
+            quote!(::core::option::Option::None),
+        ),
+    };
+
+    let ts_expr = match timestamp {
+        Some(member) => quote!(::core::convert::Into::<::core::option::Option<i64>>::into(
+            self.#member
+        )),
+        None => quote!(::core::option::Option::None),
+    };
+
+    let field_headers = if headers.is_empty() {
+        quote!()
+    } else {
+        let pushes = headers.iter().map(|(name, member)| {
+            quote! {
+                if let ::core::option::Option::Some(value) =
+                    ::flowly_kafka::message::HeaderValue::header_value(&self.#member)
+                {
+                    headers.push((::std::string::String::from(#name), value));
+                }
+            }
+        });
+
+        quote! {
+            fn field_headers(&self) -> ::std::vec::Vec<(::std::string::String, ::std::vec::Vec<u8>)> {
+                let mut headers = ::std::vec::Vec::new();
+                #(#pushes)*
+                headers
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::flowly_kafka::KafkaMessage for #ident #ty_generics #where_clause {
+            type Key = #key_ty;
+            type Value = Self;
+
+            #[inline]
+            fn key(&self) -> ::core::option::Option<Self::Key> {
+                #key_expr
+            }
+
+            #[inline]
+            fn value(&self) -> ::core::option::Option<&Self::Value> {
+                ::core::option::Option::Some(self)
+            }
+
+            #[inline]
+            fn ts_ms_utc(&self) -> ::core::option::Option<i64> {
+                #ts_expr
+            }
+
+            #[inline]
+            fn into_value(self) -> ::core::option::Option<Self::Value> {
+                ::core::option::Option::Some(self)
+            }
+
+            #field_headers
+        }
+    })
+}
+
+/// Parses the `#[kafka(..)]` attributes of `field`.
+fn field_roles(field: &syn::Field) -> syn::Result<Vec<Role>> {
+    let mut roles = Vec::new();
+
+    for attr in field.attrs.iter().filter(|x| x.path().is_ident("kafka")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                roles.push(Role::Key);
+            } else if meta.path.is_ident("timestamp") {
+                roles.push(Role::Timestamp);
+            } else if meta.path.is_ident("header") {
+                roles.push(Role::Header(meta.value()?.parse()?));
+            } else {
+                return Err(meta.error("expected `key`, `timestamp` or `header = \"..\"`"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(roles)
+}
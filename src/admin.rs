@@ -0,0 +1,737 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use bytes::Buf;
+use rdkafka::{
+    Offset, TopicPartitionList,
+    admin::{
+        AdminClient, AdminOptions, AlterConfig, NewPartitions, NewTopic, ResourceSpecifier,
+        TopicReplication, TopicResult,
+    },
+    consumer::{CommitMode, Consumer},
+    error::KafkaError,
+    groups::GroupInfo,
+    metadata::Metadata,
+    types::RDKafkaErrorCode,
+};
+
+use crate::{
+    KafkaCallbackContext, blocking,
+    builder::KafkaBuilder,
+    config::{Config, TopicSpec},
+    error::AdminError,
+};
+
+mod acl;
+
+pub use acl::{
+    AclBinding, AclBindingFilter, AclOperation, AclPatternType, AclPermission, AclResourceType,
+    Principal,
+};
+
+const DEFAULT_ADMIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerInfo {
+    pub id: i32,
+    pub host: String,
+    pub port: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterDescription {
+    /// Id of the broker that answered the metadata request.
+    pub orig_broker_id: i32,
+    pub brokers: Vec<BrokerInfo>,
+    pub topics: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionInfo {
+    pub id: i32,
+    /// Id of the leader broker, `-1` while the partition has no leader.
+    pub leader: i32,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicDescription {
+    pub name: String,
+    pub partitions: Vec<PartitionInfo>,
+    /// Effective topic configs, sensitive values are reported as `None`.
+    pub configs: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupListing {
+    pub name: String,
+    /// Group state reported by the coordinator, e.g. `Stable`, `Empty` or `Dead`.
+    pub state: String,
+    pub protocol_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMember {
+    pub id: String,
+    pub client_id: String,
+    pub client_host: String,
+    /// Assigned partitions by topic, empty for groups not using the consumer protocol.
+    pub assignment: BTreeMap<String, Vec<i32>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionOffset {
+    pub topic: String,
+    pub partition: i32,
+    /// Committed offset, `None` when the group has not committed one.
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupDescription {
+    pub name: String,
+    pub state: String,
+    pub protocol_type: String,
+    /// Assignment strategy, e.g. `range` or `cooperative-sticky`.
+    pub protocol: String,
+    pub members: Vec<GroupMember>,
+    /// Committed offsets of the partitions of all topics assigned to the members.
+    pub offsets: Vec<PartitionOffset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedRecords {
+    pub topic: String,
+    pub partition: i32,
+    /// The first offset still available after the deletion.
+    pub low_watermark: i64,
+}
+
+/// Target of [`KafkaAdmin::reset_offsets`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResetSpec {
+    /// The low watermark of every partition.
+    Earliest,
+
+    /// The high watermark of every partition.
+    Latest,
+
+    /// The first message at or after a timestamp in milliseconds, partitions without such
+    /// a message are moved to their high watermark.
+    Timestamp(i64),
+
+    /// Explicit `(partition, offset)` pairs, other partitions are left untouched.
+    Offsets(Vec<(i32, i64)>),
+}
+
+/// Thin wrapper over the rdkafka admin client, connected with the same [`Config`] as
+/// consumers and producers.
+pub struct KafkaAdmin {
+    builder: KafkaBuilder,
+    inner: Arc<AdminClient<KafkaCallbackContext>>,
+    timeout: Duration,
+}
+
+impl KafkaAdmin {
+    pub fn new(config: Config) -> Result<Self, AdminError> {
+        Self::from_builder(KafkaBuilder::new(config))
+    }
+
+    pub(crate) fn from_builder(builder: KafkaBuilder) -> Result<Self, AdminError> {
+        Ok(Self {
+            inner: Arc::new(builder.build_admin()?),
+            builder,
+            timeout: DEFAULT_ADMIN_TIMEOUT,
+        })
+    }
+
+    /// Sets how long the brokers may take to complete an operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The operation timeout, 30 seconds by default.
+    ///
+    /// # Returns
+    ///
+    /// The modified admin client with the timeout set.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Creates a topic.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the topic.
+    /// * `partitions` - The number of partitions.
+    /// * `replication` - The replication factor.
+    /// * `configs` - Topic level configs such as `retention.ms` or `cleanup.policy`.
+    pub async fn create_topic(
+        &self,
+        name: &str,
+        partitions: i32,
+        replication: i32,
+        configs: &[(&str, &str)],
+    ) -> Result<(), AdminError> {
+        let topic = configs.iter().fold(
+            NewTopic::new(name, partitions, TopicReplication::Fixed(replication)),
+            |topic, (key, value)| topic.set(key, value),
+        );
+
+        let results = self.inner.create_topics([&topic], &self.options()).await?;
+
+        check_topic_results(results)
+    }
+
+    /// Creates the topics of `names` that do not exist yet, all with the same layout.
+    pub async fn ensure_topics(&self, names: &[&str], spec: &TopicSpec) -> Result<(), AdminError> {
+        let topics = names
+            .iter()
+            .map(|name| {
+                spec.configs.iter().fold(
+                    NewTopic::new(
+                        name,
+                        spec.partitions,
+                        TopicReplication::Fixed(spec.replication),
+                    ),
+                    |topic, (key, value)| topic.set(key, value),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let results = self.inner.create_topics(&topics, &self.options()).await?;
+
+        check_topic_results(
+            results
+                .into_iter()
+                .filter(|result| !matches!(result, Err((_, RDKafkaErrorCode::TopicAlreadyExists))))
+                .collect(),
+        )
+    }
+
+    /// Deletes a topic.
+    pub async fn delete_topic(&self, name: &str) -> Result<(), AdminError> {
+        let results = self.inner.delete_topics(&[name], &self.options()).await?;
+
+        check_topic_results(results)
+    }
+
+    /// Sets dynamic configs of a topic, e.g. `retention.ms`.
+    ///
+    /// The brokers replace the whole set of dynamic configs of the topic, so configs set
+    /// earlier but missing from `entries` are reverted to their defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the topic.
+    /// * `entries` - The complete set of dynamic configs of the topic.
+    pub async fn alter_topic_config(
+        &self,
+        name: &str,
+        entries: &[(&str, &str)],
+    ) -> Result<(), AdminError> {
+        let config = entries.iter().fold(
+            AlterConfig::new(ResourceSpecifier::Topic(name)),
+            |config, (key, value)| config.set(key, value),
+        );
+
+        let results = self.inner.alter_configs([&config], &self.options()).await?;
+
+        for result in results {
+            if let Err((_, code)) = result {
+                return Err(AdminError::Topic {
+                    topic: name.to_string(),
+                    code,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Grows a topic to `new_total` partitions, partitions can never be removed.
+    pub async fn create_partitions(&self, name: &str, new_total: usize) -> Result<(), AdminError> {
+        let partitions = NewPartitions::new(name, new_total);
+        let results = self
+            .inner
+            .create_partitions([&partitions], &self.options())
+            .await?;
+
+        check_topic_results(results)
+    }
+
+    /// Describes the brokers and topics of the cluster.
+    pub async fn describe_cluster(&self) -> Result<ClusterDescription, AdminError> {
+        let metadata = self.fetch_metadata(None).await?;
+
+        Ok(ClusterDescription {
+            orig_broker_id: metadata.orig_broker_id(),
+            brokers: metadata
+                .brokers()
+                .iter()
+                .map(|broker| BrokerInfo {
+                    id: broker.id(),
+                    host: broker.host().to_string(),
+                    port: broker.port(),
+                })
+                .collect(),
+            topics: metadata
+                .topics()
+                .iter()
+                .map(|topic| topic.name().to_string())
+                .collect(),
+        })
+    }
+
+    /// Returns the number of partitions of every topic of the cluster.
+    ///
+    /// Metadata of all topics is requested on purpose, asking for a single missing topic
+    /// may make the brokers auto-create it.
+    pub(crate) async fn partition_counts(&self) -> Result<BTreeMap<String, usize>, AdminError> {
+        let metadata = self.fetch_metadata(None).await?;
+
+        Ok(metadata
+            .topics()
+            .iter()
+            .filter(|topic| topic.error().is_none())
+            .map(|topic| (topic.name().to_string(), topic.partitions().len()))
+            .collect())
+    }
+
+    /// Describes the partitions and the configs of a topic.
+    pub async fn describe_topic(&self, name: &str) -> Result<TopicDescription, AdminError> {
+        let metadata = self.fetch_metadata(Some(name.to_string())).await?;
+
+        let topic = metadata
+            .topics()
+            .iter()
+            .find(|topic| topic.name() == name)
+            .ok_or_else(|| AdminError::Topic {
+                topic: name.to_string(),
+                code: RDKafkaErrorCode::UnknownTopicOrPartition,
+            })?;
+
+        if let Some(err) = topic.error() {
+            return Err(AdminError::Topic {
+                topic: name.to_string(),
+                code: err.into(),
+            });
+        }
+
+        let partitions = topic
+            .partitions()
+            .iter()
+            .map(|partition| PartitionInfo {
+                id: partition.id(),
+                leader: partition.leader(),
+                replicas: partition.replicas().to_vec(),
+                isr: partition.isr().to_vec(),
+            })
+            .collect();
+
+        let resources = self
+            .inner
+            .describe_configs([&ResourceSpecifier::Topic(name)], &self.options())
+            .await?;
+
+        let mut configs = BTreeMap::new();
+        for resource in resources {
+            let resource = resource.map_err(|code| AdminError::Topic {
+                topic: name.to_string(),
+                code,
+            })?;
+
+            configs.extend(
+                resource
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry.name, entry.value)),
+            );
+        }
+
+        Ok(TopicDescription {
+            name: name.to_string(),
+            partitions,
+            configs,
+        })
+    }
+
+    /// Lists the consumer groups known to the cluster.
+    pub async fn list_groups(&self) -> Result<Vec<GroupListing>, AdminError> {
+        Ok(self
+            .fetch_groups(None)
+            .await?
+            .into_iter()
+            .map(|group| GroupListing {
+                name: group.name,
+                state: group.state,
+                protocol_type: group.protocol_type,
+            })
+            .collect())
+    }
+
+    /// Describes the members of a group, their assignments and the committed offsets of
+    /// the assigned topics.
+    pub async fn describe_group(&self, group_id: &str) -> Result<GroupDescription, AdminError> {
+        let mut group = self
+            .fetch_groups(Some(group_id.to_string()))
+            .await?
+            .into_iter()
+            .find(|group| group.name == group_id)
+            .ok_or_else(|| AdminError::Group {
+                group: group_id.to_string(),
+                code: RDKafkaErrorCode::GroupIdNotFound,
+            })?;
+
+        let mut topics: Vec<_> = group
+            .members
+            .iter()
+            .flat_map(|member| member.assignment.keys().cloned())
+            .collect();
+
+        topics.sort();
+        topics.dedup();
+
+        group.offsets = self.group_offsets(group_id, topics).await?;
+
+        Ok(group)
+    }
+
+    /// Fetches the offsets committed by a group for every partition of a topic, also
+    /// works for groups without active members.
+    pub async fn committed_offsets(
+        &self,
+        group_id: &str,
+        topic: &str,
+    ) -> Result<Vec<PartitionOffset>, AdminError> {
+        self.group_offsets(group_id, vec![topic.to_string()]).await
+    }
+
+    /// Moves the committed offsets of a group on a topic, returning the new offsets.
+    ///
+    /// The group must not have active members, they would overwrite the offsets with their
+    /// next commit.
+    pub async fn reset_offsets(
+        &self,
+        group_id: &str,
+        topic: &str,
+        spec: ResetSpec,
+    ) -> Result<Vec<PartitionOffset>, AdminError> {
+        let active = self
+            .fetch_groups(Some(group_id.to_string()))
+            .await?
+            .into_iter()
+            .any(|group| group.name == group_id && !group.members.is_empty());
+
+        if active {
+            return Err(AdminError::Group {
+                group: group_id.to_string(),
+                code: RDKafkaErrorCode::NonEmptyGroup,
+            });
+        }
+
+        let consumer = self.builder.build_group_consumer(group_id)?;
+        let timeout = self.timeout;
+        let topic = topic.to_string();
+
+        let committed = blocking(move || {
+            let metadata = consumer.fetch_metadata(Some(&topic), timeout)?;
+            let partitions: Vec<i32> = metadata
+                .topics()
+                .iter()
+                .flat_map(|x| x.partitions())
+                .map(|x| x.id())
+                .collect();
+
+            let mut tpl = TopicPartitionList::new();
+
+            match spec {
+                ResetSpec::Earliest | ResetSpec::Latest => {
+                    for partition in partitions {
+                        let (low, high) = consumer.fetch_watermarks(&topic, partition, timeout)?;
+                        let offset = if spec == ResetSpec::Earliest {
+                            low
+                        } else {
+                            high
+                        };
+
+                        tpl.add_partition_offset(&topic, partition, Offset::Offset(offset))?;
+                    }
+                }
+                ResetSpec::Timestamp(ts) => {
+                    let mut query = TopicPartitionList::new();
+                    for partition in &partitions {
+                        query.add_partition_offset(&topic, *partition, Offset::Offset(ts))?;
+                    }
+
+                    for elem in consumer.offsets_for_times(query, timeout)?.elements() {
+                        let offset = match elem.offset() {
+                            Offset::Offset(offset) => offset,
+                            _ => {
+                                consumer
+                                    .fetch_watermarks(&topic, elem.partition(), timeout)?
+                                    .1
+                            }
+                        };
+
+                        tpl.add_partition_offset(&topic, elem.partition(), Offset::Offset(offset))?;
+                    }
+                }
+                ResetSpec::Offsets(offsets) => {
+                    for (partition, offset) in offsets {
+                        tpl.add_partition_offset(&topic, partition, Offset::Offset(offset))?;
+                    }
+                }
+            }
+
+            consumer.commit(&tpl, CommitMode::Sync)?;
+
+            Ok::<_, KafkaError>(tpl)
+        })
+        .await?;
+
+        Ok(partition_offsets(&committed))
+    }
+
+    /// Deletes the records of a partition before `before_offset`.
+    pub async fn delete_records(
+        &self,
+        topic: &str,
+        partition: i32,
+        before_offset: i64,
+    ) -> Result<DeletedRecords, AdminError> {
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition_offset(topic, partition, Offset::Offset(before_offset))?;
+
+        let mut deleted = self.delete_records_of(tpl).await?;
+
+        deleted.pop().ok_or_else(|| AdminError::Topic {
+            topic: topic.to_string(),
+            code: RDKafkaErrorCode::UnknownTopicOrPartition,
+        })
+    }
+
+    /// Deletes every record of every partition of a topic.
+    pub async fn delete_all_records(&self, topic: &str) -> Result<Vec<DeletedRecords>, AdminError> {
+        let metadata = self.fetch_metadata(Some(topic.to_string())).await?;
+
+        let mut tpl = TopicPartitionList::new();
+        for partition in metadata.topics().iter().flat_map(|x| x.partitions()) {
+            tpl.add_partition_offset(topic, partition.id(), Offset::End)?;
+        }
+
+        self.delete_records_of(tpl).await
+    }
+
+    async fn delete_records_of(
+        &self,
+        tpl: TopicPartitionList,
+    ) -> Result<Vec<DeletedRecords>, AdminError> {
+        let result = self.inner.delete_records(&tpl, &self.options()).await?;
+
+        result
+            .elements()
+            .iter()
+            .map(|elem| {
+                elem.error()?;
+
+                Ok(DeletedRecords {
+                    topic: elem.topic().to_string(),
+                    partition: elem.partition(),
+                    low_watermark: elem.offset().to_raw().unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    /// Creates ACL bindings, creating an existing binding is not an error.
+    pub async fn create_acls(&self, acls: &[AclBinding]) -> Result<(), AdminError> {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+        let acls = acls.to_vec();
+
+        blocking(move || acl::create_acls(inner.inner().native_ptr(), &acls, timeout)).await
+    }
+
+    /// Lists the ACL bindings matching `filter`.
+    pub async fn describe_acls(
+        &self,
+        filter: &AclBindingFilter,
+    ) -> Result<Vec<AclBinding>, AdminError> {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+        let filter = filter.clone();
+
+        blocking(move || acl::describe_acls(inner.inner().native_ptr(), &filter, timeout)).await
+    }
+
+    /// Deletes the ACL bindings matching any of `filters`, returning the deleted bindings.
+    pub async fn delete_acls(
+        &self,
+        filters: &[AclBindingFilter],
+    ) -> Result<Vec<AclBinding>, AdminError> {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+        let filters = filters.to_vec();
+
+        blocking(move || acl::delete_acls(inner.inner().native_ptr(), &filters, timeout)).await
+    }
+
+    async fn group_offsets(
+        &self,
+        group_id: &str,
+        topics: Vec<String>,
+    ) -> Result<Vec<PartitionOffset>, AdminError> {
+        if topics.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let consumer = self.builder.build_group_consumer(group_id)?;
+        let timeout = self.timeout;
+
+        let committed = blocking(move || {
+            let mut tpl = TopicPartitionList::new();
+
+            for topic in &topics {
+                let metadata = consumer.fetch_metadata(Some(topic), timeout)?;
+
+                for partition in metadata.topics().iter().flat_map(|x| x.partitions()) {
+                    tpl.add_partition(topic, partition.id());
+                }
+            }
+
+            consumer.committed_offsets(tpl, timeout)
+        })
+        .await?;
+
+        Ok(partition_offsets(&committed))
+    }
+
+    /// Fetches the group list on the blocking thread pool, converting it right away as the
+    /// native list can't leave the thread.
+    async fn fetch_groups(
+        &self,
+        group: Option<String>,
+    ) -> Result<Vec<GroupDescription>, AdminError> {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+
+        let groups = blocking(move || {
+            let list = inner.inner().fetch_group_list(group.as_deref(), timeout)?;
+
+            Ok::<_, KafkaError>(
+                list.groups()
+                    .iter()
+                    .map(|group| GroupDescription {
+                        name: group.name().to_string(),
+                        state: group.state().to_string(),
+                        protocol_type: group.protocol_type().to_string(),
+                        protocol: group.protocol().to_string(),
+                        members: group
+                            .members()
+                            .iter()
+                            .map(|member| GroupMember {
+                                id: member.id().to_string(),
+                                client_id: member.client_id().to_string(),
+                                client_host: member.client_host().to_string(),
+                                assignment: member_assignment(group, member.assignment()),
+                            })
+                            .collect(),
+                        offsets: Vec::new(),
+                    })
+                    .collect(),
+            )
+        })
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// Fetches the metadata on the blocking thread pool, librdkafka only offers a blocking call.
+    async fn fetch_metadata(&self, topic: Option<String>) -> Result<Metadata, AdminError> {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+
+        Ok(blocking(move || inner.inner().fetch_metadata(topic.as_deref(), timeout)).await?)
+    }
+
+    fn options(&self) -> AdminOptions {
+        AdminOptions::new()
+            .operation_timeout(Some(self.timeout))
+            .request_timeout(Some(self.timeout))
+    }
+}
+
+/// Parses the assignment of a member using the consumer protocol
+/// (`version, [topic, [partition]], user_data`).
+fn member_assignment(group: &GroupInfo, assignment: Option<&[u8]>) -> BTreeMap<String, Vec<i32>> {
+    let mut topics = BTreeMap::new();
+
+    if group.protocol_type() != "consumer" {
+        return topics;
+    }
+
+    let Some(mut buf) = assignment else {
+        return topics;
+    };
+
+    let mut parse = || -> Option<()> {
+        (buf.remaining() >= 6).then_some(())?;
+        let _version = buf.get_i16();
+        let topic_count = buf.get_i32();
+
+        for _ in 0..topic_count {
+            (buf.remaining() >= 2).then_some(())?;
+            let len = buf.get_i16().try_into().ok()?;
+            (buf.remaining() >= len + 4).then_some(())?;
+            let topic = String::from_utf8_lossy(&buf[..len]).into_owned();
+            buf.advance(len);
+
+            let partition_count: usize = buf.get_i32().try_into().ok()?;
+            (buf.remaining() >= partition_count * 4).then_some(())?;
+            let partitions = (0..partition_count).map(|_| buf.get_i32()).collect();
+
+            topics.insert(topic, partitions);
+        }
+
+        Some(())
+    };
+
+    if parse().is_none() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            group = group.name(),
+            "malformed assignment in consumer group"
+        );
+
+        #[cfg(not(feature = "tracing"))]
+        log::warn!("malformed assignment in consumer group `{}`", group.name());
+    }
+
+    topics
+}
+
+fn partition_offsets(tpl: &TopicPartitionList) -> Vec<PartitionOffset> {
+    tpl.elements()
+        .iter()
+        .map(|elem| PartitionOffset {
+            topic: elem.topic().to_string(),
+            partition: elem.partition(),
+            offset: match elem.offset() {
+                Offset::Offset(offset) => Some(offset),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+fn check_topic_results(results: Vec<TopicResult>) -> Result<(), AdminError> {
+    for result in results {
+        if let Err((topic, code)) = result {
+            return Err(AdminError::Topic { topic, code });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,560 @@
+//! ACL management, not wrapped by rdkafka so the librdkafka admin API is called directly.
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    fmt, ptr,
+    str::FromStr,
+    time::Duration,
+};
+
+use rdkafka::{error::KafkaError, types::RDKafkaErrorCode};
+use rdkafka_sys as rdsys;
+use rdsys::{
+    rd_kafka_AclOperation_t as RawOperation, rd_kafka_AclPermissionType_t as RawPermission,
+    rd_kafka_ResourcePatternType_t as RawPattern, rd_kafka_ResourceType_t as RawResource,
+};
+
+use crate::error::AdminError;
+
+macro_rules! raw_enum {
+    ($name:ident, $raw:ident { $($variant:ident => $raw_variant:ident),* $(,)? }) => {
+        impl $name {
+            fn to_raw(self) -> $raw {
+                match self {
+                    $($name::$variant => $raw::$raw_variant,)*
+                }
+            }
+
+            fn from_raw(raw: $raw) -> Self {
+                match raw {
+                    $($raw::$raw_variant => $name::$variant,)*
+                    #[allow(unreachable_patterns)]
+                    _ => $name::Unknown,
+                }
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AclResourceType {
+    Unknown,
+    /// Matches any resource type, only valid in filters.
+    Any,
+    Topic,
+    Group,
+    /// The cluster itself, its resource name is always `kafka-cluster`.
+    Cluster,
+    TransactionalId,
+}
+
+raw_enum!(AclResourceType, RawResource {
+    Unknown => RD_KAFKA_RESOURCE_UNKNOWN,
+    Any => RD_KAFKA_RESOURCE_ANY,
+    Topic => RD_KAFKA_RESOURCE_TOPIC,
+    Group => RD_KAFKA_RESOURCE_GROUP,
+    Cluster => RD_KAFKA_RESOURCE_BROKER,
+    TransactionalId => RD_KAFKA_RESOURCE_TRANSACTIONAL_ID,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AclPatternType {
+    Unknown,
+    /// Matches any pattern type, only valid in filters.
+    Any,
+    /// Matches the literal, prefixed and wildcard bindings applying to a name, only valid
+    /// in filters.
+    Match,
+    Literal,
+    Prefixed,
+}
+
+raw_enum!(AclPatternType, RawPattern {
+    Unknown => RD_KAFKA_RESOURCE_PATTERN_UNKNOWN,
+    Any => RD_KAFKA_RESOURCE_PATTERN_ANY,
+    Match => RD_KAFKA_RESOURCE_PATTERN_MATCH,
+    Literal => RD_KAFKA_RESOURCE_PATTERN_LITERAL,
+    Prefixed => RD_KAFKA_RESOURCE_PATTERN_PREFIXED,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AclOperation {
+    Unknown,
+    /// Matches any operation, only valid in filters.
+    Any,
+    All,
+    Read,
+    Write,
+    Create,
+    Delete,
+    Alter,
+    Describe,
+    ClusterAction,
+    DescribeConfigs,
+    AlterConfigs,
+    IdempotentWrite,
+}
+
+raw_enum!(AclOperation, RawOperation {
+    Unknown => RD_KAFKA_ACL_OPERATION_UNKNOWN,
+    Any => RD_KAFKA_ACL_OPERATION_ANY,
+    All => RD_KAFKA_ACL_OPERATION_ALL,
+    Read => RD_KAFKA_ACL_OPERATION_READ,
+    Write => RD_KAFKA_ACL_OPERATION_WRITE,
+    Create => RD_KAFKA_ACL_OPERATION_CREATE,
+    Delete => RD_KAFKA_ACL_OPERATION_DELETE,
+    Alter => RD_KAFKA_ACL_OPERATION_ALTER,
+    Describe => RD_KAFKA_ACL_OPERATION_DESCRIBE,
+    ClusterAction => RD_KAFKA_ACL_OPERATION_CLUSTER_ACTION,
+    DescribeConfigs => RD_KAFKA_ACL_OPERATION_DESCRIBE_CONFIGS,
+    AlterConfigs => RD_KAFKA_ACL_OPERATION_ALTER_CONFIGS,
+    IdempotentWrite => RD_KAFKA_ACL_OPERATION_IDEMPOTENT_WRITE,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AclPermission {
+    Unknown,
+    /// Matches both permissions, only valid in filters.
+    Any,
+    Deny,
+    Allow,
+}
+
+raw_enum!(AclPermission, RawPermission {
+    Unknown => RD_KAFKA_ACL_PERMISSION_TYPE_UNKNOWN,
+    Any => RD_KAFKA_ACL_PERMISSION_TYPE_ANY,
+    Deny => RD_KAFKA_ACL_PERMISSION_TYPE_DENY,
+    Allow => RD_KAFKA_ACL_PERMISSION_TYPE_ALLOW,
+});
+
+/// A Kafka principal such as `User:alice`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Principal {
+    pub kind: String,
+    pub name: String,
+}
+
+impl Principal {
+    /// A `User` principal, the kind used by every built-in authenticator.
+    pub fn user<S: Into<String>>(name: S) -> Self {
+        Self {
+            kind: "User".to_string(),
+            name: name.into(),
+        }
+    }
+
+    /// All users (`User:*`).
+    pub fn any_user() -> Self {
+        Self::user("*")
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.name)
+    }
+}
+
+impl FromStr for Principal {
+    type Err = crate::error::ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((kind, name)) if !kind.is_empty() => Ok(Self {
+                kind: kind.to_string(),
+                name: name.to_string(),
+            }),
+            _ => Err(crate::error::ConfigError::invalid_value("principal", s)),
+        }
+    }
+}
+
+/// An ACL granting or denying an operation on a resource to a principal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AclBinding {
+    pub resource_type: AclResourceType,
+    pub resource_name: String,
+    pub pattern_type: AclPatternType,
+    pub principal: Principal,
+    /// Host the principal connects from, `*` for any host.
+    pub host: String,
+    pub operation: AclOperation,
+    pub permission: AclPermission,
+}
+
+impl AclBinding {
+    /// Allows `principal` to perform `operation` on the topic `name` from any host.
+    pub fn allow_topic<S: Into<String>>(
+        name: S,
+        principal: Principal,
+        operation: AclOperation,
+    ) -> Self {
+        Self {
+            resource_type: AclResourceType::Topic,
+            resource_name: name.into(),
+            pattern_type: AclPatternType::Literal,
+            principal,
+            host: "*".to_string(),
+            operation,
+            permission: AclPermission::Allow,
+        }
+    }
+}
+
+/// Selects ACL bindings to describe or delete, `None` fields match anything.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AclBindingFilter {
+    pub resource_type: AclResourceType,
+    pub resource_name: Option<String>,
+    pub pattern_type: AclPatternType,
+    pub principal: Option<Principal>,
+    pub host: Option<String>,
+    pub operation: AclOperation,
+    pub permission: AclPermission,
+}
+
+impl Default for AclBindingFilter {
+    fn default() -> Self {
+        Self {
+            resource_type: AclResourceType::Any,
+            resource_name: None,
+            pattern_type: AclPatternType::Any,
+            principal: None,
+            host: None,
+            operation: AclOperation::Any,
+            permission: AclPermission::Any,
+        }
+    }
+}
+
+impl From<&AclBinding> for AclBindingFilter {
+    fn from(acl: &AclBinding) -> Self {
+        Self {
+            resource_type: acl.resource_type,
+            resource_name: Some(acl.resource_name.clone()),
+            pattern_type: acl.pattern_type,
+            principal: Some(acl.principal.clone()),
+            host: Some(acl.host.clone()),
+            operation: acl.operation,
+            permission: acl.permission,
+        }
+    }
+}
+
+struct Bindings(Vec<*mut rdsys::rd_kafka_AclBinding_t>);
+
+impl Drop for Bindings {
+    fn drop(&mut self) {
+        for binding in &self.0 {
+            unsafe { rdsys::rd_kafka_AclBinding_destroy(*binding) };
+        }
+    }
+}
+
+struct Request {
+    queue: *mut rdsys::rd_kafka_queue_t,
+    options: *mut rdsys::rd_kafka_AdminOptions_t,
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        unsafe {
+            rdsys::rd_kafka_AdminOptions_destroy(self.options);
+            rdsys::rd_kafka_queue_destroy(self.queue);
+        }
+    }
+}
+
+struct Event(*mut rdsys::rd_kafka_event_t);
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { rdsys::rd_kafka_event_destroy(self.0) };
+    }
+}
+
+/// Runs an admin request on its own queue and waits for the result event.
+///
+/// # Safety
+///
+/// `rk` must be a live client handle.
+unsafe fn run_request(
+    rk: *mut rdsys::rd_kafka_t,
+    op: rdsys::rd_kafka_admin_op_t,
+    timeout: Duration,
+    submit: impl FnOnce(*const rdsys::rd_kafka_AdminOptions_t, *mut rdsys::rd_kafka_queue_t),
+) -> Result<Event, AdminError> {
+    let timeout_ms = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+    let request = unsafe {
+        Request {
+            queue: rdsys::rd_kafka_queue_new(rk),
+            options: rdsys::rd_kafka_AdminOptions_new(rk, op),
+        }
+    };
+
+    let mut errstr = [0 as c_char; 512];
+    let err = unsafe {
+        rdsys::rd_kafka_AdminOptions_set_request_timeout(
+            request.options,
+            timeout_ms,
+            errstr.as_mut_ptr(),
+            errstr.len(),
+        )
+    };
+
+    if err != rdsys::rd_kafka_resp_err_t::RD_KAFKA_RESP_ERR_NO_ERROR {
+        return Err(creation_error(&errstr).into());
+    }
+
+    submit(request.options, request.queue);
+
+    // the request itself times out after `timeout`, leave room for the result to arrive
+    let event =
+        unsafe { rdsys::rd_kafka_queue_poll(request.queue, timeout_ms.saturating_add(1000)) };
+    if event.is_null() {
+        return Err(KafkaError::AdminOp(RDKafkaErrorCode::RequestTimedOut).into());
+    }
+
+    let event = Event(event);
+    let err = unsafe { rdsys::rd_kafka_event_error(event.0) };
+    if err != rdsys::rd_kafka_resp_err_t::RD_KAFKA_RESP_ERR_NO_ERROR {
+        return Err(KafkaError::AdminOp(err.into()).into());
+    }
+
+    Ok(event)
+}
+
+pub(super) fn create_acls(
+    rk: *mut rdsys::rd_kafka_t,
+    acls: &[AclBinding],
+    timeout: Duration,
+) -> Result<(), AdminError> {
+    let mut bindings = Bindings(Vec::with_capacity(acls.len()));
+
+    for acl in acls {
+        let name = CString::new(acl.resource_name.as_str()).map_err(KafkaError::from)?;
+        let principal = CString::new(acl.principal.to_string()).map_err(KafkaError::from)?;
+        let host = CString::new(acl.host.as_str()).map_err(KafkaError::from)?;
+        let mut errstr = [0 as c_char; 512];
+
+        let binding = unsafe {
+            rdsys::rd_kafka_AclBinding_new(
+                acl.resource_type.to_raw(),
+                name.as_ptr(),
+                acl.pattern_type.to_raw(),
+                principal.as_ptr(),
+                host.as_ptr(),
+                acl.operation.to_raw(),
+                acl.permission.to_raw(),
+                errstr.as_mut_ptr(),
+                errstr.len(),
+            )
+        };
+
+        if binding.is_null() {
+            return Err(creation_error(&errstr).into());
+        }
+
+        bindings.0.push(binding);
+    }
+
+    let event = unsafe {
+        run_request(
+            rk,
+            rdsys::rd_kafka_admin_op_t::RD_KAFKA_ADMIN_OP_CREATEACLS,
+            timeout,
+            |options, queue| {
+                rdsys::rd_kafka_CreateAcls(
+                    rk,
+                    bindings.0.as_mut_ptr(),
+                    bindings.0.len(),
+                    options,
+                    queue,
+                )
+            },
+        )?
+    };
+
+    unsafe {
+        let result = rdsys::rd_kafka_event_CreateAcls_result(event.0);
+        let mut count = 0;
+        let results = rdsys::rd_kafka_CreateAcls_result_acls(result, &mut count);
+
+        for i in 0..count {
+            check_error(rdsys::rd_kafka_acl_result_error(*results.add(i)))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn describe_acls(
+    rk: *mut rdsys::rd_kafka_t,
+    filter: &AclBindingFilter,
+    timeout: Duration,
+) -> Result<Vec<AclBinding>, AdminError> {
+    let filter = Bindings(vec![new_filter(filter)?]);
+
+    let event = unsafe {
+        run_request(
+            rk,
+            rdsys::rd_kafka_admin_op_t::RD_KAFKA_ADMIN_OP_DESCRIBEACLS,
+            timeout,
+            |options, queue| rdsys::rd_kafka_DescribeAcls(rk, filter.0[0], options, queue),
+        )?
+    };
+
+    unsafe {
+        let result = rdsys::rd_kafka_event_DescribeAcls_result(event.0);
+        let mut count = 0;
+        let acls = rdsys::rd_kafka_DescribeAcls_result_acls(result, &mut count);
+
+        Ok(read_bindings(acls, count))
+    }
+}
+
+pub(super) fn delete_acls(
+    rk: *mut rdsys::rd_kafka_t,
+    filters: &[AclBindingFilter],
+    timeout: Duration,
+) -> Result<Vec<AclBinding>, AdminError> {
+    let mut raw = Bindings(Vec::with_capacity(filters.len()));
+    for filter in filters {
+        raw.0.push(new_filter(filter)?);
+    }
+
+    let event = unsafe {
+        run_request(
+            rk,
+            rdsys::rd_kafka_admin_op_t::RD_KAFKA_ADMIN_OP_DELETEACLS,
+            timeout,
+            |options, queue| {
+                rdsys::rd_kafka_DeleteAcls(rk, raw.0.as_mut_ptr(), raw.0.len(), options, queue)
+            },
+        )?
+    };
+
+    let mut deleted = Vec::new();
+
+    unsafe {
+        let result = rdsys::rd_kafka_event_DeleteAcls_result(event.0);
+        let mut count = 0;
+        let responses = rdsys::rd_kafka_DeleteAcls_result_responses(result, &mut count);
+
+        for i in 0..count {
+            let response = *responses.add(i);
+            check_error(rdsys::rd_kafka_DeleteAcls_result_response_error(response))?;
+
+            let mut matching = 0;
+            let acls =
+                rdsys::rd_kafka_DeleteAcls_result_response_matching_acls(response, &mut matching);
+
+            deleted.extend(read_bindings(acls, matching));
+        }
+    }
+
+    Ok(deleted)
+}
+
+fn new_filter(
+    filter: &AclBindingFilter,
+) -> Result<*mut rdsys::rd_kafka_AclBindingFilter_t, AdminError> {
+    let cstring = |x: Option<String>| x.map(CString::new).transpose().map_err(KafkaError::from);
+    let as_ptr = |x: &Option<CString>| x.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+    let name = cstring(filter.resource_name.clone())?;
+    let principal = cstring(filter.principal.as_ref().map(|x| x.to_string()))?;
+    let host = cstring(filter.host.clone())?;
+    let mut errstr = [0 as c_char; 512];
+
+    let raw = unsafe {
+        rdsys::rd_kafka_AclBindingFilter_new(
+            filter.resource_type.to_raw(),
+            as_ptr(&name),
+            filter.pattern_type.to_raw(),
+            as_ptr(&principal),
+            as_ptr(&host),
+            filter.operation.to_raw(),
+            filter.permission.to_raw(),
+            errstr.as_mut_ptr(),
+            errstr.len(),
+        )
+    };
+
+    if raw.is_null() {
+        return Err(creation_error(&errstr).into());
+    }
+
+    Ok(raw)
+}
+
+/// # Safety
+///
+/// `acls` must point to `count` bindings owned by a live result event.
+unsafe fn read_bindings(
+    acls: *mut *const rdsys::rd_kafka_AclBinding_t,
+    count: usize,
+) -> Vec<AclBinding> {
+    let string = |x: *const c_char| {
+        if x.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(x) }.to_string_lossy().into_owned()
+        }
+    };
+
+    (0..count)
+        .map(|i| unsafe {
+            let acl = *acls.add(i);
+            let principal = string(rdsys::rd_kafka_AclBinding_principal(acl));
+
+            AclBinding {
+                resource_type: AclResourceType::from_raw(rdsys::rd_kafka_AclBinding_restype(acl)),
+                resource_name: string(rdsys::rd_kafka_AclBinding_name(acl)),
+                pattern_type: AclPatternType::from_raw(
+                    rdsys::rd_kafka_AclBinding_resource_pattern_type(acl),
+                ),
+                principal: principal.parse().unwrap_or(Principal {
+                    kind: String::new(),
+                    name: principal,
+                }),
+                host: string(rdsys::rd_kafka_AclBinding_host(acl)),
+                operation: AclOperation::from_raw(rdsys::rd_kafka_AclBinding_operation(acl)),
+                permission: AclPermission::from_raw(rdsys::rd_kafka_AclBinding_permission_type(
+                    acl,
+                )),
+            }
+        })
+        .collect()
+}
+
+/// # Safety
+///
+/// `error` must be null or owned by a live result event.
+unsafe fn check_error(error: *const rdsys::rd_kafka_error_t) -> Result<(), AdminError> {
+    if error.is_null() {
+        return Ok(());
+    }
+
+    let code = unsafe { rdsys::rd_kafka_error_code(error) };
+    if code == rdsys::rd_kafka_resp_err_t::RD_KAFKA_RESP_ERR_NO_ERROR {
+        return Ok(());
+    }
+
+    let message = unsafe { CStr::from_ptr(rdsys::rd_kafka_error_string(error)) }
+        .to_string_lossy()
+        .into_owned();
+
+    Err(AdminError::Acl {
+        code: code.into(),
+        message,
+    })
+}
+
+fn creation_error(errstr: &[c_char]) -> KafkaError {
+    let message = unsafe { CStr::from_ptr(errstr.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+
+    KafkaError::AdminOpCreation(message)
+}
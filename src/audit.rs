@@ -0,0 +1,172 @@
+//! Audit trail of the messages produced and consumed by the clients, written as JSON to an
+//! audit topic for compliance pipelines recording who wrote what and when.
+
+use std::{convert::Infallible, time::Duration};
+
+use rdkafka::{
+    Message as _,
+    error::KafkaError,
+    message::{BorrowedMessage, Headers},
+    producer::{FutureProducer, FutureRecord, future_producer::Delivery},
+    util::AsyncRuntime,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    KafkaCallbackContext,
+    builder::KafkaBuilder,
+    config::Config,
+    error::Error,
+    interceptor::{ConsumerInterceptor, ProducerInterceptor, SentRecord, now_ms},
+    runtime::Runtime,
+};
+
+/// Whether an [`AuditRecord`] is about a produced or a consumed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditDirection {
+    Produced,
+    Consumed,
+}
+
+/// Metadata of a message written to the audit topic, its payload is left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub direction: AuditDirection,
+    pub topic: String,
+
+    /// Partition and offset of the message, unknown for failed deliveries.
+    pub partition: Option<i32>,
+    pub offset: Option<i64>,
+
+    /// Key and header values, decoded as lossy UTF-8.
+    pub key: Option<String>,
+    pub headers: Vec<(String, String)>,
+
+    /// Size of the payload in bytes.
+    pub size: usize,
+
+    /// The delivery error, `None` for delivered and consumed messages.
+    pub error: Option<String>,
+
+    /// How long the delivery took, `None` for consumed messages.
+    pub latency_ms: Option<u64>,
+
+    /// Milliseconds since the epoch at which the record was written.
+    pub ts_ms: i64,
+}
+
+/// Interceptor writing an [`AuditRecord`] for every message produced or consumed by the
+/// clients it is added to, see
+/// [`KafkaProducer::with_interceptor`](crate::producer::KafkaProducer::with_interceptor) and
+/// [`KafkaConsumer::with_interceptor`](crate::consumer::KafkaConsumer::with_interceptor).
+///
+/// Records are keyed by the audited topic and sent without waiting for their delivery, so
+/// the audited clients are not slowed down. Records that could not be delivered are logged.
+#[derive(Clone)]
+pub struct AuditTap {
+    producer: FutureProducer<KafkaCallbackContext, Runtime>,
+    topic: String,
+}
+
+impl AuditTap {
+    /// Creates a tap producing to `audit_topic` with its own client built from `config`.
+    pub fn new<S: Into<String>>(config: Config, audit_topic: S) -> Result<Self, Error<Infallible>> {
+        Ok(Self {
+            producer: KafkaBuilder::new(config).build_producer()?,
+            topic: audit_topic.into(),
+        })
+    }
+
+    fn publish(&self, record: &AuditRecord) {
+        let payload = match serde_json::to_vec(record) {
+            Ok(payload) => payload,
+            Err(err) => return warn_failed(&err),
+        };
+
+        let sent = self.producer.send_result(
+            FutureRecord::to(&self.topic)
+                .key(record.topic.as_bytes())
+                .payload(&payload),
+        );
+
+        match sent {
+            Ok(delivery) => Runtime::spawn(async move {
+                if let Ok(Err((err, _))) = delivery.await {
+                    warn_failed(&err);
+                }
+            }),
+            Err((err, _)) => warn_failed(&err),
+        }
+    }
+}
+
+impl ProducerInterceptor for AuditTap {
+    fn on_delivery(
+        &self,
+        record: &SentRecord<'_>,
+        result: Result<&Delivery, &KafkaError>,
+        elapsed: Duration,
+    ) {
+        // deliveries of the tap itself are not audited
+        if record.topic == self.topic {
+            return;
+        }
+
+        self.publish(&AuditRecord {
+            direction: AuditDirection::Produced,
+            topic: record.topic.to_string(),
+            partition: result.ok().map(|x| x.partition),
+            offset: result.ok().map(|x| x.offset),
+            key: record.key.map(|x| String::from_utf8_lossy(x).into_owned()),
+            headers: record.headers.map(header_values).unwrap_or_default(),
+            size: record.payload_len,
+            error: result.err().map(ToString::to_string),
+            latency_ms: Some(elapsed.as_millis() as u64),
+            ts_ms: now_ms(),
+        });
+    }
+}
+
+impl ConsumerInterceptor for AuditTap {
+    fn on_consume(&self, msg: &BorrowedMessage<'_>) {
+        self.publish(&AuditRecord {
+            direction: AuditDirection::Consumed,
+            topic: msg.topic().to_string(),
+            partition: Some(msg.partition()),
+            offset: Some(msg.offset()),
+            key: msg.key().map(|x| String::from_utf8_lossy(x).into_owned()),
+            headers: msg.headers().map(header_values).unwrap_or_default(),
+            size: msg.payload_len(),
+            error: None,
+            latency_ms: None,
+            ts_ms: now_ms(),
+        });
+    }
+}
+
+fn header_values<H: Headers>(headers: &H) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|hdr| {
+            let value = hdr.value.map(String::from_utf8_lossy).unwrap_or_default();
+            (hdr.key.to_string(), value.into_owned())
+        })
+        .collect()
+}
+
+fn warn_failed(err: &dyn std::fmt::Display) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("kafka audit record not delivered: {err}");
+
+    #[cfg(not(feature = "tracing"))]
+    log::warn!("kafka audit record not delivered: {err}");
+}
+
+impl std::fmt::Debug for AuditTap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditTap")
+            .field("topic", &self.topic)
+            .finish()
+    }
+}
@@ -1,16 +1,35 @@
+use std::{sync::Arc, time::Duration};
+
 use rdkafka::{
-    ClientConfig, config::RDKafkaLogLevel, consumer::StreamConsumer, error::KafkaError,
-    producer::FutureProducer,
+    ClientConfig,
+    admin::AdminClient,
+    config::RDKafkaLogLevel,
+    consumer::{BaseConsumer, StreamConsumer},
+    producer::{FutureProducer, ThreadedProducer},
 };
+use tokio::sync::watch;
 
 use crate::{
     KafkaCallbackContext,
-    config::{Config, KafkaLogLevel},
+    admin::KafkaAdmin,
+    config::{AutoOffsetReset, Config, KafkaLogLevel},
+    consumer::{AckTracker, RebalanceEvents},
+    error::{AdminError, ConfigError, Error},
+    interceptor::ConsumerInterceptor,
+    runtime::Runtime,
+    secret::SecretSource,
+    statistics::{Statistics, StatisticsReceiver},
 };
 
 #[derive(Debug, Clone)]
 pub(crate) struct KafkaBuilder {
-    inner: ClientConfig,
+    config: Config,
+    admin: ClientConfig,
+    consumer: ClientConfig,
+    producer: ClientConfig,
+    statistics: watch::Sender<Option<Arc<Statistics>>>,
+    interceptors: Vec<Arc<dyn ConsumerInterceptor>>,
+    defer_assignments: bool,
 }
 
 impl KafkaBuilder {
@@ -29,6 +48,14 @@ impl KafkaBuilder {
         builder.set("bootstrap.servers", brokers);
         builder.set("group.id", &config.group_id);
 
+        if let Some(client_id) = &config.client_id {
+            builder.set("client.id", client_id);
+        }
+
+        if let Some(rack) = &config.rack {
+            builder.set("client.rack", rack);
+        }
+
         if let Some(peof) = &config.partition_eof {
             builder.set("enable.partition.eof", if *peof { "true" } else { "false" });
         }
@@ -41,6 +68,29 @@ impl KafkaBuilder {
             builder.set("message.timeout.ms", message_timeout.get().to_string());
         }
 
+        if let Some(socket_timeout_ms) = &config.socket_timeout_ms {
+            builder.set("socket.timeout.ms", socket_timeout_ms.to_string());
+        }
+
+        if let Some(metadata_request_timeout_ms) = &config.metadata_request_timeout_ms {
+            builder.set(
+                "metadata.request.timeout.ms",
+                metadata_request_timeout_ms.to_string(),
+            );
+        }
+
+        if let Some(topic_metadata_refresh_interval_ms) = &config.topic_metadata_refresh_interval_ms
+        {
+            builder.set(
+                "topic.metadata.refresh.interval.ms",
+                topic_metadata_refresh_interval_ms.to_string(),
+            );
+        }
+
+        if let Some(statistics_interval_ms) = &config.statistics_interval_ms {
+            builder.set("statistics.interval.ms", statistics_interval_ms.to_string());
+        }
+
         if let Some(max_message_size) = &config.max_message_size {
             let max_message_size_kbytes = max_message_size / 1024;
             builder.set("message.max.bytes", max_message_size.to_string());
@@ -57,6 +107,35 @@ impl KafkaBuilder {
             );
         }
 
+        // committed by the consumer instead, see `ConfigBuilder::commit_every`
+        if config.commit_every.is_some() {
+            builder.set("enable.auto.commit", "false");
+        }
+
+        if let Some(security_protocol) = &config.security_protocol {
+            builder.set("security.protocol", security_protocol.to_string());
+        }
+
+        if let Some(sasl_mechanism) = &config.sasl_mechanism {
+            builder.set("sasl.mechanisms", sasl_mechanism.to_string());
+        }
+
+        if let Some(sasl_username) = &config.sasl_username {
+            builder.set("sasl.username", sasl_username);
+        }
+
+        if let Some(ssl_ca_location) = &config.ssl_ca_location {
+            builder.set("ssl.ca.location", ssl_ca_location);
+        }
+
+        if let Some(ssl_certificate_location) = &config.ssl_certificate_location {
+            builder.set("ssl.certificate.location", ssl_certificate_location);
+        }
+
+        if let Some(ssl_key_location) = &config.ssl_key_location {
+            builder.set("ssl.key.location", ssl_key_location);
+        }
+
         builder.set_log_level(match config.log_level {
             KafkaLogLevel::Critical => RDKafkaLogLevel::Critical,
             KafkaLogLevel::Error => RDKafkaLogLevel::Error,
@@ -65,20 +144,310 @@ impl KafkaBuilder {
             KafkaLogLevel::Debug => RDKafkaLogLevel::Debug,
         });
 
-        Self { inner: builder }
+        let admin = builder.clone();
+        let mut consumer = builder.clone();
+
+        consumer.set(
+            "auto.offset.reset",
+            match config.auto_offset_reset {
+                // librdkafka has no `none`, `error` fails the fetch instead of resetting
+                AutoOffsetReset::None => "error",
+                AutoOffsetReset::Latest => "latest",
+                AutoOffsetReset::Earliest => "earliest",
+            },
+        );
+
+        if let Some(fetch_min_bytes) = &config.fetch_min_bytes {
+            consumer.set("fetch.min.bytes", fetch_min_bytes.to_string());
+        }
+
+        if let Some(fetch_max_bytes) = &config.fetch_max_bytes {
+            consumer.set("fetch.max.bytes", fetch_max_bytes.to_string());
+        }
+
+        if let Some(max_partition_fetch_bytes) = &config.max_partition_fetch_bytes {
+            consumer.set(
+                "max.partition.fetch.bytes",
+                max_partition_fetch_bytes.to_string(),
+            );
+        }
+
+        if let Some(fetch_wait_max_ms) = &config.fetch_wait_max_ms {
+            consumer.set("fetch.wait.max.ms", fetch_wait_max_ms.to_string());
+        }
+
+        let mut producer = builder.clone();
+
+        if let Some(acks) = &config.acks {
+            producer.set("request.required.acks", acks.to_string());
+        }
+
+        if let Some(enable_idempotence) = &config.enable_idempotence {
+            producer.set(
+                "enable.idempotence",
+                if *enable_idempotence { "true" } else { "false" },
+            );
+        }
+
+        if let Some(compression) = &config.compression {
+            producer.set("compression.type", compression.to_string());
+        }
+
+        if let Some(compression_level) = &config.compression_level {
+            producer.set("compression.level", compression_level.to_string());
+        }
+
+        if let Some(queue_buffering_max_messages) = &config.queue_buffering_max_messages {
+            producer.set(
+                "queue.buffering.max.messages",
+                queue_buffering_max_messages.to_string(),
+            );
+        }
+
+        if let Some(queue_buffering_max_ms) = &config.queue_buffering_max_ms {
+            producer.set("queue.buffering.max.ms", queue_buffering_max_ms.to_string());
+        }
+
+        if let Some(linger_ms) = &config.linger_ms {
+            producer.set("linger.ms", linger_ms.to_string());
+        }
+
+        if let Some(batch_size) = &config.batch_size {
+            producer.set("batch.size", batch_size.to_string());
+        }
+
+        if let Some(batch_num_messages) = &config.batch_num_messages {
+            producer.set("batch.num.messages", batch_num_messages.to_string());
+        }
+
+        Self {
+            config,
+            admin,
+            consumer,
+            producer,
+            statistics: watch::Sender::new(None),
+            interceptors: Vec::new(),
+            defer_assignments: false,
+        }
     }
 
     #[inline]
-    pub(crate) fn build_consumer(
-        &self,
-    ) -> Result<StreamConsumer<KafkaCallbackContext>, KafkaError> {
-        self.inner.create_with_context(KafkaCallbackContext(()))
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
     }
 
+    /// The configuration of the consumers built by this builder, without the credentials.
+    #[cfg(test)]
     #[inline]
-    pub(crate) fn build_producer(
+    pub(crate) fn consumer_config(&self) -> &ClientConfig {
+        &self.consumer
+    }
+
+    /// Subscribes to the statistics of every client built by this builder.
+    #[inline]
+    pub(crate) fn statistics(&self) -> StatisticsReceiver {
+        self.statistics.subscribe()
+    }
+
+    /// Adds an interceptor to the consumers built by this builder.
+    #[inline]
+    pub(crate) fn add_interceptor(&mut self, interceptor: Arc<dyn ConsumerInterceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    /// Leaves the assignment of the consumers to the consumer reading its offsets from a store.
+    #[inline]
+    pub(crate) fn defer_assignments(&mut self) {
+        self.defer_assignments = true;
+    }
+
+    #[inline]
+    pub(crate) fn interceptors(&self) -> &[Arc<dyn ConsumerInterceptor>] {
+        &self.interceptors
+    }
+
+    fn context(&self) -> KafkaCallbackContext {
+        KafkaCallbackContext {
+            statistics: self.statistics.clone(),
+            on_error: self.config.on_client_error.clone(),
+            hooks: self.config.client_hooks.clone(),
+            interceptors: self.interceptors.clone(),
+            acks: self
+                .config
+                .commit_on_revoke
+                .map(|x| Arc::new(AckTracker::new(Duration::from_millis(x.into())))),
+            rebalance_events: RebalanceEvents::new(self.config.quiesce_on_rebalance),
+            offset_resets: self.config.topic_offset_reset.clone(),
+            deferred_assignments: self.defer_assignments.then(Default::default),
+            offset_reset_error: Default::default(),
+        }
+    }
+
+    /// Resolves the credentials only when a client is built so they never live in the builder.
+    fn with_secrets(&self, base: &ClientConfig) -> Result<ClientConfig, Vec<ConfigError>> {
+        let mut builder = base.clone();
+        let resolve = |secret: &SecretSource| secret.resolve().map_err(|err| vec![err]);
+
+        if let Some(sasl_password) = &self.config.sasl_password {
+            builder.set("sasl.password", resolve(sasl_password)?);
+        }
+
+        if let Some(ssl_key_password) = &self.config.ssl_key_password {
+            builder.set("ssl.key.password", resolve(ssl_key_password)?);
+        }
+
+        Ok(builder)
+    }
+
+    pub(crate) fn build_consumer<E>(
+        &self,
+    ) -> Result<StreamConsumer<KafkaCallbackContext, Runtime>, Error<E>> {
+        self.config
+            .validate_consumer()
+            .map_err(Error::InvalidConfig)?;
+
+        self.with_secrets(&self.consumer)
+            .map_err(Error::InvalidConfig)?
+            .create_with_context(self.context())
+            .map_err(Error::Connect)
+    }
+
+    pub(crate) fn build_base_consumer<E>(
+        &self,
+    ) -> Result<BaseConsumer<KafkaCallbackContext>, Error<E>> {
+        self.config
+            .validate_consumer()
+            .map_err(Error::InvalidConfig)?;
+
+        self.with_secrets(&self.consumer)
+            .map_err(Error::InvalidConfig)?
+            .create_with_context(self.context())
+            .map_err(Error::Connect)
+    }
+
+    pub(crate) fn build_producer<E>(
+        &self,
+    ) -> Result<FutureProducer<KafkaCallbackContext, Runtime>, Error<E>> {
+        self.config.validate().map_err(Error::InvalidConfig)?;
+
+        self.with_secrets(&self.producer)
+            .map_err(Error::InvalidConfig)?
+            .create_with_context(self.context())
+            .map_err(Error::Connect)
+    }
+
+    pub(crate) fn build_threaded_producer<E>(
         &self,
-    ) -> Result<FutureProducer<KafkaCallbackContext>, KafkaError> {
-        self.inner.create_with_context(KafkaCallbackContext(()))
+    ) -> Result<ThreadedProducer<KafkaCallbackContext>, Error<E>> {
+        self.config.validate().map_err(Error::InvalidConfig)?;
+
+        self.with_secrets(&self.producer)
+            .map_err(Error::InvalidConfig)?
+            .create_with_context(self.context())
+            .map_err(Error::Connect)
+    }
+
+    pub(crate) fn build_admin(&self) -> Result<AdminClient<KafkaCallbackContext>, AdminError> {
+        self.config.validate().map_err(AdminError::InvalidConfig)?;
+
+        Ok(self
+            .with_secrets(&self.admin)
+            .map_err(AdminError::InvalidConfig)?
+            .create_with_context(self.context())?)
+    }
+
+    /// Creates the missing `topics` when [`Config::ensure_topics`] is set.
+    pub(crate) async fn ensure_topics(&self, topics: &[&str]) -> Result<(), AdminError> {
+        let Some(spec) = &self.config.ensure_topics else {
+            return Ok(());
+        };
+
+        KafkaAdmin::from_builder(self.clone())?
+            .ensure_topics(topics, spec)
+            .await
+    }
+
+    /// Checks that every topic of `topics` exists with at least `min_partitions` partitions,
+    /// regex subscriptions (`^...`) are skipped.
+    pub(crate) async fn preflight<E>(
+        &self,
+        topics: &[&str],
+        min_partitions: usize,
+    ) -> Result<(), Error<E>> {
+        let counts = KafkaAdmin::from_builder(self.clone())?
+            .partition_counts()
+            .await?;
+
+        let required = min_partitions.max(1);
+
+        for topic in topics.iter().filter(|topic| !topic.starts_with('^')) {
+            let found = counts.get(*topic).copied().unwrap_or(0);
+
+            if found < required {
+                return Err(Error::TopicMissing {
+                    topic: topic.to_string(),
+                    found,
+                    required,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a consumer of another group used by the admin client to read and commit
+    /// offsets, it never subscribes nor commits on its own.
+    pub(crate) fn build_group_consumer(
+        &self,
+        group_id: &str,
+    ) -> Result<BaseConsumer<KafkaCallbackContext>, AdminError> {
+        self.config.validate().map_err(AdminError::InvalidConfig)?;
+
+        let mut builder = self
+            .with_secrets(&self.consumer)
+            .map_err(AdminError::InvalidConfig)?;
+
+        builder.set("group.id", group_id);
+        builder.set("enable.auto.commit", "false");
+
+        Ok(builder.create_with_context(self.context())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto_offset_reset(reset: AutoOffsetReset) -> Option<String> {
+        let config = Config::builder().auto_offset_reset(reset).build();
+
+        KafkaBuilder::new(config)
+            .consumer_config()
+            .get("auto.offset.reset")
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn consumer_config_sets_auto_offset_reset() {
+        assert_eq!(
+            auto_offset_reset(AutoOffsetReset::Earliest).as_deref(),
+            Some("earliest")
+        );
+        assert_eq!(
+            auto_offset_reset(AutoOffsetReset::Latest).as_deref(),
+            Some("latest")
+        );
+        assert_eq!(
+            auto_offset_reset(AutoOffsetReset::None).as_deref(),
+            Some("error")
+        );
+    }
+
+    #[test]
+    fn auto_offset_reset_is_consumer_only() {
+        let builder = KafkaBuilder::new(Config::default());
+
+        assert_eq!(builder.producer.get("auto.offset.reset"), None);
+        assert_eq!(builder.admin.get("auto.offset.reset"), None);
     }
 }
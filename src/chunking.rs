@@ -0,0 +1,486 @@
+use std::collections::{HashMap, VecDeque};
+
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "flowly")]
+use flowly::Service;
+#[cfg(feature = "flowly")]
+use futures::Stream;
+
+use crate::{KafkaMessage, Message, codec::Encoder, config::Config};
+
+/// Header holding the id shared by all chunks of a payload.
+pub const CHUNK_ID_HEADER: &str = "flowly.chunk.id";
+
+/// Header holding the zero based index of a chunk.
+pub const CHUNK_INDEX_HEADER: &str = "flowly.chunk.index";
+
+/// Header holding the number of chunks of a payload.
+pub const CHUNK_COUNT_HEADER: &str = "flowly.chunk.count";
+
+/// Room left in every chunk for the key, headers and the record overhead.
+const CHUNK_OVERHEAD: usize = 4096;
+
+/// Most chunks a payload can be split into, bounds what a corrupt count header allocates.
+pub const MAX_CHUNKS: usize = 4096;
+
+/// Splits encoded payloads larger than the chunk size into numbered chunk messages.
+///
+/// Every chunk keeps the key (so all of them land on the same partition and stay ordered),
+/// the timestamp and the headers of the original message, plus the `flowly.chunk.*` headers
+/// used by [`Reassembler`]. Payloads that fit are passed through as a single message
+/// without chunk headers.
+pub struct Chunker<E> {
+    encoder: E,
+    chunk_size: usize,
+    buffer: BytesMut,
+}
+
+impl<E> Chunker<E> {
+    /// Creates a chunker splitting payloads into chunks of at most `chunk_size` bytes.
+    pub fn new(encoder: E, chunk_size: usize) -> Self {
+        Self {
+            encoder,
+            chunk_size: chunk_size.max(1),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Creates a chunker sized to fit the `max_message_size` of `config`.
+    pub fn from_config(encoder: E, config: &Config) -> Self {
+        let max_message_size = config
+            .max_message_size
+            .or(Config::default_max_message_size())
+            .unwrap_or_default() as usize;
+
+        Self::new(encoder, max_message_size.saturating_sub(CHUNK_OVERHEAD))
+    }
+
+    /// Encodes the value of `msg` and splits it into messages ready to be produced.
+    pub fn split<M>(&mut self, msg: &M) -> Result<Vec<Message<Bytes, M::Key>>, E::Error>
+    where
+        M: KafkaMessage,
+        E: Encoder<M::Value>,
+    {
+        let headers = msg.headers().map(|x| x.to_vec());
+
+        let Some(value) = msg.value() else {
+            return Ok(vec![Message {
+                key: msg.key(),
+                ts_ms_utc: msg.ts_ms_utc(),
+                payload: None,
+                partition: -1,
+                headers,
+            }]);
+        };
+
+        self.buffer.clear();
+        self.encoder.encode(value, &mut self.buffer)?;
+        let payload = self.buffer.split().freeze();
+
+        if payload.len() <= self.chunk_size {
+            return Ok(vec![Message {
+                key: msg.key(),
+                ts_ms_utc: msg.ts_ms_utc(),
+                payload: Some(payload),
+                partition: -1,
+                headers,
+            }]);
+        }
+
+        let id = crate::unique_id();
+        let count = payload.len().div_ceil(self.chunk_size);
+
+        Ok((0..count)
+            .map(|index| {
+                let start = index * self.chunk_size;
+                let end = (start + self.chunk_size).min(payload.len());
+
+                let mut headers = headers.clone().unwrap_or_default();
+                headers.push((CHUNK_ID_HEADER.to_string(), id.clone().into_bytes()));
+                headers.push((
+                    CHUNK_INDEX_HEADER.to_string(),
+                    index.to_string().into_bytes(),
+                ));
+                headers.push((
+                    CHUNK_COUNT_HEADER.to_string(),
+                    count.to_string().into_bytes(),
+                ));
+
+                Message {
+                    key: msg.key(),
+                    ts_ms_utc: msg.ts_ms_utc(),
+                    payload: Some(payload.slice(start..end)),
+                    partition: -1,
+                    headers: Some(headers),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E> Service<M> for Chunker<E>
+where
+    M: KafkaMessage + Send,
+    M::Key: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+{
+    type Out = Result<Message<Bytes, M::Key>, E::Error>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        let chunks = match self.split(&input) {
+            Ok(chunks) => chunks.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+
+        futures::stream::iter(chunks)
+    }
+}
+
+struct Pending {
+    chunks: Vec<Option<Bytes>>,
+    received: usize,
+}
+
+/// Buffers the chunks produced by [`Chunker`] and re-emits the original payload once all of
+/// them have arrived. Messages without chunk headers are passed through unchanged, so the
+/// consumer has to be configured with `decode_headers` enabled.
+///
+/// At most `max_pending` payloads are buffered at a time, the oldest incomplete one is
+/// dropped when a new one would exceed the limit. Chunks announcing more than [`MAX_CHUNKS`]
+/// chunks are dropped, as are the payloads whose chunks disagree on their count.
+pub struct Reassembler {
+    pending: HashMap<String, Pending>,
+    order: VecDeque<String>,
+    max_pending: usize,
+}
+
+impl Reassembler {
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            pending: HashMap::new(),
+            order: VecDeque::new(),
+            max_pending: max_pending.max(1),
+        }
+    }
+
+    /// Adds a consumed message, returning the reassembled message once it is complete.
+    pub fn push<K>(&mut self, mut msg: Message<Bytes, K>) -> Option<Message<Bytes, K>> {
+        let Some((id, index, count)) = msg.headers.as_deref().and_then(chunk_headers) else {
+            return Some(msg);
+        };
+
+        if index >= count {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(chunk.id = %id, chunk.index = index, chunk.count = count, "dropping out of range chunk");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("dropping chunk {index} of `{id}` with only {count} chunks");
+            return None;
+        }
+
+        if count > MAX_CHUNKS {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(chunk.id = %id, chunk.count = count, "dropping chunk over the chunk limit");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!(
+                "dropping chunk of `{id}` with {count} chunks, over the limit of {MAX_CHUNKS}"
+            );
+            return None;
+        }
+
+        if self
+            .pending
+            .get(&id)
+            .is_some_and(|pending| pending.chunks.len() != count)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(chunk.id = %id, chunk.count = count, "dropping chunked message with mismatching chunk counts");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("dropping chunked message `{id}` with mismatching chunk counts");
+            self.pending.remove(&id);
+            self.order.retain(|x| x != &id);
+            return None;
+        }
+
+        if !self.pending.contains_key(&id) {
+            while self.order.len() >= self.max_pending {
+                if let Some(evicted) = self.order.pop_front() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(chunk.id = %evicted, "dropping incomplete chunked message");
+
+                    #[cfg(not(feature = "tracing"))]
+                    log::warn!("dropping incomplete chunked message `{evicted}`");
+                    self.pending.remove(&evicted);
+                }
+            }
+
+            self.order.push_back(id.clone());
+            self.pending.insert(
+                id.clone(),
+                Pending {
+                    chunks: vec![None; count],
+                    received: 0,
+                },
+            );
+        }
+
+        let pending = self.pending.get_mut(&id)?;
+        let slot = pending.chunks.get_mut(index)?;
+
+        if slot.is_none() {
+            *slot = Some(msg.payload.take().unwrap_or_default());
+            pending.received += 1;
+        }
+
+        if pending.received < pending.chunks.len() {
+            return None;
+        }
+
+        let pending = self.pending.remove(&id)?;
+        self.order.retain(|x| x != &id);
+
+        let mut payload = BytesMut::new();
+        for chunk in pending.chunks.into_iter().flatten() {
+            payload.extend_from_slice(&chunk);
+        }
+
+        if let Some(headers) = &mut msg.headers {
+            headers.retain(|(k, _)| {
+                k != CHUNK_ID_HEADER && k != CHUNK_INDEX_HEADER && k != CHUNK_COUNT_HEADER
+            });
+        }
+
+        msg.payload = Some(payload.freeze());
+
+        Some(msg)
+    }
+
+    /// Number of incomplete payloads currently buffered.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<K, E> Service<Result<Message<Bytes, K>, E>> for Reassembler
+where
+    K: Send,
+    E: Send,
+{
+    type Out = Result<Message<Bytes, K>, E>;
+
+    fn handle(
+        &mut self,
+        input: Result<Message<Bytes, K>, E>,
+        _cx: &flowly::Context,
+    ) -> impl Stream<Item = Self::Out> + Send {
+        let out = match input {
+            Ok(msg) => self.push(msg).map(Ok),
+            Err(err) => Some(Err(err)),
+        };
+
+        futures::stream::iter(out)
+    }
+}
+
+fn chunk_headers(headers: &[(String, Vec<u8>)]) -> Option<(String, usize, usize)> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| std::str::from_utf8(v).ok())
+    };
+
+    Some((
+        header(CHUNK_ID_HEADER)?.to_string(),
+        header(CHUNK_INDEX_HEADER)?.parse().ok()?,
+        header(CHUNK_COUNT_HEADER)?.parse().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::RawEncoder;
+
+    const PAYLOAD: &[u8] = b"a payload split into many chunks";
+
+    fn chunks(payload: &'static [u8], chunk_size: usize) -> Vec<Message<Bytes>> {
+        let msg = Message {
+            key: Some(Bytes::from_static(b"key")),
+            payload: Some(Bytes::from_static(payload)),
+            headers: Some(vec![("tenant".to_string(), b"acme".to_vec())]),
+            ..Default::default()
+        };
+
+        Chunker::new(RawEncoder, chunk_size).split(&msg).unwrap()
+    }
+
+    fn reassemble(
+        reassembler: &mut Reassembler,
+        chunks: impl IntoIterator<Item = Message<Bytes>>,
+    ) -> Vec<Message<Bytes>> {
+        chunks
+            .into_iter()
+            .filter_map(|chunk| reassembler.push(chunk))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_fit_the_chunk_size() {
+        let chunks = chunks(PAYLOAD, 5);
+
+        assert_eq!(chunks.len(), PAYLOAD.len().div_ceil(5));
+        assert!(
+            chunks
+                .iter()
+                .all(|x| x.payload.as_ref().unwrap().len() <= 5)
+        );
+        assert!(chunks.iter().all(|x| x.key.as_deref() == Some(&b"key"[..])));
+    }
+
+    #[test]
+    fn payload_within_the_chunk_size_is_not_chunked() {
+        let chunks = chunks(PAYLOAD, PAYLOAD.len());
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].payload.as_deref(), Some(PAYLOAD));
+        assert_eq!(
+            chunks[0].headers,
+            Some(vec![("tenant".to_string(), b"acme".to_vec())])
+        );
+    }
+
+    #[test]
+    fn reassembles_chunks_in_order() {
+        let mut reassembler = Reassembler::default();
+        let out = reassemble(&mut reassembler, chunks(PAYLOAD, 5));
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].payload.as_deref(), Some(PAYLOAD));
+        assert_eq!(
+            out[0].headers,
+            Some(vec![("tenant".to_string(), b"acme".to_vec())])
+        );
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn reassembles_chunks_out_of_order() {
+        let mut chunks = chunks(PAYLOAD, 5);
+        chunks.reverse();
+        chunks.swap(1, 3);
+
+        let out = reassemble(&mut Reassembler::default(), chunks);
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].payload.as_deref(), Some(PAYLOAD));
+    }
+
+    #[test]
+    fn duplicated_chunks_are_ignored() {
+        let chunks = chunks(PAYLOAD, 5);
+        let duplicated = chunks[..2].iter().cloned().chain(chunks.iter().cloned());
+
+        let out = reassemble(&mut Reassembler::default(), duplicated);
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].payload.as_deref(), Some(PAYLOAD));
+    }
+
+    #[test]
+    fn missing_chunks_keep_the_payload_pending() {
+        let mut chunks = chunks(PAYLOAD, 5);
+        chunks.remove(2);
+
+        let mut reassembler = Reassembler::default();
+
+        assert!(reassemble(&mut reassembler, chunks).is_empty());
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    fn set_header(chunk: &mut Message<Bytes>, name: &str, value: usize) {
+        for (key, header) in chunk.headers.as_mut().unwrap() {
+            if key == name {
+                *header = value.to_string().into_bytes();
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_chunks_are_dropped() {
+        let mut chunk = chunks(PAYLOAD, 5).remove(0);
+        set_header(&mut chunk, CHUNK_INDEX_HEADER, 99);
+
+        let mut reassembler = Reassembler::default();
+
+        assert!(reassembler.push(chunk).is_none());
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn chunks_over_the_chunk_limit_are_dropped() {
+        let mut chunk = chunks(PAYLOAD, 5).remove(0);
+        set_header(&mut chunk, CHUNK_COUNT_HEADER, usize::MAX);
+
+        let mut reassembler = Reassembler::default();
+
+        assert!(reassembler.push(chunk).is_none());
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn mismatching_chunk_counts_drop_the_payload() {
+        let mut chunks = chunks(PAYLOAD, 5);
+        let count = chunks.len();
+        set_header(&mut chunks[1], CHUNK_COUNT_HEADER, count + 1);
+
+        let mut reassembler = Reassembler::default();
+
+        assert!(reassembler.push(chunks[0].clone()).is_none());
+        assert!(reassembler.push(chunks[1].clone()).is_none());
+        assert_eq!(reassembler.pending(), 0);
+
+        // the rest starts over and never completes without the dropped chunks
+        assert!(reassemble(&mut reassembler, chunks[2..].iter().cloned()).is_empty());
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    #[test]
+    fn oldest_pending_payload_is_evicted_over_the_limit() {
+        let first = chunks(PAYLOAD, 5);
+        let second = chunks(PAYLOAD, 5);
+        let mut reassembler = Reassembler::new(1);
+
+        assert!(reassembler.push(first[0].clone()).is_none());
+        assert_eq!(reassemble(&mut reassembler, second).len(), 1);
+
+        // the first chunk of the evicted payload is gone for good
+        assert!(reassemble(&mut reassembler, first[1..].iter().cloned()).is_empty());
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    #[test]
+    fn messages_without_chunk_headers_pass_through() {
+        let msg: Message<Bytes> = Message {
+            key: None,
+            ts_ms_utc: None,
+            payload: Some(Bytes::from_static(PAYLOAD)),
+            partition: 0,
+            headers: None,
+        };
+
+        assert_eq!(Reassembler::new(1).push(msg.clone()), Some(msg));
+    }
+}
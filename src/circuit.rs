@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+use crate::{config::CircuitBreakerConfig, error::Error};
+
+/// State of the circuit breaker of a consumer or producer.
+#[derive(Debug, Clone)]
+pub(crate) struct CircuitBreaker {
+    config: Option<CircuitBreakerConfig>,
+    failures: u32,
+
+    /// Set while the circuit is open and kept once the cooldown elapsed (half-open), so
+    /// that the next failure opens it again right away.
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: Option<CircuitBreakerConfig>) -> Self {
+        Self {
+            config,
+            failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Fails with [`Error::CircuitOpen`] while the cooldown has not elapsed.
+    pub(crate) fn check<E>(&self) -> Result<(), Error<E>> {
+        match self.open_until {
+            Some(until) if until > Instant::now() => Err(Error::CircuitOpen {
+                retry_after: until - Instant::now(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the outcome of a broker operation, codec errors do not count.
+    pub(crate) fn record<T, E>(&mut self, result: &Result<T, Error<E>>) {
+        match result.as_ref().map_err(Error::root) {
+            Ok(..) => self.record_success(),
+            Err(
+                Error::KafkaError(..)
+                | Error::Connect(..)
+                | Error::Subscribe { .. }
+                | Error::Receive(..)
+                | Error::Deliver { .. }
+                | Error::AdminError(..),
+            ) => self.record_failure(),
+            Err(..) => (),
+        }
+    }
+
+    pub(crate) fn record_success(&mut self) {
+        self.failures = 0;
+        self.open_until = None;
+    }
+
+    pub(crate) fn record_failure(&mut self) {
+        let Some(config) = &self.config else {
+            return;
+        };
+
+        self.failures += 1;
+
+        if self.open_until.is_some() || self.failures >= config.failure_threshold {
+            self.failures = 0;
+            self.open_until =
+                Some(Instant::now() + Duration::from_millis(config.cooldown_ms.into()));
+        }
+    }
+}
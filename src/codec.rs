@@ -0,0 +1,45 @@
+#[cfg(feature = "avro")]
+pub mod avro;
+#[cfg(feature = "bincode")]
+pub mod bincode;
+pub mod claim_check;
+#[cfg(feature = "json")]
+pub mod debezium;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+pub mod func;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
+pub mod key;
+pub mod owned;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(not(feature = "flowly"))]
+mod traits;
+#[cfg(feature = "schema-registry")]
+pub(crate) mod wire;
+
+#[cfg(feature = "avro")]
+pub use avro::{AvroDecoder, AvroEncoder};
+#[cfg(feature = "bincode")]
+pub use bincode::{BincodeDecoder, BincodeEncoder};
+pub use claim_check::{BlobStore, ClaimCheck, FsBlobStore};
+#[cfg(feature = "json")]
+pub use debezium::{ChangeEvent, DebeziumDecoder};
+#[cfg(feature = "encryption")]
+pub use encrypted::{Encrypted, KeyProvider, StaticKeyProvider};
+#[cfg(feature = "flowly")]
+pub use flowly::{BytesDecoder, Decoder, Encoder, Reader, Writer};
+pub use func::{FnDecoder, FnEncoder};
+#[cfg(feature = "json")]
+pub use json::{JsonDecoder, JsonEncoder};
+#[cfg(feature = "json-schema")]
+pub use json_schema::JsonSchemaCodec;
+pub use key::{BigEndianEncoder, RawEncoder};
+pub use owned::OwnedEncoder;
+#[cfg(feature = "protobuf")]
+pub use protobuf::{ProtoDecoder, ProtoEncoder};
+#[cfg(not(feature = "flowly"))]
+pub use traits::{BytesDecoder, Decoder, Encoder, Reader, Writer};
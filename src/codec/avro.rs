@@ -0,0 +1,145 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use apache_avro::{from_avro_datum, from_value, to_avro_datum, to_value};
+use bytes::Buf;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    codec::wire,
+    error::AvroCodecError,
+    schema_registry::{self, Schema, SchemaRegistryClient, SchemaType, SubjectSchema},
+};
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Encodes values with an Avro schema in the Confluent wire format
+/// (`magic byte + schema id + Avro datum`).
+///
+/// The schema id is resolved on the first `encode`, call [`AvroEncoder::register`] beforehand
+/// to avoid blocking the runtime on the registry request.
+pub struct AvroEncoder<T> {
+    subject: SubjectSchema,
+    schema: apache_avro::Schema,
+    _m: PhantomData<fn(&T)>,
+}
+
+impl<T> AvroEncoder<T> {
+    /// Creates an encoder writing `schema` under `subject`.
+    pub fn new<S: Into<String>>(
+        registry: SchemaRegistryClient,
+        subject: S,
+        schema: &str,
+    ) -> Result<Self, AvroCodecError> {
+        Ok(Self {
+            schema: apache_avro::Schema::parse_str(schema)?,
+            subject: SubjectSchema::new(
+                registry,
+                subject.into(),
+                Schema::new(SchemaType::Avro, schema),
+            ),
+            _m: PhantomData,
+        })
+    }
+
+    /// Creates an encoder for the value subject of `topic` (`<topic>-value`).
+    pub fn for_topic(
+        registry: SchemaRegistryClient,
+        topic: &str,
+        schema: &str,
+    ) -> Result<Self, AvroCodecError> {
+        Self::new(registry, format!("{topic}-value"), schema)
+    }
+
+    /// Only looks the schema up instead of registering it when it is unknown to the registry.
+    pub fn auto_register(mut self, auto_register: bool) -> Self {
+        self.subject.auto_register = auto_register;
+        self
+    }
+
+    /// Registers (or looks up) the schema and remembers its id.
+    pub async fn register(&mut self) -> Result<u32, AvroCodecError> {
+        Ok(self.subject.resolve().await?)
+    }
+}
+
+impl<T: Serialize> Encoder<T> for AvroEncoder<T> {
+    type Error = AvroCodecError;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        let id = self.subject.resolve_blocking()?;
+        let value = to_value(item)?.resolve(&self.schema)?;
+        let datum = to_avro_datum(&self.schema, value)?;
+
+        wire::write_header(writer, id);
+        writer.put_slice(&datum);
+
+        Ok(())
+    }
+}
+
+/// Decodes Confluent wire format Avro payloads, fetching writer schemas from the registry.
+///
+/// Unknown schema ids are fetched synchronously, which requires a multi-threaded tokio
+/// runtime unless the registry cache already holds them.
+pub struct AvroDecoder<T> {
+    registry: SchemaRegistryClient,
+    reader_schema: Option<apache_avro::Schema>,
+    schemas: HashMap<u32, apache_avro::Schema>,
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> AvroDecoder<T> {
+    pub fn new(registry: SchemaRegistryClient) -> Self {
+        Self {
+            registry,
+            reader_schema: None,
+            schemas: HashMap::new(),
+            _m: PhantomData,
+        }
+    }
+
+    /// Resolves every payload into `schema` (Avro schema evolution) instead of decoding it
+    /// with the writer schema as is.
+    pub fn with_reader_schema(mut self, schema: &str) -> Result<Self, AvroCodecError> {
+        self.reader_schema = Some(apache_avro::Schema::parse_str(schema)?);
+        Ok(self)
+    }
+
+    fn load_writer_schema(&mut self, id: u32) -> Result<(), AvroCodecError> {
+        if !self.schemas.contains_key(&id) {
+            let schema = match self.registry.cached(id) {
+                Some(schema) => schema,
+                None => {
+                    let registry = self.registry.clone();
+                    schema_registry::block_on(async move { registry.schema_by_id(id).await })??
+                }
+            };
+
+            if schema.schema_type != SchemaType::Avro {
+                return Err(AvroCodecError::NotAvro(id));
+            }
+
+            self.schemas
+                .insert(id, apache_avro::Schema::parse_str(&schema.schema)?);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for AvroDecoder<T> {
+    type Error = AvroCodecError;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        let id = wire::read_header(reader)?;
+        self.load_writer_schema(id)?;
+
+        let value = from_avro_datum(
+            &self.schemas[&id],
+            &mut reader.reader(),
+            self.reader_schema.as_ref(),
+        )?;
+
+        Ok(from_value(&value)?)
+    }
+}
@@ -0,0 +1,110 @@
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::error::BincodeCodecError;
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Encodes values with bincode (standard configuration), prefixed by a single version byte.
+///
+/// Bincode payloads carry no field names or types, so bump the version whenever the
+/// layout of `T` changes; decoders built for another version reject the payload instead
+/// of decoding garbage.
+pub struct BincodeEncoder<T> {
+    version: u8,
+    _m: PhantomData<fn(&T)>,
+}
+
+impl<T> BincodeEncoder<T> {
+    /// Creates an encoder writing `version` as the header byte.
+    #[inline]
+    pub fn new(version: u8) -> Self {
+        Self {
+            version,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BincodeEncoder<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<T> Clone for BincodeEncoder<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.version)
+    }
+}
+
+impl<T: Serialize> Encoder<T> for BincodeEncoder<T> {
+    type Error = BincodeCodecError;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        writer.put_u8(self.version);
+        bincode::serde::encode_into_std_write(
+            item,
+            &mut writer.writer(),
+            bincode::config::standard(),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Decodes payloads written by [`BincodeEncoder`] with the same version.
+pub struct BincodeDecoder<T> {
+    version: u8,
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> BincodeDecoder<T> {
+    /// Creates a decoder accepting only payloads with the `version` header byte.
+    #[inline]
+    pub fn new(version: u8) -> Self {
+        Self {
+            version,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BincodeDecoder<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<T> Clone for BincodeDecoder<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.version)
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for BincodeDecoder<T> {
+    type Error = BincodeCodecError;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        if !reader.has_remaining() {
+            return Err(BincodeCodecError::Truncated);
+        }
+
+        let version = reader.get_u8();
+        if version != self.version {
+            return Err(BincodeCodecError::VersionMismatch {
+                expected: self.version,
+                found: version,
+            });
+        }
+
+        Ok(bincode::serde::decode_from_std_read(
+            &mut reader.reader(),
+            bincode::config::standard(),
+        )?)
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bytes::{Buf, BytesMut};
+
+use crate::error::ClaimCheckError;
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Envelope tag of a payload stored inline.
+const INLINE: u8 = 0;
+
+/// Envelope tag of a payload replaced by a blob store reference.
+const REFERENCE: u8 = 1;
+
+/// Storage of the payloads offloaded by [`ClaimCheck`], e.g. S3, GCS or a shared filesystem.
+///
+/// Both methods are called from the codec, so implementations backed by async clients
+/// have to block on them (e.g. with `tokio::task::block_in_place`).
+pub trait BlobStore {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Stores `data`, returning the reference written into the message.
+    fn put(&self, data: &[u8]) -> Result<String, Self::Error>;
+
+    /// Loads the data stored under `reference`.
+    fn get(&self, reference: &str) -> Result<Vec<u8>, Self::Error>;
+}
+
+/// Stores blobs as files of a directory, usually a mount shared by producers and consumers.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, reference: &str) -> io::Result<PathBuf> {
+        // references come from consumed messages, never let them escape the root
+        let name = Path::new(reference);
+        if name.components().count() != 1 || name.file_name().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid blob reference `{reference}`"),
+            ));
+        }
+
+        Ok(self.root.join(name))
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    type Error = io::Error;
+
+    fn put(&self, data: &[u8]) -> Result<String, Self::Error> {
+        let reference = crate::unique_id();
+        std::fs::write(self.path(&reference)?, data)?;
+
+        Ok(reference)
+    }
+
+    fn get(&self, reference: &str) -> Result<Vec<u8>, Self::Error> {
+        std::fs::read(self.path(reference)?)
+    }
+}
+
+/// Offloads payloads larger than a threshold to a [`BlobStore`], producing only a reference
+/// to them; decoding resolves references transparently before running the inner decoder.
+///
+/// Every payload is prefixed with a tag byte telling whether it is stored inline or by
+/// reference, so both sides have to use the codec.
+#[derive(Debug, Clone)]
+pub struct ClaimCheck<E, S: BlobStore> {
+    inner: E,
+    store: S,
+    threshold: usize,
+    buffer: BytesMut,
+}
+
+impl<E, S: BlobStore> ClaimCheck<E, S> {
+    /// Creates a codec offloading encoded payloads longer than `threshold` bytes to `store`.
+    pub fn new(inner: E, store: S, threshold: usize) -> Self {
+        Self {
+            inner,
+            store,
+            threshold,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    #[inline]
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+impl<T, E: Encoder<T>, S: BlobStore> Encoder<T> for ClaimCheck<E, S> {
+    type Error = ClaimCheckError<E::Error>;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        self.buffer.clear();
+        self.inner
+            .encode(item, &mut self.buffer)
+            .map_err(ClaimCheckError::Codec)?;
+
+        if self.buffer.len() > self.threshold {
+            let reference = self
+                .store
+                .put(&self.buffer)
+                .map_err(|err| ClaimCheckError::Store(Box::new(err)))?;
+
+            writer.put_u8(REFERENCE);
+            writer.put_slice(reference.as_bytes());
+        } else {
+            writer.put_u8(INLINE);
+            writer.put_slice(&self.buffer);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, D: Decoder<T>, S: BlobStore> Decoder<T> for ClaimCheck<D, S> {
+    type Error = ClaimCheckError<D::Error>;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        if !reader.has_remaining() {
+            return Err(ClaimCheckError::InvalidEnvelope);
+        }
+
+        match reader.get_u8() {
+            INLINE => self.inner.decode(reader).map_err(ClaimCheckError::Codec),
+            REFERENCE => {
+                let reference = reader.copy_to_bytes(reader.remaining());
+                let reference = std::str::from_utf8(&reference)
+                    .map_err(|_| ClaimCheckError::InvalidEnvelope)?;
+
+                let data = self
+                    .store
+                    .get(reference)
+                    .map_err(|err| ClaimCheckError::Store(Box::new(err)))?;
+
+                self.inner
+                    .decode(&mut data.as_slice())
+                    .map_err(ClaimCheckError::Codec)
+            }
+            _ => Err(ClaimCheckError::InvalidEnvelope),
+        }
+    }
+}
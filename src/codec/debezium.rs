@@ -0,0 +1,168 @@
+use std::marker::PhantomData;
+
+use bytes::Buf;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::error::DebeziumError;
+
+use super::{Decoder, Reader};
+
+/// The `source` block of a Debezium envelope, only the fields common to all connectors.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Source {
+    pub connector: Option<String>,
+    pub name: Option<String>,
+    pub ts_ms: Option<i64>,
+    pub db: Option<String>,
+    pub schema: Option<String>,
+    pub table: Option<String>,
+}
+
+/// A Debezium change event envelope as written by the connectors.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Envelope<T> {
+    #[serde(default = "Option::default")]
+    pub before: Option<T>,
+    #[serde(default = "Option::default")]
+    pub after: Option<T>,
+    pub op: String,
+    #[serde(default)]
+    pub source: Option<Source>,
+    #[serde(default)]
+    pub ts_ms: Option<i64>,
+}
+
+/// A row change captured by Debezium.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent<T> {
+    /// A row was created (`op = c`).
+    Insert(T),
+
+    /// A row was updated (`op = u`), `before` is only present with full row images.
+    Update { before: Option<T>, after: T },
+
+    /// A row was deleted (`op = d`), `before` is only present with full row images.
+    Delete(Option<T>),
+
+    /// A row was read while snapshotting the table (`op = r`).
+    Snapshot(T),
+
+    /// The table was truncated (`op = t`).
+    Truncate,
+}
+
+impl<T> ChangeEvent<T> {
+    /// Returns the state of the row after the change, `None` for deletes and truncates.
+    pub fn after(&self) -> Option<&T> {
+        match self {
+            ChangeEvent::Insert(row)
+            | ChangeEvent::Snapshot(row)
+            | ChangeEvent::Update { after: row, .. } => Some(row),
+            ChangeEvent::Delete(..) | ChangeEvent::Truncate => None,
+        }
+    }
+
+    fn from_envelope<E>(envelope: Envelope<T>) -> Result<Self, DebeziumError<E>> {
+        let after = |op| envelope.after.ok_or(DebeziumError::MissingAfter(op));
+
+        Ok(match envelope.op.as_str() {
+            "c" => ChangeEvent::Insert(after("c")?),
+            "r" => ChangeEvent::Snapshot(after("r")?),
+            "u" => ChangeEvent::Update {
+                before: envelope.before,
+                after: after("u")?,
+            },
+            "d" => ChangeEvent::Delete(envelope.before),
+            "t" => ChangeEvent::Truncate,
+            _ => return Err(DebeziumError::UnsupportedOp(envelope.op)),
+        })
+    }
+}
+
+impl<T> TryFrom<Envelope<T>> for ChangeEvent<T> {
+    type Error = DebeziumError<std::convert::Infallible>;
+
+    #[inline]
+    fn try_from(envelope: Envelope<T>) -> Result<Self, Self::Error> {
+        Self::from_envelope(envelope)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEnvelope<T> {
+    WithSchema { payload: Envelope<T> },
+    Plain(Envelope<T>),
+}
+
+/// Decodes Debezium JSON envelopes, both with (`schemas.enable=true`) and without the
+/// embedded schema.
+pub struct JsonEnvelopeDecoder<T> {
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for JsonEnvelopeDecoder<T> {
+    fn default() -> Self {
+        Self { _m: PhantomData }
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<Envelope<T>> for JsonEnvelopeDecoder<T> {
+    type Error = serde_json::Error;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<Envelope<T>, Self::Error> {
+        Ok(match serde_json::from_reader(reader.reader())? {
+            JsonEnvelope::WithSchema { payload } => payload,
+            JsonEnvelope::Plain(envelope) => envelope,
+        })
+    }
+}
+
+/// Unwraps Debezium change event envelopes decoded by `D` into [`ChangeEvent`]s.
+///
+/// Tombstones following deletes have no payload and never reach the decoder.
+pub struct DebeziumDecoder<T, D = JsonEnvelopeDecoder<T>> {
+    inner: D,
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> DebeziumDecoder<T> {
+    /// Creates a decoder of JSON envelopes.
+    pub fn json() -> Self {
+        Self::new(JsonEnvelopeDecoder::default())
+    }
+}
+
+#[cfg(feature = "avro")]
+impl<T> DebeziumDecoder<T, crate::codec::AvroDecoder<Envelope<T>>> {
+    /// Creates a decoder of Avro envelopes written with the Confluent Avro converter.
+    pub fn avro(registry: crate::schema_registry::SchemaRegistryClient) -> Self {
+        Self::new(crate::codec::AvroDecoder::new(registry))
+    }
+}
+
+impl<T, D> DebeziumDecoder<T, D> {
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Default for DebeziumDecoder<T> {
+    fn default() -> Self {
+        Self::json()
+    }
+}
+
+impl<T, D: Decoder<Envelope<T>>> Decoder<ChangeEvent<T>> for DebeziumDecoder<T, D> {
+    type Error = DebeziumError<D::Error>;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<ChangeEvent<T>, Self::Error> {
+        let envelope = self.inner.decode(reader).map_err(DebeziumError::Codec)?;
+
+        ChangeEvent::from_envelope(envelope)
+    }
+}
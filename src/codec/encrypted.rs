@@ -0,0 +1,265 @@
+use std::{collections::HashMap, sync::Arc};
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload},
+};
+use bytes::{Buf, BytesMut};
+
+use crate::error::EncryptionError;
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Length of the AES-GCM nonce written after the key id.
+const NONCE_LEN: usize = 12;
+
+pub type KeyProviderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Source of the AES-256 data keys of [`Encrypted`], e.g. a static key or a KMS client
+/// caching unwrapped data keys.
+pub trait KeyProvider {
+    /// Returns the id and the key new payloads are encrypted with.
+    fn current_key(&self) -> Result<(String, [u8; 32]), KeyProviderError>;
+
+    /// Returns the key with `key_id`, or `None` when it is unknown.
+    fn key(&self, key_id: &str) -> Result<Option<[u8; 32]>, KeyProviderError>;
+}
+
+/// A fixed set of keys, encrypting with one of them and decrypting with any,
+/// which allows rotating keys without breaking consumers of older payloads.
+#[derive(Clone)]
+pub struct StaticKeyProvider {
+    current: String,
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl StaticKeyProvider {
+    /// Creates a provider encrypting with `key` identified by `key_id`.
+    pub fn new<S: Into<String>>(key_id: S, key: [u8; 32]) -> Self {
+        let current = key_id.into();
+
+        Self {
+            keys: HashMap::from([(current.clone(), key)]),
+            current,
+        }
+    }
+
+    /// Adds a key only used for decryption, e.g. the previous one after a rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - The id written into the payloads encrypted with the key.
+    /// * `key` - The AES-256 key.
+    ///
+    /// # Returns
+    ///
+    /// The modified provider with the key added.
+    pub fn with_key<S: Into<String>>(mut self, key_id: S, key: [u8; 32]) -> Self {
+        self.keys.entry(key_id.into()).or_insert(key);
+        self
+    }
+}
+
+impl std::fmt::Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticKeyProvider")
+            .field("current", &self.current)
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key(&self) -> Result<(String, [u8; 32]), KeyProviderError> {
+        Ok((self.current.clone(), self.keys[&self.current]))
+    }
+
+    fn key(&self, key_id: &str) -> Result<Option<[u8; 32]>, KeyProviderError> {
+        Ok(self.keys.get(key_id).copied())
+    }
+}
+
+/// Encrypts the payloads of the wrapped codec with AES-256-GCM.
+///
+/// Payloads are written as `key id length (u8) + key id + nonce (12 bytes) + ciphertext`,
+/// the key id is authenticated as associated data.
+#[derive(Clone)]
+pub struct Encrypted<C> {
+    inner: C,
+    keys: Arc<dyn KeyProvider + Send + Sync>,
+    buffer: BytesMut,
+}
+
+impl<C> Encrypted<C> {
+    pub fn new<P>(inner: C, keys: P) -> Self
+    where
+        P: KeyProvider + Send + Sync + 'static,
+    {
+        Self {
+            inner,
+            keys: Arc::new(keys),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<T, E: Encoder<T>> Encoder<T> for Encrypted<E> {
+    type Error = EncryptionError<E::Error>;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        self.buffer.clear();
+        self.inner
+            .encode(item, &mut self.buffer)
+            .map_err(EncryptionError::Codec)?;
+
+        let (key_id, key) = self
+            .keys
+            .current_key()
+            .map_err(EncryptionError::KeyProvider)?;
+
+        let key_id_len: u8 = key_id
+            .len()
+            .try_into()
+            .map_err(|_| EncryptionError::KeyIdTooLong(key_id.clone()))?;
+
+        let cipher = Aes256Gcm::new(&key.into());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &self.buffer,
+                    aad: key_id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Encrypt)?;
+
+        writer.put_u8(key_id_len);
+        writer.put_slice(key_id.as_bytes());
+        writer.put_slice(&nonce);
+        writer.put_slice(&ciphertext);
+
+        Ok(())
+    }
+}
+
+impl<T, D: Decoder<T>> Decoder<T> for Encrypted<D> {
+    type Error = EncryptionError<D::Error>;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        if !reader.has_remaining() {
+            return Err(EncryptionError::Truncated);
+        }
+
+        let key_id_len = reader.get_u8() as usize;
+        if reader.remaining() < key_id_len + NONCE_LEN {
+            return Err(EncryptionError::Truncated);
+        }
+
+        let mut key_id = vec![0; key_id_len];
+        reader.copy_to_slice(&mut key_id);
+        let key_id = String::from_utf8_lossy(&key_id).into_owned();
+
+        let mut nonce = [0; NONCE_LEN];
+        reader.copy_to_slice(&mut nonce);
+
+        let ciphertext = reader.copy_to_bytes(reader.remaining());
+
+        let key = self
+            .keys
+            .key(&key_id)
+            .map_err(EncryptionError::KeyProvider)?
+            .ok_or(EncryptionError::UnknownKey(key_id.clone()))?;
+
+        let plaintext = Aes256Gcm::new(&key.into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: key_id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Decrypt)?;
+
+        self.inner
+            .decode(&mut plaintext.as_slice())
+            .map_err(EncryptionError::Codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::codec::{BytesDecoder, RawEncoder};
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn keys() -> StaticKeyProvider {
+        StaticKeyProvider::new("k1", KEY).with_key("k2", KEY)
+    }
+
+    fn encrypt(payload: &[u8]) -> BytesMut {
+        let mut encoder = Encrypted::new(RawEncoder, keys());
+        let mut buf = BytesMut::new();
+        encoder.encode(&payload.to_vec(), &mut buf).unwrap();
+        buf
+    }
+
+    fn decrypt(payload: &[u8]) -> Result<Bytes, EncryptionError<impl std::fmt::Debug>> {
+        Encrypted::new(BytesDecoder, keys()).decode(&mut &payload[..])
+    }
+
+    #[test]
+    fn round_trip() {
+        let payload = encrypt(b"hello");
+
+        assert_eq!(payload[0], 2);
+        assert_eq!(&payload[1..3], b"k1");
+        assert!(!payload.windows(5).any(|x| x == b"hello"));
+        assert_eq!(decrypt(&payload).unwrap(), Bytes::from_static(b"hello"));
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let mut payload = encrypt(b"hello");
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+
+        assert!(matches!(decrypt(&payload), Err(EncryptionError::Decrypt)));
+    }
+
+    #[test]
+    fn tampered_key_id_is_rejected() {
+        // both ids map to the same key, only the associated data differs
+        let mut payload = encrypt(b"hello");
+        payload[2] = b'2';
+
+        assert!(matches!(decrypt(&payload), Err(EncryptionError::Decrypt)));
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        let mut payload = encrypt(b"hello");
+        payload[2] = b'3';
+
+        assert!(matches!(decrypt(&payload), Err(EncryptionError::UnknownKey(id)) if id == "k3"));
+    }
+
+    #[test]
+    fn truncated_payload_is_rejected() {
+        let payload = encrypt(b"hello");
+
+        assert!(matches!(decrypt(&[]), Err(EncryptionError::Truncated)));
+        assert!(matches!(
+            decrypt(&payload[..8]),
+            Err(EncryptionError::Truncated)
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+use std::marker::PhantomData;
+
+use bytes::Buf;
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Decodes with a plain function of the bytes, e.g. `FnDecoder::new(|buf| parse(buf))`.
+pub struct FnDecoder<F, E> {
+    f: F,
+    _m: PhantomData<fn() -> E>,
+}
+
+impl<F, E> FnDecoder<F, E> {
+    #[inline]
+    pub fn new(f: F) -> Self {
+        Self { f, _m: PhantomData }
+    }
+}
+
+impl<F: Clone, E> Clone for FnDecoder<F, E> {
+    fn clone(&self) -> Self {
+        Self::new(self.f.clone())
+    }
+}
+
+impl<T, E, F> Decoder<T> for FnDecoder<F, E>
+where
+    F: FnMut(&[u8]) -> Result<T, E>,
+{
+    type Error = E;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        let remaining = reader.remaining();
+
+        // payloads are usually contiguous, only a chained reader is copied
+        if reader.chunk().len() == remaining {
+            let decoded = (self.f)(reader.chunk());
+            reader.advance(remaining);
+            return decoded;
+        }
+
+        let bytes = reader.copy_to_bytes(remaining);
+        (self.f)(&bytes)
+    }
+}
+
+/// Encodes with a plain function returning the bytes, e.g.
+/// `FnEncoder::new(|x: &Order| serialize(x))`.
+pub struct FnEncoder<F, E> {
+    f: F,
+    _m: PhantomData<fn() -> E>,
+}
+
+impl<F, E> FnEncoder<F, E> {
+    #[inline]
+    pub fn new(f: F) -> Self {
+        Self { f, _m: PhantomData }
+    }
+}
+
+impl<F: Clone, E> Clone for FnEncoder<F, E> {
+    fn clone(&self) -> Self {
+        Self::new(self.f.clone())
+    }
+}
+
+impl<T, B, E, F> Encoder<T> for FnEncoder<F, E>
+where
+    F: FnMut(&T) -> Result<B, E>,
+    B: AsRef<[u8]>,
+{
+    type Error = E;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        writer.put_slice((self.f)(item)?.as_ref());
+        Ok(())
+    }
+}
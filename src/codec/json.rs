@@ -0,0 +1,98 @@
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::{Decoder, Encoder, OwnedEncoder, Reader, Writer};
+
+/// Encodes values as plain JSON payloads.
+pub struct JsonEncoder<T> {
+    pretty: bool,
+    _m: PhantomData<fn(&T)>,
+}
+
+impl<T> JsonEncoder<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            pretty: false,
+            _m: PhantomData,
+        }
+    }
+
+    /// Writes indented JSON, mostly useful for topics read by humans.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+}
+
+impl<T> Default for JsonEncoder<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for JsonEncoder<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pretty: self.pretty,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for JsonEncoder<T> {
+    type Error = serde_json::Error;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        if self.pretty {
+            serde_json::to_writer_pretty(writer.writer(), item)
+        } else {
+            serde_json::to_writer(writer.writer(), item)
+        }
+    }
+}
+
+impl<T: Serialize> OwnedEncoder<T> for JsonEncoder<T> {
+    type Error = serde_json::Error;
+
+    fn encode_owned(&mut self, item: T, dst: &mut BytesMut) -> Result<Bytes, Self::Error> {
+        self.encode(&item, dst)?;
+        Ok(dst.split().freeze())
+    }
+}
+
+/// Decodes plain JSON payloads.
+pub struct JsonDecoder<T> {
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonDecoder<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self { _m: PhantomData }
+    }
+}
+
+impl<T> Default for JsonDecoder<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for JsonDecoder<T> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for JsonDecoder<T> {
+    type Error = serde_json::Error;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        serde_json::from_reader(reader.reader())
+    }
+}
@@ -0,0 +1,121 @@
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    codec::wire,
+    error::JsonSchemaCodecError,
+    schema_registry::{Schema, SchemaRegistryClient, SchemaType, SubjectSchema},
+};
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Serializes values as JSON in the Confluent wire format, validating every payload
+/// against a JSON Schema registered under the subject before it is produced.
+///
+/// Decoding validates against the same schema only when enabled with
+/// [`JsonSchemaCodec::validate_on_decode`].
+pub struct JsonSchemaCodec<T> {
+    subject: SubjectSchema,
+    validator: jsonschema::Validator,
+    validate_on_decode: bool,
+    _m: PhantomData<fn(&T) -> T>,
+}
+
+impl<T> JsonSchemaCodec<T> {
+    /// Creates a codec writing `schema` under `subject`.
+    pub fn new<S: Into<String>>(
+        registry: SchemaRegistryClient,
+        subject: S,
+        schema: &str,
+    ) -> Result<Self, JsonSchemaCodecError> {
+        let value: serde_json::Value = serde_json::from_str(schema)?;
+        let validator = jsonschema::validator_for(&value)
+            .map_err(|err| JsonSchemaCodecError::InvalidSchema(err.to_string()))?;
+
+        Ok(Self {
+            subject: SubjectSchema::new(
+                registry,
+                subject.into(),
+                Schema::new(SchemaType::Json, schema),
+            ),
+            validator,
+            validate_on_decode: false,
+            _m: PhantomData,
+        })
+    }
+
+    /// Creates a codec for the value subject of `topic` (`<topic>-value`).
+    pub fn for_topic(
+        registry: SchemaRegistryClient,
+        topic: &str,
+        schema: &str,
+    ) -> Result<Self, JsonSchemaCodecError> {
+        Self::new(registry, format!("{topic}-value"), schema)
+    }
+
+    /// Also validates consumed payloads against the schema.
+    pub fn validate_on_decode(mut self, validate_on_decode: bool) -> Self {
+        self.validate_on_decode = validate_on_decode;
+        self
+    }
+
+    /// Only looks the schema up instead of registering it when it is unknown to the registry.
+    pub fn auto_register(mut self, auto_register: bool) -> Self {
+        self.subject.auto_register = auto_register;
+        self
+    }
+
+    /// Registers (or looks up) the schema and remembers its id.
+    pub async fn register(&mut self) -> Result<u32, JsonSchemaCodecError> {
+        Ok(self.subject.resolve().await?)
+    }
+
+    fn validate(&self, value: &serde_json::Value) -> Result<(), JsonSchemaCodecError> {
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(value)
+            .map(|err| err.to_string())
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(JsonSchemaCodecError::Invalid(errors))
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for JsonSchemaCodec<T> {
+    type Error = JsonSchemaCodecError;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        let value = serde_json::to_value(item)?;
+        self.validate(&value)?;
+
+        let id = self.subject.resolve_blocking()?;
+
+        wire::write_header(writer, id);
+        serde_json::to_writer(writer.writer(), &value)?;
+
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Decoder<T> for JsonSchemaCodec<T> {
+    type Error = JsonSchemaCodecError;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        wire::read_header(reader)?;
+
+        if self.validate_on_decode {
+            let value: serde_json::Value = serde_json::from_reader(reader.reader())?;
+            self.validate(&value)?;
+
+            Ok(serde_json::from_value(value)?)
+        } else {
+            Ok(serde_json::from_reader(reader.reader())?)
+        }
+    }
+}
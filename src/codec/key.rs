@@ -0,0 +1,45 @@
+use std::convert::Infallible;
+
+use super::{Encoder, Writer};
+
+/// Writes anything viewable as bytes as is, the default key encoder of the producer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawEncoder;
+
+impl<T: AsRef<[u8]>> Encoder<T> for RawEncoder {
+    type Error = Infallible;
+
+    #[inline]
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        writer.put_slice(item.as_ref());
+        Ok(())
+    }
+}
+
+/// Writes integers in big-endian byte order like the Java `IntegerSerializer` and
+/// `LongSerializer`, so keys hash to the same partitions as Java producers when the
+/// `murmur2_random` partitioner is used.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BigEndianEncoder;
+
+macro_rules! impl_big_endian {
+    ($($ty:ty),*) => {
+        $(
+            impl Encoder<$ty> for BigEndianEncoder {
+                type Error = Infallible;
+
+                #[inline]
+                fn encode<W: Writer>(
+                    &mut self,
+                    item: &$ty,
+                    writer: &mut W,
+                ) -> Result<(), Self::Error> {
+                    writer.put_slice(&item.to_be_bytes());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_big_endian!(u16, i16, u32, i32, u64, i64, u128, i128);
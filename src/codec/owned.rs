@@ -0,0 +1,25 @@
+use std::convert::Infallible;
+
+use bytes::{Bytes, BytesMut};
+
+use super::RawEncoder;
+
+/// Encoder taking values by move, used by
+/// [`KafkaProducer::send_owned`](crate::producer::KafkaProducer::send_owned).
+///
+/// Values holding their bytes already, e.g. `Bytes` or `Vec<u8>`, can be returned as the
+/// payload without a copy, others are written to `dst` and split off it.
+pub trait OwnedEncoder<T> {
+    type Error;
+
+    fn encode_owned(&mut self, item: T, dst: &mut BytesMut) -> Result<Bytes, Self::Error>;
+}
+
+impl<T: Into<Bytes>> OwnedEncoder<T> for RawEncoder {
+    type Error = Infallible;
+
+    #[inline]
+    fn encode_owned(&mut self, item: T, _dst: &mut BytesMut) -> Result<Bytes, Self::Error> {
+        Ok(item.into())
+    }
+}
@@ -0,0 +1,102 @@
+use std::marker::PhantomData;
+
+use crate::{
+    codec::wire,
+    error::ProtoCodecError,
+    schema_registry::{Schema, SchemaRegistryClient, SchemaType, SubjectSchema},
+};
+
+use super::{Decoder, Encoder, Reader, Writer};
+
+/// Encodes prost messages in the Confluent Protobuf wire format
+/// (`magic byte + schema id + message indexes + message`).
+///
+/// The `.proto` source of the message is registered under the subject on the first `encode`,
+/// call [`ProtoEncoder::register`] beforehand to avoid blocking the runtime.
+pub struct ProtoEncoder<T> {
+    subject: SubjectSchema,
+    message_indexes: Vec<i32>,
+    _m: PhantomData<fn(&T)>,
+}
+
+impl<T> ProtoEncoder<T> {
+    /// Creates an encoder writing the first message of the `.proto` source `schema` under `subject`.
+    pub fn new<S: Into<String>>(registry: SchemaRegistryClient, subject: S, schema: &str) -> Self {
+        Self {
+            subject: SubjectSchema::new(
+                registry,
+                subject.into(),
+                Schema::new(SchemaType::Protobuf, schema),
+            ),
+            message_indexes: vec![0],
+            _m: PhantomData,
+        }
+    }
+
+    /// Creates an encoder for the value subject of `topic` (`<topic>-value`).
+    pub fn for_topic(registry: SchemaRegistryClient, topic: &str, schema: &str) -> Self {
+        Self::new(registry, format!("{topic}-value"), schema)
+    }
+
+    /// Sets the path of `T` inside the `.proto` source, e.g. `[1, 0]` for the first nested
+    /// message of the second top-level message.
+    pub fn message_indexes(mut self, message_indexes: Vec<i32>) -> Self {
+        self.message_indexes = message_indexes;
+        self
+    }
+
+    /// Only looks the schema up instead of registering it when it is unknown to the registry.
+    pub fn auto_register(mut self, auto_register: bool) -> Self {
+        self.subject.auto_register = auto_register;
+        self
+    }
+
+    /// Registers (or looks up) the schema and remembers its id.
+    pub async fn register(&mut self) -> Result<u32, ProtoCodecError> {
+        Ok(self.subject.resolve().await?)
+    }
+}
+
+impl<T: prost::Message> Encoder<T> for ProtoEncoder<T> {
+    type Error = ProtoCodecError;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error> {
+        let id = self.subject.resolve_blocking()?;
+
+        wire::write_header(writer, id);
+        wire::write_message_indexes(writer, &self.message_indexes);
+        item.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+/// Decodes Confluent wire format Protobuf payloads into a prost message.
+///
+/// The schema id and message indexes are only skipped, `T` defines how the message is read.
+pub struct ProtoDecoder<T> {
+    _m: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for ProtoDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ProtoDecoder<T> {
+    pub fn new() -> Self {
+        Self { _m: PhantomData }
+    }
+}
+
+impl<T: prost::Message + Default> Decoder<T> for ProtoDecoder<T> {
+    type Error = ProtoCodecError;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error> {
+        wire::read_header(reader)?;
+        wire::read_message_indexes(reader)?;
+
+        Ok(T::decode(reader)?)
+    }
+}
@@ -0,0 +1,50 @@
+//! The codec traits of flowly, for builds without the `flowly` feature.
+
+use std::convert::Infallible;
+
+use bytes::{Buf, BufMut, Bytes};
+
+/// Source of the bytes read by a [`Decoder`], implemented for every [`Buf`].
+pub trait Reader: Buf {}
+
+impl<T: Buf> Reader for T {}
+
+/// Destination of the bytes written by an [`Encoder`], implemented for every [`BufMut`].
+pub trait Writer: BufMut {}
+
+impl<T: BufMut> Writer for T {}
+
+/// Decodes a value from the bytes of a payload or key.
+pub trait Decoder<T> {
+    type Error;
+
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<T, Self::Error>;
+}
+
+/// Encodes a value into the bytes of a payload or key.
+pub trait Encoder<T> {
+    type Error;
+
+    fn encode<W: Writer>(&mut self, item: &T, writer: &mut W) -> Result<(), Self::Error>;
+
+    fn size_hint() -> (usize, Option<usize>) {
+        (0, None)
+    }
+
+    fn estimate_size(&self, _: &T) -> usize {
+        Self::size_hint().0
+    }
+}
+
+/// Returns the bytes as is, the default decoder of the consumers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BytesDecoder;
+
+impl Decoder<Bytes> for BytesDecoder {
+    type Error = Infallible;
+
+    #[inline]
+    fn decode<R: Reader>(&mut self, reader: &mut R) -> Result<Bytes, Self::Error> {
+        Ok(reader.copy_to_bytes(reader.remaining()))
+    }
+}
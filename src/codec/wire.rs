@@ -0,0 +1,86 @@
+use bytes::{Buf, BufMut};
+
+use crate::error::WireFormatError;
+
+/// First byte of every payload framed for the schema registry.
+pub(crate) const MAGIC_BYTE: u8 = 0;
+
+/// Writes the `magic byte + big-endian schema id` header.
+pub(crate) fn write_header(dst: &mut impl BufMut, schema_id: u32) {
+    dst.put_u8(MAGIC_BYTE);
+    dst.put_u32(schema_id);
+}
+
+/// Reads the `magic byte + big-endian schema id` header, returning the schema id.
+pub(crate) fn read_header(buf: &mut impl Buf) -> Result<u32, WireFormatError> {
+    if buf.remaining() < 5 {
+        return Err(WireFormatError::Truncated);
+    }
+
+    match buf.get_u8() {
+        MAGIC_BYTE => Ok(buf.get_u32()),
+        magic => Err(WireFormatError::UnknownMagicByte(magic)),
+    }
+}
+
+/// Writes the message index path of the Confluent Protobuf framing as zigzag varints,
+/// the common `[0]` path is written as a single `0`.
+#[cfg(feature = "protobuf")]
+pub(crate) fn write_message_indexes(dst: &mut impl BufMut, indexes: &[i32]) {
+    if indexes == [0] {
+        write_varint(dst, 0);
+        return;
+    }
+
+    write_varint(dst, indexes.len() as i32);
+    for index in indexes {
+        write_varint(dst, *index);
+    }
+}
+
+/// Reads the message index path of the Confluent Protobuf framing.
+#[cfg(feature = "protobuf")]
+pub(crate) fn read_message_indexes(buf: &mut impl Buf) -> Result<Vec<i32>, WireFormatError> {
+    let count = read_varint(buf)?;
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+
+    if count < 0 || count as usize > buf.remaining() {
+        return Err(WireFormatError::InvalidMessageIndexes);
+    }
+
+    (0..count).map(|_| read_varint(buf)).collect()
+}
+
+#[cfg(feature = "protobuf")]
+fn write_varint(dst: &mut impl BufMut, value: i32) {
+    let mut value = ((value << 1) ^ (value >> 31)) as u32;
+
+    while value >= 0x80 {
+        dst.put_u8((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    dst.put_u8(value as u8);
+}
+
+#[cfg(feature = "protobuf")]
+fn read_varint(buf: &mut impl Buf) -> Result<i32, WireFormatError> {
+    let mut value = 0u32;
+
+    for shift in (0..35).step_by(7) {
+        if !buf.has_remaining() {
+            return Err(WireFormatError::Truncated);
+        }
+
+        let byte = buf.get_u8();
+        value |= ((byte & 0x7f) as u32) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(((value >> 1) as i32) ^ -((value & 1) as i32));
+        }
+    }
+
+    Err(WireFormatError::InvalidMessageIndexes)
+}
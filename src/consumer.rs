@@ -1,26 +1,209 @@
-use std::marker::PhantomData;
+use std::{
+    fmt,
+    marker::PhantomData,
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
 use bytes::Bytes;
-use flowly::{Decoder, Service};
+#[cfg(feature = "flowly")]
+use flowly::Service;
 
-use futures::Stream;
+use futures::{
+    Stream, StreamExt,
+    future::{self, Either},
+};
 use rdkafka::{
-    Message as _,
-    consumer::{Consumer, stream_consumer::StreamConsumer},
-    error::KafkaError,
-    message::Headers as _,
+    Message as _, TopicPartitionList,
+    consumer::{CommitMode, Consumer, ConsumerGroupMetadata, stream_consumer::StreamConsumer},
+    error::{KafkaError, KafkaResult},
+    message::{BorrowedMessage, Headers as _},
+    types::RDKafkaErrorCode,
+};
+
+use crate::{
+    KafkaCallbackContext, Message, blocking,
+    builder::KafkaBuilder,
+    circuit::CircuitBreaker,
+    codec::Decoder,
+    config::{BackoffPolicy, Config},
+    error::{ConfigError, DecodeFailure, Error},
+    health::{HEALTH_CHECK_TIMEOUT, Health, consumer_lag},
+    interceptor::ConsumerInterceptor,
+    offset_store::{OffsetStore, StoreCursor},
+    rate_limit::RateLimiter,
+    runtime::Runtime,
+    statistics::StatisticsReceiver,
 };
 
-use crate::{KafkaCallbackContext, Message, builder::KafkaBuilder, config::Config, error::Error};
+mod ack;
+mod commit;
+mod group;
+mod multi_cluster;
+mod polling;
+mod rebalance;
+mod shared;
+
+pub use ack::AckHandle;
+pub(crate) use ack::AckTracker;
+use commit::{CommitBatcher, FINAL_COMMIT_TIMEOUT_MS};
+pub use group::GroupMember;
+pub use multi_cluster::{ClusterMessage, MultiClusterConsumer};
+pub use polling::PollingConsumer;
+pub use rebalance::Event;
+pub(crate) use rebalance::{RebalanceEvent, RebalanceEvents};
+pub(crate) use shared::SharedMessage;
+
+/// Longest wait for a message of [`KafkaConsumer::events`] before it returns the rebalances
+/// that happened meanwhile.
+pub const REBALANCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Decides what the consumer [`Service`] does with the [`Error::Receive`] errors of
+/// `recv`, other errors are always yielded.
+#[derive(Clone)]
+pub enum ErrorPolicy {
+    /// Rebuild the client on the errors matched by the predicate, counted against
+    /// `reconnect_count`, other errors are yielded.
+    ReconnectOn(Arc<dyn Fn(&KafkaError) -> bool + Send + Sync>),
 
-pub struct KafkaConsumer<M = Bytes, D: Decoder<M> = flowly::BytesDecoder> {
+    /// Yield every error and keep consuming.
+    YieldAndContinue,
+
+    /// Yield the first error and end the stream.
+    FailStream,
+}
+
+impl ErrorPolicy {
+    /// Rebuilds the client on the errors matched by `matcher`.
+    pub fn reconnect_on<F>(matcher: F) -> Self
+    where
+        F: Fn(&KafkaError) -> bool + Send + Sync + 'static,
+    {
+        ErrorPolicy::ReconnectOn(Arc::new(matcher))
+    }
+}
+
+impl Default for ErrorPolicy {
+    /// Rebuilds the client on fatal transaction errors only.
+    fn default() -> Self {
+        ErrorPolicy::reconnect_on(|err| matches!(err, KafkaError::Transaction(e) if e.is_fatal()))
+    }
+}
+
+impl fmt::Debug for ErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorPolicy::ReconnectOn(..) => f.write_str("ReconnectOn(..)"),
+            ErrorPolicy::YieldAndContinue => f.write_str("YieldAndContinue"),
+            ErrorPolicy::FailStream => f.write_str("FailStream"),
+        }
+    }
+}
+
+/// Waits for the next message, assigning the partitions deferred to the offset store
+/// meanwhile since librdkafka fetches nothing from them until then.
+async fn recv_assigned<'a, E>(
+    consumer: &'a StreamConsumer<KafkaCallbackContext, Runtime>,
+    cursor: Option<&StoreCursor>,
+) -> Result<KafkaResult<BorrowedMessage<'a>>, Error<E>> {
+    let (Some(cursor), Some(deferred)) = (cursor, &consumer.context().deferred_assignments) else {
+        return Ok(consumer.recv().await);
+    };
+
+    loop {
+        cursor.assign(consumer).await?;
+
+        match future::select(pin!(consumer.recv()), pin!(deferred.changed())).await {
+            Either::Left((received, _)) => return Ok(received),
+            Either::Right(..) => continue,
+        }
+    }
+}
+
+/// Commits the consumed offsets of the client when dropped with the consumer, see
+/// [`ConfigBuilder::commit_on_drop`](crate::config::ConfigBuilder::commit_on_drop).
+struct CommitOnDrop {
+    consumer: Option<Arc<StreamConsumer<KafkaCallbackContext, Runtime>>>,
+    timeout: Option<Duration>,
+}
+
+impl CommitOnDrop {
+    fn new(timeout_ms: Option<u32>) -> Self {
+        Self {
+            consumer: None,
+            timeout: timeout_ms.map(|x| Duration::from_millis(x.into())),
+        }
+    }
+}
+
+impl Drop for CommitOnDrop {
+    fn drop(&mut self) {
+        let (Some(consumer), Some(timeout)) = (self.consumer.take(), self.timeout) else {
+            return;
+        };
+
+        // committed from another thread, a drop can not wait for longer than `timeout`. The
+        // thread owns the client until librdkafka gives up on the commit, so a timed out
+        // commit lingers in the background and its outcome is only logged.
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(late) = tx.send(consumer.commit_consumer_state(CommitMode::Sync)) {
+                log_commit_failure(late.0, " after timing out");
+            }
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => log_commit_failure(result, ""),
+            Err(..) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    "kafka commit on drop timed out after {timeout:?}, still committing in the background"
+                );
+
+                #[cfg(not(feature = "tracing"))]
+                log::warn!(
+                    "kafka commit on drop timed out after {timeout:?}, still committing in the background"
+                );
+            }
+        }
+    }
+}
+
+fn log_commit_failure(result: KafkaResult<()>, context: &str) {
+    match result {
+        Ok(()) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {}
+        Err(err) => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka commit on drop failed: {err}{context}");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka commit on drop failed: {err}{context}");
+        }
+    }
+}
+
+pub struct KafkaConsumer<
+    M = Bytes,
+    D: Decoder<M> = crate::codec::BytesDecoder,
+    K = Bytes,
+    KD: Decoder<K> = crate::codec::BytesDecoder,
+> {
     builder: KafkaBuilder,
     decoder: D,
-    inner: Option<StreamConsumer<KafkaCallbackContext>>,
+    key_decoder: KD,
+    inner: Option<Arc<StreamConsumer<KafkaCallbackContext, Runtime>>>,
+    last_recv: Option<SystemTime>,
     reconnect_count: u32,
-    reconnect_sleep_ms: u32,
+    backoff: BackoffPolicy,
+    breaker: CircuitBreaker,
+    limiter: RateLimiter,
+    error_policy: ErrorPolicy,
     decode_headers: bool,
-    _m: PhantomData<M>,
+    offset_store: Option<StoreCursor>,
+    commit_on_drop: CommitOnDrop,
+    commit_batcher: Option<CommitBatcher>,
+    _m: PhantomData<(M, K)>,
 }
 
 impl KafkaConsumer {
@@ -30,81 +213,624 @@ impl KafkaConsumer {
     }
 }
 
+#[cfg(feature = "json")]
+impl<M: serde::de::DeserializeOwned> KafkaConsumer<M, crate::codec::JsonDecoder<M>> {
+    /// Creates a consumer decoding plain JSON payloads into `M`.
+    #[inline]
+    pub fn new_json(config: Config) -> Self {
+        Self::new_with_decoder(Default::default(), config)
+    }
+}
+
 impl<M, D: Decoder<M>> KafkaConsumer<M, D> {
     pub fn new_with_decoder(decoder: D, config: Config) -> Self {
         Self {
             reconnect_count: config.reconnect_count,
-            reconnect_sleep_ms: config.reconnect_sleep_ms,
+            backoff: config.backoff_policy(),
+            breaker: CircuitBreaker::new(config.circuit_breaker),
+            limiter: RateLimiter::new(config.rate_limit),
+            error_policy: ErrorPolicy::default(),
             decode_headers: config.decode_headers,
+            offset_store: None,
+            commit_on_drop: CommitOnDrop::new(
+                config
+                    .commit_on_drop
+                    .or(config.commit_every.map(|_| FINAL_COMMIT_TIMEOUT_MS)),
+            ),
+            commit_batcher: config.commit_every.map(CommitBatcher::new),
             builder: KafkaBuilder::new(config),
             inner: None,
+            last_recv: None,
             decoder,
+            key_decoder: crate::codec::BytesDecoder,
             _m: PhantomData,
         }
     }
+}
+
+impl<M, D: Decoder<M>, K, KD: Decoder<K>> KafkaConsumer<M, D, K, KD> {
+    /// Decodes message keys with `key_decoder` instead of returning them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_decoder` - The decoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The consumer producing messages with keys of type `K2`.
+    pub fn with_key_decoder<K2, KD2: Decoder<K2>>(
+        self,
+        key_decoder: KD2,
+    ) -> KafkaConsumer<M, D, K2, KD2> {
+        KafkaConsumer {
+            builder: self.builder,
+            decoder: self.decoder,
+            key_decoder,
+            inner: self.inner,
+            last_recv: self.last_recv,
+            reconnect_count: self.reconnect_count,
+            backoff: self.backoff,
+            breaker: self.breaker,
+            limiter: self.limiter,
+            error_policy: self.error_policy,
+            decode_headers: self.decode_headers,
+            offset_store: self.offset_store,
+            commit_on_drop: self.commit_on_drop,
+            commit_batcher: self.commit_batcher,
+            _m: PhantomData,
+        }
+    }
+
+    /// Sets what the [`Service`] loop does with Kafka errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The error policy, reconnecting on fatal transaction errors by default.
+    ///
+    /// # Returns
+    ///
+    /// The consumer with the error policy set.
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Adds an interceptor seeing every received message and commit, applied on the next
+    /// [`connect`](Self::connect).
+    ///
+    /// # Arguments
+    ///
+    /// * `interceptor` - The interceptor, run after the ones added before.
+    ///
+    /// # Returns
+    ///
+    /// The consumer with the interceptor added.
+    pub fn with_interceptor<I: ConsumerInterceptor>(mut self, interceptor: I) -> Self {
+        self.builder.add_interceptor(Arc::new(interceptor));
+        self
+    }
+
+    /// Reads and saves the consumed offsets with an external store instead of Kafka.
+    ///
+    /// Assigned partitions start at their stored offset, they are only handed to librdkafka
+    /// once the consumer loaded it. The offset of a message is saved when the next one is
+    /// requested, i.e. once it was processed, before reconnecting and, in the background, when
+    /// the consumer is dropped. Auto commit should be disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The offset store, keyed by the `group_id` of the config.
+    ///
+    /// # Returns
+    ///
+    /// The consumer reading its offsets from `store`.
+    pub fn with_offset_store<S: OffsetStore>(mut self, store: S) -> Self {
+        let group = self.builder.config().group_id.clone();
+        self.offset_store = Some(StoreCursor::new(Arc::new(store), group));
+        self.builder.defer_assignments();
+        self
+    }
+
+    /// Saves the offset of the last returned message to the offset store right away, e.g.
+    /// before shutting down. Does nothing without an offset store.
+    pub async fn checkpoint(&mut self) -> Result<(), Error<D::Error>> {
+        match &mut self.offset_store {
+            Some(cursor) => cursor.checkpoint().await,
+            None => Ok(()),
+        }
+    }
 
     #[inline]
     pub fn is_connected(&self) -> bool {
         self.inner.is_some()
     }
 
+    /// Verifies that `topics` exist with at least `min_partitions` partitions each, failing
+    /// with [`Error::TopicMissing`] instead of waiting for a topic that never shows up.
+    ///
+    /// Meant to be called once before [`connect`](Self::connect), it never creates topics.
+    pub async fn preflight(
+        &self,
+        topics: &[&str],
+        min_partitions: usize,
+    ) -> Result<(), Error<D::Error>> {
+        self.builder.preflight(topics, min_partitions).await
+    }
+
     pub async fn connect(&mut self, topics: &[&str]) -> Result<(), Error<D::Error>> {
+        self.breaker.check()?;
+
+        let res = self.try_connect(topics).await;
+        self.breaker.record(&res);
+
+        res
+    }
+
+    async fn try_connect(&mut self, topics: &[&str]) -> Result<(), Error<D::Error>> {
+        // saved before the assignment of the new client loads it from the store
+        self.checkpoint().await?;
+
         self.inner = None;
+        self.commit_on_drop.consumer = None;
+
+        self.builder.ensure_topics(topics).await?;
 
         let consumer = self.builder.build_consumer()?;
-        consumer.subscribe(topics)?;
+        consumer
+            .subscribe(topics)
+            .map_err(|source| Error::Subscribe {
+                topic: topics.join(","),
+                source,
+            })?;
+        let consumer = Arc::new(consumer);
+        self.commit_on_drop.consumer = Some(consumer.clone());
         self.inner.replace(consumer);
 
         Ok(())
     }
 
-    pub async fn recv(&mut self) -> Result<Message<M>, Error<D::Error>> {
-        let consumer = self.inner.as_mut().ok_or(Error::NoConnection)?;
+    /// Subscribes to the librdkafka statistics of this client, only emitted when
+    /// `statistics_interval_ms` is set.
+    #[inline]
+    pub fn statistics(&self) -> StatisticsReceiver {
+        self.builder.statistics()
+    }
 
-        let msg = consumer.recv().await?;
-        let payload = if let Some(mut msg) = msg.payload() {
-            Some(
-                self.decoder
-                    .decode(&mut msg)
-                    .map_err(Error::MessageCodecError)?,
-            )
-        } else {
-            None
+    /// Returns the group metadata of the client, needed to commit offsets within a
+    /// transaction of a producer.
+    pub fn group_metadata(&self) -> Option<ConsumerGroupMetadata> {
+        self.inner.as_ref()?.group_metadata()
+    }
+
+    /// Returns the group membership of the client: its member id and generation, e.g. to
+    /// label metrics, `None` while disconnected.
+    pub fn group_member(&self) -> Option<GroupMember> {
+        group::group_member(self.inner.as_ref()?.client().native_ptr())
+    }
+
+    /// Returns the member id assigned by the group coordinator, `None` until the consumer
+    /// joined the group.
+    pub fn member_id(&self) -> Option<String> {
+        self.group_member()
+            .map(|member| member.member_id)
+            .filter(|id| !id.is_empty())
+    }
+
+    /// Returns the generation of the group, `None` until the consumer joined the group.
+    pub fn generation_id(&self) -> Option<i32> {
+        self.group_member()
+            .map(|member| member.generation_id)
+            .filter(|generation| *generation >= 0)
+    }
+
+    /// Returns the partitions currently assigned to the client.
+    pub fn assignment(&self) -> Result<TopicPartitionList, Error<D::Error>> {
+        let consumer = self.inner.as_ref().ok_or(Error::NoConnection)?;
+
+        Ok(consumer.assignment()?)
+    }
+
+    /// Adds `partitions` to the current assignment, starting at their offsets, without
+    /// revoking the partitions already assigned.
+    ///
+    /// Meant for the cooperative rebalance protocol (`partition.assignment.strategy` set to
+    /// `cooperative-sticky`) and for manually managed assignments.
+    pub fn incremental_assign(
+        &self,
+        partitions: &TopicPartitionList,
+    ) -> Result<(), Error<D::Error>> {
+        let consumer = self.inner.as_ref().ok_or(Error::NoConnection)?;
+
+        Ok(consumer.incremental_assign(partitions)?)
+    }
+
+    /// Removes `partitions` from the current assignment, keeping the other partitions
+    /// assigned.
+    pub fn incremental_unassign(
+        &self,
+        partitions: &TopicPartitionList,
+    ) -> Result<(), Error<D::Error>> {
+        let consumer = self.inner.as_ref().ok_or(Error::NoConnection)?;
+
+        Ok(consumer.incremental_unassign(partitions)?)
+    }
+
+    /// Checks the connectivity of the consumer: a metadata request to the brokers and the
+    /// lag of the assigned partitions, both on the blocking thread pool.
+    pub async fn health(&self) -> Health {
+        let Some(consumer) = self.inner.clone() else {
+            return Health::disconnected(self.last_recv);
         };
 
-        let headers = if self.decode_headers
-            && let Some(headers) = msg.headers()
-        {
-            Some(
-                headers
-                    .iter()
-                    .filter_map(|hdr| Some((hdr.key.to_string(), hdr.value?.to_vec())))
-                    .collect(),
+        let (brokers_reachable, lag) = blocking(move || {
+            let reachable = consumer.fetch_metadata(None, HEALTH_CHECK_TIMEOUT).is_ok();
+
+            (
+                reachable,
+                reachable.then(|| consumer_lag(&consumer)).flatten(),
             )
-        } else {
-            None
+        })
+        .await;
+
+        Health {
+            connected: true,
+            last_activity: self.last_recv,
+            brokers_reachable,
+            lag,
+        }
+    }
+
+    pub async fn recv(&mut self) -> Result<Message<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.recv_with_offset().await.map(|(msg, _)| msg)
+    }
+
+    /// Receives the next message, or `None` once `timeout` elapsed without one, so periodic
+    /// work can run between messages.
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Message<M, K>>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let deadline = Instant::now() + timeout;
+
+        Ok(self.recv_until(Some(deadline)).await?.map(|(msg, ..)| msg))
+    }
+
+    /// Receives the next message along with the handle acknowledging it, whose offset is
+    /// committed before its partition is revoked, see
+    /// [`ConfigBuilder::commit_on_revoke`](crate::config::ConfigBuilder::commit_on_revoke).
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InvalidConfig`] when `commit_on_revoke` is not set.
+    pub async fn recv_with_ack(&mut self) -> Result<(Message<M, K>, AckHandle), Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if self.builder.config().commit_on_revoke.is_none() {
+            return Err(Error::InvalidConfig(vec![
+                ConfigError::AckRequiresCommitOnRevoke,
+            ]));
+        }
+
+        let (msg, _, ack) = self
+            .recv_until(None)
+            .await?
+            .expect("receives without a deadline always return a message");
+
+        Ok((
+            msg,
+            ack.expect("clients built with commit_on_revoke track acks"),
+        ))
+    }
+
+    /// Receives the next message along with its offset.
+    pub(crate) async fn recv_with_offset(&mut self) -> Result<(Message<M, K>, i64), Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.recv_until(None).await.map(|x| {
+            let (msg, offset, _) = x.expect("receives without a deadline always return a message");
+            (msg, offset)
+        })
+    }
+
+    /// Receives the next message along with its offset and ack handle, or `None` if no
+    /// message arrived before `deadline`. Only the wait for a message is cut short, so no
+    /// message is lost.
+    ///
+    /// Handles dropped by the callers keep the offset from being committed on revoke.
+    async fn recv_until(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(Message<M, K>, i64, Option<AckHandle>)>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let consumer = self.inner.clone().ok_or(Error::NoConnection)?;
+        self.breaker.check()?;
+
+        // the partitions the last assignment could not reset are paused until then
+        if let Some(err) = consumer.context().take_offset_reset_error() {
+            return Err(Error::KafkaError(err));
+        }
+
+        if let Some(cursor) = &mut self.offset_store {
+            cursor.checkpoint().await?;
+        }
+
+        // the messages returned so far were processed once the next one is requested
+        if let Some(batcher) = &mut self.commit_batcher {
+            batcher.commit_if_due(&consumer);
+        }
+
+        self.limiter.ready().await;
+
+        let (msg, ack) = loop {
+            let received = loop {
+                // the interval of the commit policy is also due while waiting for a message
+                let commit_due = self
+                    .commit_batcher
+                    .as_ref()
+                    .and_then(CommitBatcher::next_due);
+                let cursor = self.offset_store.as_ref();
+                let Some(wake) = [deadline, commit_due].into_iter().flatten().min() else {
+                    break recv_assigned(&consumer, cursor).await?;
+                };
+
+                let remaining = wake.saturating_duration_since(Instant::now());
+                let received = recv_assigned(&consumer, cursor);
+                if let Some(received) = crate::runtime::timeout(remaining, received).await {
+                    break received?;
+                }
+
+                if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+                    return Ok(None);
+                }
+
+                if let Some(batcher) = &mut self.commit_batcher {
+                    batcher.commit_if_due(&consumer);
+                }
+            };
+
+            let msg = match received {
+                Ok(msg) => {
+                    self.breaker.record_success();
+                    msg
+                }
+                Err(err) => {
+                    self.breaker.record_failure();
+                    return Err(Error::Receive(err));
+                }
+            };
+
+            // messages of partitions being revoked are left to their next owner
+            let ack = match &consumer.context().acks {
+                Some(acks) => match acks.track(msg.topic(), msg.partition(), msg.offset()) {
+                    Some(ack) => Some(ack),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            break (msg, ack);
         };
+        let msg = SharedMessage::new(msg, &consumer);
+        self.last_recv = Some(SystemTime::now());
+        self.limiter.record(1, msg.payload_len());
+
+        for interceptor in self.builder.interceptors() {
+            interceptor.on_consume(&msg);
+        }
+
+        #[cfg(feature = "metrics")]
+        crate::telemetry::message_received(
+            msg.topic(),
+            msg.partition(),
+            &self.builder.config().group_id,
+            msg.payload_len(),
+        );
+
+        #[cfg(feature = "otel")]
+        let span = crate::otel::consumer_span(&msg, &self.builder.config().group_id);
+
+        // shared with the message, decoders reading bytes and decode failures copy nothing
+        let payload = msg.payload_bytes();
+        let key = msg.key_bytes();
+
+        #[cfg(feature = "metrics")]
+        let decoding = Instant::now();
+
+        let decoded = self
+            .decode(&msg, payload.clone(), key.clone())
+            .map_err(|error| {
+                Error::DecodeFailed(Box::new(DecodeFailure {
+                    raw: payload,
+                    key,
+                    error,
+                }))
+                .at(msg.topic(), msg.partition(), msg.offset())
+            });
+
+        #[cfg(feature = "otel")]
+        crate::otel::end_span(&span, &decoded);
+
+        #[cfg(feature = "metrics")]
+        crate::telemetry::decode_duration(
+            msg.topic(),
+            &self.builder.config().group_id,
+            decoding.elapsed(),
+        );
+
+        #[cfg(feature = "metrics")]
+        if decoded.is_err() {
+            crate::telemetry::decode_failed(
+                msg.topic(),
+                msg.partition(),
+                &self.builder.config().group_id,
+            );
+        }
 
-        Ok(Message {
-            key: msg.key().map(|x| x.to_vec().into()),
-            ts_ms_utc: msg.timestamp().to_millis(),
+        if let Some(cursor) = &mut self.offset_store {
+            cursor.returned(&msg);
+        }
+
+        if let Some(batcher) = &mut self.commit_batcher {
+            batcher.returned();
+        }
+
+        match decoded {
+            Ok(decoded) => Ok(Some((decoded, msg.offset(), ack))),
+            Err(err) => {
+                // never returned, the message is skipped like without acks
+                if let Some(ack) = ack {
+                    ack.ack();
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Takes the rebalances of the client since the last call.
+    fn rebalance_events(&self) -> Vec<RebalanceEvent> {
+        self.inner
+            .as_ref()
+            .map(|consumer| consumer.context().rebalance_events.drain())
+            .unwrap_or_default()
+    }
+
+    fn decode(
+        &mut self,
+        msg: &BorrowedMessage<'_>,
+        payload: Option<Bytes>,
+        key: Option<Bytes>,
+    ) -> Result<Message<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        decode_message(
+            &mut self.decoder,
+            &mut self.key_decoder,
+            self.decode_headers,
+            msg,
             payload,
-            partition: msg.partition(),
-            headers,
-        })
+            key,
+        )
     }
 }
 
-impl<M, D, I> Service<I> for KafkaConsumer<M, D>
+/// Decodes `msg` from its detached `payload` and `key`, so decoders reading bytes get
+/// slices of them instead of copies.
+pub(crate) fn decode_message<M, D, K, KD>(
+    decoder: &mut D,
+    key_decoder: &mut KD,
+    decode_headers: bool,
+    msg: &BorrowedMessage<'_>,
+    payload: Option<Bytes>,
+    key: Option<Bytes>,
+) -> Result<Message<M, K>, Error<D::Error>>
+where
+    D: Decoder<M>,
+    KD: Decoder<K>,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+{
+    let payload = if let Some(mut payload) = payload {
+        Some(
+            decoder
+                .decode(&mut payload)
+                .map_err(Error::MessageCodecError)?,
+        )
+    } else {
+        None
+    };
+
+    let key = if let Some(mut key) = key {
+        Some(
+            key_decoder
+                .decode(&mut key)
+                .map_err(|err| Error::KeyCodecError(Box::new(err)))?,
+        )
+    } else {
+        None
+    };
+
+    let headers = if decode_headers && let Some(headers) = msg.headers() {
+        Some(
+            headers
+                .iter()
+                .filter_map(|hdr| Some((hdr.key.to_string(), hdr.value?.to_vec())))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Ok(Message {
+        key,
+        ts_ms_utc: msg.timestamp().to_millis(),
+        payload,
+        partition: msg.partition(),
+        headers,
+    })
+}
+
+impl<M, D, K, KD> KafkaConsumer<M, D, K, KD>
 where
     D: Decoder<M> + Send,
     D::Error: std::error::Error + Send,
-    I: AsRef<str> + Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
     M: Send,
+    K: Send,
 {
-    type Out = Result<Message<M>, Error<D::Error>>;
+    /// Consumes the topic `input` as a stream of messages, connecting and reconnecting as
+    /// needed, what the flowly `Service` of the consumer yields.
+    pub fn stream<I>(
+        &mut self,
+        input: I,
+    ) -> impl Stream<Item = Result<Message<M, K>, Error<D::Error>>> + Send
+    where
+        I: AsRef<str> + Send,
+    {
+        self.run(input, false).filter_map(|event| {
+            future::ready(match event {
+                Ok(Event::Message(msg)) => Some(Ok(msg)),
+                Ok(..) => None,
+                Err(err) => Some(Err(err)),
+            })
+        })
+    }
 
-    fn handle(&mut self, input: I, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+    /// Consumes the topic `input` like [`stream`](Self::stream), along with the rebalances
+    /// in between the messages, see
+    /// [`ConfigBuilder::quiesce_on_rebalance`](crate::config::ConfigBuilder::quiesce_on_rebalance).
+    ///
+    /// Rebalances are returned within [`REBALANCE_POLL_INTERVAL`] even when no message
+    /// arrives.
+    pub fn events<I>(
+        &mut self,
+        input: I,
+    ) -> impl Stream<Item = Result<Event<M, K>, Error<D::Error>>> + Send
+    where
+        I: AsRef<str> + Send,
+    {
+        self.run(input, true)
+    }
+
+    fn run<I>(
+        &mut self,
+        input: I,
+        rebalances: bool,
+    ) -> impl Stream<Item = Result<Event<M, K>, Error<D::Error>>> + Send
+    where
+        I: AsRef<str> + Send,
+    {
         let mut reconnect_counter = if self.reconnect_count == 0 {
             u64::MAX
         } else {
@@ -112,29 +838,86 @@ where
         };
 
         let mut error = None;
+        let mut failures = 0;
 
         async_stream::stream! {
             while reconnect_counter > 0 {
                 if !self.is_connected() {
                     match self.connect(&[input.as_ref()]).await {
                         Ok(..) => (),
+                        Err(err @ Error::InvalidConfig(..)) => {
+                            yield Err(err);
+                            return;
+                        }
+                        Err(Error::CircuitOpen { retry_after }) => {
+                            yield Err(Error::CircuitOpen { retry_after });
+                            crate::runtime::sleep(retry_after).await;
+                            continue;
+                        }
                         Err(err) => {
                             error.replace(err);
                             reconnect_counter -= 1;
-                            tokio::time::sleep(std::time::Duration::from_millis(self.reconnect_sleep_ms as _)).await;
+                            crate::runtime::sleep(self.backoff.delay(failures)).await;
+                            failures += 1;
                             continue;
                         },
                     }
                 }
 
-                match self.recv().await {
-                    Ok(msg) => yield Ok(msg),
-                    Err(Error::KafkaError(KafkaError::Transaction(e))) if e.is_fatal() => {
-                        error.replace(Error::KafkaError(KafkaError::Transaction(e)));
-                        reconnect_counter -= 1;
-                        self.inner = None;
-                        tokio::time::sleep(std::time::Duration::from_millis(self.reconnect_sleep_ms as _)).await;
-                        continue;
+                if rebalances {
+                    for event in self.rebalance_events() {
+                        yield Ok(event.into());
+                    }
+                }
+
+                let received = if rebalances {
+                    self.recv_timeout(REBALANCE_POLL_INTERVAL).await
+                } else {
+                    self.recv().await.map(Some)
+                };
+
+                if rebalances {
+                    for event in self.rebalance_events() {
+                        yield Ok(event.into());
+                    }
+                }
+
+                match received {
+                    Ok(Some(msg)) => {
+                        failures = 0;
+
+                        #[cfg(feature = "metrics")]
+                        let yielded = Instant::now();
+
+                        yield Ok(Event::Message(msg));
+
+                        // time the downstream stages took before asking for the next message
+                        #[cfg(feature = "metrics")]
+                        crate::telemetry::stream_stalled(
+                            input.as_ref(),
+                            &self.builder.config().group_id,
+                            yielded.elapsed(),
+                        );
+                    }
+                    Ok(None) => (),
+                    Err(Error::Receive(err)) => match &self.error_policy {
+                        ErrorPolicy::ReconnectOn(matcher) if matcher(&err) => {
+                            error.replace(Error::Receive(err));
+                            reconnect_counter -= 1;
+                            self.inner = None;
+                            crate::runtime::sleep(self.backoff.delay(failures)).await;
+                            failures += 1;
+                            continue;
+                        }
+                        ErrorPolicy::FailStream => {
+                            yield Err(Error::Receive(err));
+                            return;
+                        }
+                        _ => yield Err(Error::Receive(err)),
+                    },
+                    Err(Error::CircuitOpen { retry_after }) => {
+                        yield Err(Error::CircuitOpen { retry_after });
+                        crate::runtime::sleep(retry_after).await;
                     }
 
                     Err(err) => yield Err(err),
@@ -142,6 +925,10 @@ where
             }
 
             if let Some(err) = error {
+                #[cfg(feature = "tracing")]
+                tracing::error!(topic = input.as_ref(), "kafka error: {err}");
+
+                #[cfg(not(feature = "tracing"))]
                 log::error!("kafka error: {err}");
 
                 yield Err(err);
@@ -149,3 +936,59 @@ where
         }
     }
 }
+
+#[cfg(feature = "flowly")]
+impl<M, D, K, KD, I> Service<I> for KafkaConsumer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    D::Error: std::error::Error + Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    I: AsRef<str> + Send,
+    M: Send,
+    K: Send,
+{
+    type Out = Result<Message<M, K>, Error<D::Error>>;
+
+    #[inline]
+    fn handle(&mut self, input: I, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        self.stream(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommitEvery;
+
+    fn drop_timeout(config: Config) -> Option<Duration> {
+        KafkaConsumer::new(config).commit_on_drop.timeout
+    }
+
+    #[test]
+    fn no_commit_on_drop_by_default() {
+        assert_eq!(drop_timeout(Config::default()), None);
+    }
+
+    #[test]
+    fn commit_every_commits_on_drop() {
+        let config = Config::builder()
+            .commit_every(CommitEvery::count(100).or_interval(1_000))
+            .build();
+
+        assert_eq!(
+            drop_timeout(config),
+            Some(Duration::from_millis(FINAL_COMMIT_TIMEOUT_MS.into()))
+        );
+    }
+
+    #[test]
+    fn commit_on_drop_overrides_final_commit_timeout() {
+        let config = Config::builder()
+            .commit_every(CommitEvery::count(100))
+            .commit_on_drop(250)
+            .build();
+
+        assert_eq!(drop_timeout(config), Some(Duration::from_millis(250)));
+    }
+}
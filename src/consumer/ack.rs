@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use rdkafka::{
+    Offset, TopicPartitionList,
+    consumer::{BaseConsumer, CommitMode, Consumer},
+    error::KafkaError,
+    types::RDKafkaErrorCode,
+};
+
+use crate::KafkaCallbackContext;
+
+/// Marks a message returned by
+/// [`KafkaConsumer::recv_with_ack`](super::KafkaConsumer::recv_with_ack) as processed.
+///
+/// Offsets are only committed up to the first message not acknowledged yet, a handle
+/// dropped without [`ack`](Self::ack) keeps its message and the following ones of the
+/// partition uncommitted.
+pub struct AckHandle {
+    tracker: Arc<AckTracker>,
+    topic: String,
+    partition: i32,
+    offset: i64,
+    acked: bool,
+}
+
+impl AckHandle {
+    #[inline]
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    #[inline]
+    pub fn partition(&self) -> i32 {
+        self.partition
+    }
+
+    #[inline]
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Acknowledges the message, its offset is committed once the messages before it are
+    /// acknowledged too.
+    pub fn ack(mut self) {
+        self.acked = true;
+        self.tracker
+            .release(&self.topic, self.partition, self.offset, true);
+    }
+}
+
+impl Drop for AckHandle {
+    fn drop(&mut self) {
+        if !self.acked {
+            self.tracker
+                .release(&self.topic, self.partition, self.offset, false);
+        }
+    }
+}
+
+impl fmt::Debug for AckHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AckHandle")
+            .field("topic", &self.topic)
+            .field("partition", &self.partition)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+/// Acks of a partition since it was assigned.
+#[derive(Default)]
+struct PartitionAcks {
+    /// Offsets of the handles not released yet.
+    outstanding: BTreeSet<i64>,
+
+    /// Highest acknowledged offset.
+    acked: Option<i64>,
+
+    /// Lowest offset whose handle was dropped without an ack.
+    dropped: Option<i64>,
+}
+
+impl PartitionAcks {
+    /// Next offset to commit, `None` when nothing can be committed.
+    fn committable(&self) -> Option<i64> {
+        let mut next = self.acked? + 1;
+
+        let first = self.outstanding.first().copied();
+        for offset in first.into_iter().chain(self.dropped) {
+            next = next.min(offset);
+        }
+
+        Some(next)
+    }
+}
+
+#[derive(Default)]
+struct AckState {
+    partitions: HashMap<(String, i32), PartitionAcks>,
+
+    /// Partitions being revoked, their messages are no longer returned.
+    revoked: HashSet<(String, i32)>,
+}
+
+/// Acks of the messages returned by a consumer, committed by the rebalance revoking their
+/// partitions, see [`ConfigBuilder::commit_on_revoke`](crate::config::ConfigBuilder::commit_on_revoke).
+pub(crate) struct AckTracker {
+    state: Mutex<AckState>,
+    released: Condvar,
+    timeout: Duration,
+}
+
+impl AckTracker {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            state: Default::default(),
+            released: Condvar::new(),
+            timeout,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AckState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Hands out the handle of a received message, `None` when its partition is being
+    /// revoked and the message is not to be returned.
+    pub(crate) fn track(
+        self: &Arc<Self>,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+    ) -> Option<AckHandle> {
+        let key = (topic.to_string(), partition);
+
+        let mut state = self.lock();
+        if state.revoked.contains(&key) {
+            return None;
+        }
+
+        state
+            .partitions
+            .entry(key)
+            .or_default()
+            .outstanding
+            .insert(offset);
+
+        Some(AckHandle {
+            tracker: self.clone(),
+            topic: topic.to_string(),
+            partition,
+            offset,
+            acked: false,
+        })
+    }
+
+    fn release(&self, topic: &str, partition: i32, offset: i64, acked: bool) {
+        let mut state = self.lock();
+
+        // the partition was revoked and committed in the meantime
+        let Some(acks) = state.partitions.get_mut(&(topic.to_string(), partition)) else {
+            return;
+        };
+
+        acks.outstanding.remove(&offset);
+        if acked {
+            acks.acked = acks.acked.max(Some(offset));
+        } else {
+            acks.dropped = Some(acks.dropped.map_or(offset, |x| x.min(offset)));
+        }
+
+        drop(state);
+        self.released.notify_all();
+    }
+
+    /// Partitions are returned again once assigned back.
+    pub(crate) fn assigned(&self, tpl: &TopicPartitionList) {
+        let mut state = self.lock();
+
+        for elem in tpl.elements() {
+            state
+                .revoked
+                .remove(&(elem.topic().to_string(), elem.partition()));
+        }
+    }
+
+    /// Stops returning the messages of the partitions of `tpl`, waits for their outstanding
+    /// handles and commits their acknowledged offsets, blocking the rebalance meanwhile.
+    pub(crate) fn revoke(
+        &self,
+        consumer: &BaseConsumer<KafkaCallbackContext>,
+        tpl: &TopicPartitionList,
+    ) {
+        let keys: Vec<_> = tpl
+            .elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .collect();
+
+        let deadline = Instant::now() + self.timeout;
+        let mut state = self.lock();
+        state.revoked.extend(keys.iter().cloned());
+
+        loop {
+            let outstanding = keys.iter().any(|key| {
+                state
+                    .partitions
+                    .get(key)
+                    .is_some_and(|acks| !acks.outstanding.is_empty())
+            });
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !outstanding || remaining.is_zero() {
+                break;
+            }
+
+            state = self
+                .released
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+
+        let mut offsets = TopicPartitionList::new();
+        for key in &keys {
+            let Some(acks) = state.partitions.remove(key) else {
+                continue;
+            };
+
+            if !acks.outstanding.is_empty() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    topic = key.0,
+                    partition = key.1,
+                    "{} messages not acknowledged before the revocation",
+                    acks.outstanding.len()
+                );
+
+                #[cfg(not(feature = "tracing"))]
+                log::warn!(
+                    "{} messages of {}/{} not acknowledged before the revocation",
+                    acks.outstanding.len(),
+                    key.0,
+                    key.1
+                );
+            }
+
+            if let Some(next) = acks.committable() {
+                let _ = offsets.add_partition_offset(&key.0, key.1, Offset::Offset(next));
+            }
+        }
+        drop(state);
+
+        if offsets.count() == 0 {
+            return;
+        }
+
+        match consumer.commit(&offsets, CommitMode::Sync) {
+            Ok(()) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {}
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("kafka commit on revoke failed: {err}");
+
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("kafka commit on revoke failed: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use rdkafka::{
+    consumer::{CommitMode, Consumer, stream_consumer::StreamConsumer},
+    error::KafkaError,
+    types::RDKafkaErrorCode,
+};
+
+use crate::{KafkaCallbackContext, config::CommitEvery, runtime::Runtime};
+
+/// How long the final commit of a consumer with a [`CommitEvery`] policy waits when it is
+/// dropped, unless `commit_on_drop` is set.
+pub(crate) const FINAL_COMMIT_TIMEOUT_MS: u32 = 5_000;
+
+/// Counts the messages returned by a consumer and commits their stored offsets once its
+/// [`CommitEvery`] policy is due.
+#[derive(Debug)]
+pub(crate) struct CommitBatcher {
+    count: Option<u32>,
+    interval: Option<Duration>,
+    pending: u32,
+    last_commit: Instant,
+}
+
+impl CommitBatcher {
+    pub(crate) fn new(policy: CommitEvery) -> Self {
+        Self {
+            count: policy.count,
+            interval: policy.interval_ms.map(|x| Duration::from_millis(x.into())),
+            pending: 0,
+            last_commit: Instant::now(),
+        }
+    }
+
+    /// Counts a message returned to the caller.
+    #[inline]
+    pub(crate) fn returned(&mut self) {
+        self.pending = self.pending.saturating_add(1);
+    }
+
+    /// Returns whether messages were returned since the last commit and the policy is due
+    /// at `now`.
+    fn due(&self, now: Instant) -> bool {
+        self.pending > 0
+            && (self.count.is_some_and(|count| self.pending >= count)
+                || self.interval.is_some_and(|interval| {
+                    now.saturating_duration_since(self.last_commit) >= interval
+                }))
+    }
+
+    /// Returns when the interval of the policy is due, if messages were returned since the
+    /// last commit, so a consumer waiting for the next message can commit in between.
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        if self.pending == 0 {
+            return None;
+        }
+
+        self.interval.map(|interval| self.last_commit + interval)
+    }
+
+    /// Commits the stored offsets of `consumer` without waiting for the brokers if messages
+    /// were returned since the last commit and the policy is due.
+    pub(crate) fn commit_if_due(
+        &mut self,
+        consumer: &StreamConsumer<KafkaCallbackContext, Runtime>,
+    ) {
+        if !self.due(Instant::now()) {
+            return;
+        }
+
+        let result = consumer.commit_consumer_state(CommitMode::Async);
+
+        // retried once due again, the stored offsets are kept
+        self.last_commit = Instant::now();
+
+        match result {
+            Ok(()) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {
+                self.pending = 0;
+            }
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("kafka batched commit failed: {err}");
+
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("kafka batched commit failed: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batcher(policy: CommitEvery, returned: u32) -> CommitBatcher {
+        let mut batcher = CommitBatcher::new(policy);
+        for _ in 0..returned {
+            batcher.returned();
+        }
+
+        batcher
+    }
+
+    #[test]
+    fn nothing_returned_is_never_due() {
+        let policy = CommitEvery::count(1).or_interval(10);
+        let batcher = batcher(policy, 0);
+        let later = batcher.last_commit + Duration::from_secs(60);
+
+        assert!(!batcher.due(later));
+        assert_eq!(batcher.next_due(), None);
+    }
+
+    #[test]
+    fn count_is_due_once_reached() {
+        let below = batcher(CommitEvery::count(3).or_interval(1_000), 2);
+        assert!(!below.due(below.last_commit));
+
+        let reached = batcher(CommitEvery::count(3).or_interval(1_000), 3);
+        assert!(reached.due(reached.last_commit));
+    }
+
+    #[test]
+    fn interval_is_due_once_elapsed() {
+        let batcher = batcher(CommitEvery::count(100).or_interval(1_000), 1);
+        let start = batcher.last_commit;
+
+        assert!(!batcher.due(start + Duration::from_millis(999)));
+        assert!(batcher.due(start + Duration::from_millis(1_000)));
+        assert_eq!(batcher.next_due(), Some(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn count_only_has_no_timer() {
+        let batcher = batcher(CommitEvery::count(100), 1);
+        let later = batcher.last_commit + Duration::from_secs(3_600);
+
+        assert!(!batcher.due(later));
+        assert_eq!(batcher.next_due(), None);
+    }
+}
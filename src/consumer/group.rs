@@ -0,0 +1,77 @@
+//! Membership of a consumer in its group, the member id and generation are not exposed by
+//! rdkafka so they are read from the serialized librdkafka group metadata.
+
+use std::{ffi::c_void, ptr};
+
+use rdkafka::types::RDKafka;
+use rdkafka_sys as rdsys;
+
+/// Magic prefix of the serialized group metadata.
+const MAGIC: &[u8] = b"CGMDv2:";
+
+/// Group membership of a consumer, as of its last rebalance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMember {
+    pub group_id: String,
+    /// Assigned by the group coordinator, empty until the consumer joined the group.
+    pub member_id: String,
+    /// Incremented by the coordinator on every rebalance, -1 until the consumer joined.
+    pub generation_id: i32,
+    /// The `group.instance.id` of static members.
+    pub group_instance_id: Option<String>,
+}
+
+/// Reads the group membership of the consumer `rk`.
+pub(crate) fn group_member(rk: *mut RDKafka) -> Option<GroupMember> {
+    let cgmd = unsafe { rdsys::rd_kafka_consumer_group_metadata(rk) };
+    if cgmd.is_null() {
+        return None;
+    }
+
+    let mut buffer: *mut c_void = ptr::null_mut();
+    let mut size = 0;
+    let error =
+        unsafe { rdsys::rd_kafka_consumer_group_metadata_write(cgmd, &mut buffer, &mut size) };
+    unsafe { rdsys::rd_kafka_consumer_group_metadata_destroy(cgmd) };
+
+    if !error.is_null() {
+        unsafe { rdsys::rd_kafka_error_destroy(error) };
+        return None;
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, size) }.to_vec();
+    unsafe { rdsys::rd_kafka_mem_free(ptr::null_mut(), buffer) };
+
+    parse(&bytes)
+}
+
+/// Parses the group metadata written by `rd_kafka_consumer_group_metadata_write`: the
+/// magic, the generation in native byte order, the group and member ids, a null flag and
+/// the group instance id, strings being nul terminated.
+fn parse(bytes: &[u8]) -> Option<GroupMember> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (generation, rest) = rest.split_first_chunk::<4>()?;
+    let (group_id, rest) = read_str(rest)?;
+    let (member_id, rest) = read_str(rest)?;
+    let (is_null, rest) = rest.split_first()?;
+
+    let group_instance_id = if *is_null == 0 {
+        Some(read_str(rest)?.0)
+    } else {
+        None
+    };
+
+    Some(GroupMember {
+        group_id,
+        member_id,
+        generation_id: i32::from_ne_bytes(*generation),
+        group_instance_id,
+    })
+}
+
+fn read_str(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let end = bytes.iter().position(|x| *x == 0)?;
+    let value = String::from_utf8_lossy(&bytes[..end]).into_owned();
+
+    Some((value, &bytes[end + 1..]))
+}
@@ -0,0 +1,227 @@
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "flowly")]
+use flowly::Service;
+#[cfg(feature = "flowly")]
+use futures::Stream;
+use futures::{StreamExt, stream::FuturesUnordered};
+
+#[cfg(feature = "flowly")]
+use crate::config::BackoffPolicy;
+use crate::{Message, codec::Decoder, error::Error};
+
+use super::KafkaConsumer;
+
+/// A message received by a [`MultiClusterConsumer`].
+#[derive(Debug, Clone)]
+pub struct ClusterMessage<M, K> {
+    /// Index of the cluster the message was consumed from.
+    pub cluster: usize,
+    /// Offset of the message in its partition on that cluster.
+    pub offset: i64,
+    pub message: Message<M, K>,
+}
+
+/// Consumes the same topics on several clusters as a single stream, for active-active
+/// deployments where a message may be produced in either region.
+///
+/// Messages are merged by timestamp on a best effort basis: once a message is received,
+/// the clusters without a pending message get `merge_window` to deliver one, then the
+/// oldest pending message is returned. Messages without a timestamp are returned first.
+pub struct MultiClusterConsumer<
+    M = bytes::Bytes,
+    D: Decoder<M> = crate::codec::BytesDecoder,
+    K = bytes::Bytes,
+    KD: Decoder<K> = crate::codec::BytesDecoder,
+> {
+    clusters: Vec<KafkaConsumer<M, D, K, KD>>,
+    pending: Vec<Option<ClusterMessage<M, K>>>,
+    merge_window: Duration,
+
+    /// Delay between the reconnections of the [`Service`] loop.
+    #[cfg(feature = "flowly")]
+    backoff: BackoffPolicy,
+}
+
+impl<M, D: Decoder<M>, K, KD: Decoder<K>> MultiClusterConsumer<M, D, K, KD> {
+    /// Creates a consumer merging the messages of `clusters`, the index of a consumer in
+    /// the list is the `cluster` of its messages.
+    pub fn new(clusters: Vec<KafkaConsumer<M, D, K, KD>>) -> Self {
+        #[cfg(feature = "flowly")]
+        let backoff = clusters
+            .first()
+            .map(|consumer| consumer.backoff)
+            .unwrap_or(BackoffPolicy::Fixed { delay_ms: 1000 });
+
+        Self {
+            pending: clusters.iter().map(|_| None).collect(),
+            clusters,
+            merge_window: Duration::from_millis(50),
+            #[cfg(feature = "flowly")]
+            backoff,
+        }
+    }
+
+    /// Sets how long the clusters without a pending message are waited for before the
+    /// oldest pending message is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `merge_window` - The wait, 50 milliseconds by default. Longer windows order the
+    ///   messages better at the cost of latency.
+    ///
+    /// # Returns
+    ///
+    /// The consumer with the updated merge window.
+    pub fn merge_window(mut self, merge_window: Duration) -> Self {
+        self.merge_window = merge_window;
+        self
+    }
+
+    /// Returns the consumer of a cluster, e.g. to check its health.
+    #[inline]
+    pub fn cluster(&self, cluster: usize) -> Option<&KafkaConsumer<M, D, K, KD>> {
+        self.clusters.get(cluster)
+    }
+
+    /// Whether at least one cluster is connected.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.clusters.iter().any(KafkaConsumer::is_connected)
+    }
+
+    /// Connects the clusters that are not connected yet, a region being down does not stop
+    /// the others. Fails with the last error when no cluster could be connected.
+    pub async fn connect(&mut self, topics: &[&str]) -> Result<(), Error<D::Error>> {
+        let mut error = None;
+
+        for consumer in self.clusters.iter_mut().filter(|x| !x.is_connected()) {
+            if let Err(err) = consumer.connect(topics).await {
+                error.replace(err);
+            }
+        }
+
+        match error {
+            Some(err) if !self.is_connected() => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn recv(&mut self) -> Result<ClusterMessage<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if !self.is_connected() {
+            return Err(Error::NoConnection);
+        }
+
+        {
+            let pending = &mut self.pending;
+            let mut receiving: FuturesUnordered<_> =
+                self.clusters
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(cluster, consumer)| {
+                        consumer.is_connected() && pending[*cluster].is_none()
+                    })
+                    .map(|(cluster, consumer)| async move {
+                        (cluster, consumer.recv_with_offset().await)
+                    })
+                    .collect();
+
+            let mut deadline = None;
+
+            loop {
+                let next = if pending.iter().any(Option::is_some) {
+                    let deadline =
+                        *deadline.get_or_insert_with(|| Instant::now() + self.merge_window);
+
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+
+                    match crate::runtime::timeout(remaining, receiving.next()).await {
+                        Some(next) => next,
+                        None => break,
+                    }
+                } else {
+                    receiving.next().await
+                };
+
+                match next {
+                    Some((cluster, Ok((message, offset)))) => {
+                        pending[cluster] = Some(ClusterMessage {
+                            cluster,
+                            offset,
+                            message,
+                        });
+                    }
+                    Some((_, Err(err))) => return Err(err),
+                    None => break,
+                }
+            }
+        }
+
+        let oldest = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(cluster, msg)| Some((cluster, msg.as_ref()?.message.ts_ms_utc)))
+            .min_by_key(|(_, ts)| ts.unwrap_or(i64::MIN))
+            .map(|(cluster, _)| cluster);
+
+        oldest
+            .and_then(|cluster| self.pending[cluster].take())
+            .ok_or(Error::NoConnection)
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, D, K, KD, I> Service<I> for MultiClusterConsumer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    D::Error: std::error::Error + Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    I: AsRef<str> + Send,
+    M: Send,
+    K: Send,
+{
+    type Out = Result<ClusterMessage<M, K>, Error<D::Error>>;
+
+    fn handle(&mut self, input: I, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        let mut failures = 0;
+
+        async_stream::stream! {
+            loop {
+                if !self.is_connected()
+                    && let Err(err) = self.connect(&[input.as_ref()]).await
+                {
+                    let fatal = err.is_fatal();
+                    yield Err(err);
+
+                    if fatal {
+                        return;
+                    }
+
+                    crate::runtime::sleep(self.backoff.delay(failures)).await;
+                    failures += 1;
+                    continue;
+                }
+
+                match self.recv().await {
+                    Ok(msg) => {
+                        failures = 0;
+                        yield Ok(msg);
+                    }
+                    Err(err) => {
+                        let fatal = err.is_fatal();
+                        yield Err(err);
+
+                        if fatal {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
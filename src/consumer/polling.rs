@@ -0,0 +1,219 @@
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use rdkafka::{
+    Message as _,
+    consumer::{BaseConsumer, CommitMode, Consumer},
+};
+
+use crate::{
+    KafkaCallbackContext, Message,
+    builder::KafkaBuilder,
+    codec::Decoder,
+    config::Config,
+    error::{DecodeFailure, Error},
+    interceptor::ConsumerInterceptor,
+    statistics::StatisticsReceiver,
+};
+
+use super::{SharedMessage, decode_message};
+
+/// Result of a [`PollingConsumer::poll`] that returned a message.
+type Polled<M, K, E> = Result<Message<M, K>, Error<E>>;
+
+/// Consumer over a `BaseConsumer` polled by the caller, e.g. from a loop shared with other
+/// event sources, instead of a stream driven by the async runtime.
+///
+/// Callbacks like rebalances, commits and statistics are only served while
+/// [`poll`](Self::poll) is called, so it should be called regularly, well within
+/// `max.poll.interval.ms`.
+pub struct PollingConsumer<
+    M = Bytes,
+    D: Decoder<M> = crate::codec::BytesDecoder,
+    K = Bytes,
+    KD: Decoder<K> = crate::codec::BytesDecoder,
+> {
+    builder: KafkaBuilder,
+    decoder: D,
+    key_decoder: KD,
+    inner: Option<Arc<BaseConsumer<KafkaCallbackContext>>>,
+    decode_headers: bool,
+    _m: PhantomData<(M, K)>,
+}
+
+impl PollingConsumer {
+    #[inline]
+    pub fn new(config: Config) -> Self {
+        Self::new_with_decoder(Default::default(), config)
+    }
+}
+
+impl<M, D: Decoder<M>> PollingConsumer<M, D> {
+    pub fn new_with_decoder(decoder: D, config: Config) -> Self {
+        Self {
+            decode_headers: config.decode_headers,
+            builder: KafkaBuilder::new(config),
+            inner: None,
+            decoder,
+            key_decoder: crate::codec::BytesDecoder,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<M, D: Decoder<M>, K, KD: Decoder<K>> PollingConsumer<M, D, K, KD> {
+    /// Decodes message keys with `key_decoder` instead of returning them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_decoder` - The decoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The consumer producing messages with keys of type `K2`.
+    pub fn with_key_decoder<K2, KD2: Decoder<K2>>(
+        self,
+        key_decoder: KD2,
+    ) -> PollingConsumer<M, D, K2, KD2> {
+        PollingConsumer {
+            builder: self.builder,
+            decoder: self.decoder,
+            key_decoder,
+            inner: self.inner,
+            decode_headers: self.decode_headers,
+            _m: PhantomData,
+        }
+    }
+
+    /// Adds an interceptor seeing every received message and commit, applied on the next
+    /// [`connect`](Self::connect).
+    ///
+    /// # Arguments
+    ///
+    /// * `interceptor` - The interceptor, run after the ones added before.
+    ///
+    /// # Returns
+    ///
+    /// The consumer with the interceptor added.
+    pub fn with_interceptor<I: ConsumerInterceptor>(mut self, interceptor: I) -> Self {
+        self.builder.add_interceptor(Arc::new(interceptor));
+        self
+    }
+
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Creates the client and subscribes to `topics`, without blocking on the brokers.
+    ///
+    /// Unlike [`KafkaConsumer::connect`](super::KafkaConsumer::connect) the topics are not
+    /// created, even with `ensure_topics` set.
+    pub fn connect(&mut self, topics: &[&str]) -> Result<(), Error<D::Error>> {
+        self.inner = None;
+
+        let consumer = self.builder.build_base_consumer()?;
+        consumer
+            .subscribe(topics)
+            .map_err(|source| Error::Subscribe {
+                topic: topics.join(","),
+                source,
+            })?;
+        self.inner.replace(Arc::new(consumer));
+
+        Ok(())
+    }
+
+    /// Subscribes to the librdkafka statistics of this client, only emitted when
+    /// `statistics_interval_ms` is set.
+    #[inline]
+    pub fn statistics(&self) -> StatisticsReceiver {
+        self.builder.statistics()
+    }
+
+    /// Polls the client for up to `timeout`, serving its callbacks.
+    ///
+    /// # Returns
+    ///
+    /// The next message, or `None` if none arrived within `timeout`.
+    pub fn poll(&mut self, timeout: Duration) -> Option<Polled<M, K, D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let Some(consumer) = &self.inner else {
+            return Some(Err(Error::NoConnection));
+        };
+
+        // the partitions the last assignment could not reset are paused until then
+        if let Some(err) = consumer.context().take_offset_reset_error() {
+            return Some(Err(Error::KafkaError(err)));
+        }
+
+        let msg = match consumer.poll(timeout)? {
+            Ok(msg) => msg,
+            Err(err) => return Some(Err(Error::Receive(err))),
+        };
+
+        for interceptor in self.builder.interceptors() {
+            interceptor.on_consume(&msg);
+        }
+
+        #[cfg(feature = "metrics")]
+        crate::telemetry::message_received(
+            msg.topic(),
+            msg.partition(),
+            &self.builder.config().group_id,
+            msg.payload_len(),
+        );
+
+        let msg = SharedMessage::new(msg, consumer);
+        let payload = msg.payload_bytes();
+        let key = msg.key_bytes();
+
+        #[cfg(feature = "metrics")]
+        let decoding = std::time::Instant::now();
+
+        let decoded = decode_message(
+            &mut self.decoder,
+            &mut self.key_decoder,
+            self.decode_headers,
+            &msg,
+            payload.clone(),
+            key.clone(),
+        )
+        .map_err(|error| {
+            Error::DecodeFailed(Box::new(DecodeFailure {
+                raw: payload,
+                key,
+                error,
+            }))
+            .at(msg.topic(), msg.partition(), msg.offset())
+        });
+
+        #[cfg(feature = "metrics")]
+        crate::telemetry::decode_duration(
+            msg.topic(),
+            &self.builder.config().group_id,
+            decoding.elapsed(),
+        );
+
+        #[cfg(feature = "metrics")]
+        if decoded.is_err() {
+            crate::telemetry::decode_failed(
+                msg.topic(),
+                msg.partition(),
+                &self.builder.config().group_id,
+            );
+        }
+
+        Some(decoded)
+    }
+
+    /// Commits the offsets of the messages returned so far, blocking until the brokers
+    /// answer.
+    pub fn commit(&self) -> Result<(), Error<D::Error>> {
+        let consumer = self.inner.as_ref().ok_or(Error::NoConnection)?;
+
+        Ok(consumer.commit_consumer_state(CommitMode::Sync)?)
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+};
+
+use bytes::Bytes;
+use rdkafka::{
+    TopicPartitionList,
+    consumer::{BaseConsumer, Consumer},
+};
+
+use crate::{KafkaCallbackContext, Message};
+
+/// Item of [`KafkaConsumer::events`](super::KafkaConsumer::events), the messages along with
+/// the rebalances in between them.
+#[derive(Debug, Clone)]
+pub enum Event<M, K = Bytes> {
+    Message(Message<M, K>),
+
+    /// Partitions change hands, `revoked` is empty when the rebalance only assigns
+    /// partitions, e.g. when joining the group. Stateful stages snapshot the state of the
+    /// revoked partitions here.
+    RebalanceStarted {
+        revoked: TopicPartitionList,
+    },
+
+    /// The rebalance completed, the messages that follow are read from the new assignment.
+    /// Stateful stages restore the state of the assigned partitions here.
+    RebalanceCompleted {
+        assigned: TopicPartitionList,
+    },
+}
+
+pub(crate) enum RebalanceEvent {
+    Started(TopicPartitionList),
+    Completed(TopicPartitionList),
+}
+
+impl<M, K> From<RebalanceEvent> for Event<M, K> {
+    fn from(event: RebalanceEvent) -> Self {
+        match event {
+            RebalanceEvent::Started(revoked) => Event::RebalanceStarted { revoked },
+            RebalanceEvent::Completed(assigned) => Event::RebalanceCompleted { assigned },
+        }
+    }
+}
+
+#[derive(Default)]
+struct RebalanceState {
+    /// Events not returned yet, only queued once someone reads them.
+    queue: Option<VecDeque<RebalanceEvent>>,
+    in_progress: bool,
+}
+
+/// Rebalances of a consumer, see
+/// [`ConfigBuilder::quiesce_on_rebalance`](crate::config::ConfigBuilder::quiesce_on_rebalance).
+pub(crate) struct RebalanceEvents {
+    quiesce: bool,
+    state: Mutex<RebalanceState>,
+}
+
+impl RebalanceEvents {
+    pub(crate) fn new(quiesce: bool) -> Self {
+        Self {
+            quiesce,
+            state: Default::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RebalanceState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Takes the events queued so far, the following ones are queued until the next call.
+    pub(crate) fn drain(&self) -> Vec<RebalanceEvent> {
+        self.lock()
+            .queue
+            .get_or_insert_default()
+            .drain(..)
+            .collect()
+    }
+
+    fn push(state: &mut RebalanceState, event: RebalanceEvent) {
+        if let Some(queue) = &mut state.queue {
+            queue.push_back(event);
+        }
+    }
+
+    /// Starts a rebalance revoking `tpl`, the partitions kept are paused until it completes
+    /// when quiescing.
+    pub(crate) fn revoking(
+        &self,
+        consumer: &BaseConsumer<KafkaCallbackContext>,
+        tpl: &TopicPartitionList,
+    ) {
+        let mut state = self.lock();
+        state.in_progress = true;
+        Self::push(&mut state, RebalanceEvent::Started(tpl.clone()));
+        drop(state);
+
+        if !self.quiesce {
+            return;
+        }
+
+        let Ok(assignment) = consumer.assignment() else {
+            return;
+        };
+
+        let mut kept = TopicPartitionList::new();
+        for elem in assignment.elements() {
+            if tpl.find_partition(elem.topic(), elem.partition()).is_none() {
+                kept.add_partition(elem.topic(), elem.partition());
+            }
+        }
+
+        // eager rebalances revoke the whole assignment
+        if kept.count() == 0 {
+            return;
+        }
+
+        if let Err(err) = consumer.pause(&kept) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka pause on rebalance failed: {err}");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka pause on rebalance failed: {err}");
+        }
+    }
+
+    /// Completes the rebalance assigning `tpl`, resuming the partitions paused meanwhile.
+    pub(crate) fn assigned(
+        &self,
+        consumer: &impl Consumer<KafkaCallbackContext>,
+        tpl: &TopicPartitionList,
+    ) {
+        let mut state = self.lock();
+        if !state.in_progress {
+            Self::push(
+                &mut state,
+                RebalanceEvent::Started(TopicPartitionList::new()),
+            );
+        }
+
+        state.in_progress = false;
+        Self::push(&mut state, RebalanceEvent::Completed(tpl.clone()));
+        drop(state);
+
+        if !self.quiesce {
+            return;
+        }
+
+        let resumed = consumer
+            .assignment()
+            .and_then(|assignment| consumer.resume(&assignment));
+
+        if let Err(err) = resumed {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka resume after rebalance failed: {err}");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka resume after rebalance failed: {err}");
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{any::Any, ops::Deref, sync::Arc};
+
+use bytes::Bytes;
+use rdkafka::{Message as _, message::BorrowedMessage};
+
+/// A consumed message sharing its key and payload as [`Bytes`] without copying them out of
+/// the librdkafka buffers.
+///
+/// The returned buffers keep the message, and with it the client it was read from, alive
+/// until the last of them is dropped.
+pub(crate) struct SharedMessage(Arc<Owned>);
+
+struct Owned {
+    // declared first so it is destroyed before the client it was read from
+    msg: BorrowedMessage<'static>,
+    _client: Arc<dyn Any + Send + Sync>,
+}
+
+impl SharedMessage {
+    pub(crate) fn new<'a, C: Send + Sync + 'static>(
+        msg: BorrowedMessage<'a>,
+        client: &'a Arc<C>,
+    ) -> Self {
+        // SAFETY: the message only borrows the client for as long as it lives, which the
+        // clone of the `Arc` kept along with it guarantees until it is destroyed.
+        let msg =
+            unsafe { std::mem::transmute::<BorrowedMessage<'a>, BorrowedMessage<'static>>(msg) };
+
+        Self(Arc::new(Owned {
+            msg,
+            _client: client.clone(),
+        }))
+    }
+
+    /// The payload of the message, `None` for a tombstone.
+    pub(crate) fn payload_bytes(&self) -> Option<Bytes> {
+        self.0.msg.payload()?;
+
+        Some(Bytes::from_owner(Payload(self.0.clone())))
+    }
+
+    pub(crate) fn key_bytes(&self) -> Option<Bytes> {
+        self.0.msg.key()?;
+
+        Some(Bytes::from_owner(Key(self.0.clone())))
+    }
+}
+
+impl Deref for SharedMessage {
+    type Target = BorrowedMessage<'static>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0.msg
+    }
+}
+
+struct Payload(Arc<Owned>);
+
+impl AsRef<[u8]> for Payload {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.msg.payload().unwrap_or_default()
+    }
+}
+
+struct Key(Arc<Owned>);
+
+impl AsRef<[u8]> for Key {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.msg.key().unwrap_or_default()
+    }
+}
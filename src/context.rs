@@ -0,0 +1,407 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rdkafka::{
+    ClientContext, Offset, TopicPartitionList,
+    config::RDKafkaLogLevel,
+    consumer::{BaseConsumer, Consumer, ConsumerContext, Rebalance, RebalanceProtocol},
+    error::{KafkaError, KafkaResult},
+    message::Message,
+    producer::{
+        DeliveryResult, ProducerContext,
+        future_producer::{Delivery, OwnedDeliveryResult},
+    },
+    types::RDKafkaRespErr,
+};
+use tokio::sync::{oneshot, watch};
+
+use crate::{
+    config::{AutoOffsetReset, ClientErrorHandler},
+    consumer::{AckTracker, RebalanceEvents},
+    interceptor::ConsumerInterceptor,
+    offset_store::{Deferred, DeferredAssignments},
+    statistics::Statistics,
+};
+
+/// Callbacks of the underlying librdkafka clients, run in addition to the ones of the
+/// crate, see [`ConfigBuilder::client_hooks`](crate::config::ConfigBuilder::client_hooks).
+///
+/// Every method defaults to a no-op. They are called from librdkafka threads and must
+/// return quickly.
+#[allow(unused_variables)]
+pub trait ClientHooks: Send + Sync + 'static {
+    /// A librdkafka log line, already filtered by the configured `log_level`.
+    fn log(&self, level: RDKafkaLogLevel, facility: &str, message: &str) {}
+
+    /// Statistics emitted every `statistics_interval_ms`.
+    fn stats(&self, statistics: &Statistics) {}
+
+    /// A global client error, e.g. all brokers down.
+    fn error(&self, error: &KafkaError, reason: &str) {}
+
+    /// Runs before partitions are assigned or revoked.
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {}
+
+    /// Runs after partitions were assigned or revoked.
+    fn post_rebalance(&self, rebalance: &Rebalance<'_>) {}
+
+    /// Runs after offsets were committed, automatically or not.
+    fn commit(&self, result: &KafkaResult<()>, offsets: &TopicPartitionList) {}
+}
+
+impl fmt::Debug for dyn ClientHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClientHooks")
+    }
+}
+
+pub(crate) struct KafkaCallbackContext {
+    pub(crate) statistics: watch::Sender<Option<Arc<Statistics>>>,
+    pub(crate) on_error: Option<ClientErrorHandler>,
+    pub(crate) hooks: Option<Arc<dyn ClientHooks>>,
+    pub(crate) interceptors: Vec<Arc<dyn ConsumerInterceptor>>,
+
+    /// Acks committed before partitions are revoked, set with `commit_on_revoke`.
+    pub(crate) acks: Option<Arc<AckTracker>>,
+
+    /// Rebalances returned by the event streams of the consumer.
+    pub(crate) rebalance_events: RebalanceEvents,
+
+    /// `auto_offset_reset` overrides of topics, applied to the assignment of their partitions.
+    pub(crate) offset_resets: BTreeMap<String, AutoOffsetReset>,
+
+    /// Assignments held back until the consumer loaded their offsets, set with an offset store.
+    pub(crate) deferred_assignments: Option<DeferredAssignments>,
+
+    /// Failure to apply `offset_resets` to the last assignment, returned by the consumer.
+    pub(crate) offset_reset_error: Mutex<Option<KafkaError>>,
+}
+
+/// How long an assignment waits for the committed offsets of the topics with an
+/// `auto_offset_reset` override.
+const OFFSET_RESET_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl KafkaCallbackContext {
+    /// Takes the failure to apply `topic_offset_reset` to the last assignment, whose
+    /// overridden partitions were paused instead of fetched from `auto_offset_reset`.
+    pub(crate) fn take_offset_reset_error(&self) -> Option<KafkaError> {
+        self.offset_reset_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+
+    /// Assigns the partitions deferred to the offset store of the consumer, completing their
+    /// rebalance.
+    pub(crate) fn complete_assignment<C: Consumer<Self>>(&self, consumer: &C, deferred: &Deferred) {
+        self.update_assignment(consumer, true, &deferred.tpl);
+        self.rebalanced(consumer, &Rebalance::Assign(&deferred.tpl));
+
+        if deferred.paused.count() > 0 {
+            self.pause_unreset(consumer, &deferred.paused);
+        }
+    }
+
+    fn cooperative<C: Consumer<Self>>(&self, consumer: &C) -> bool {
+        matches!(
+            consumer.rebalance_protocol(),
+            RebalanceProtocol::Cooperative
+        )
+    }
+
+    /// Assigns or unassigns `tpl` like the default rebalance of rdkafka.
+    fn update_assignment<C: Consumer<Self>>(
+        &self,
+        consumer: &C,
+        assign: bool,
+        tpl: &TopicPartitionList,
+    ) {
+        let updated = match (assign, self.cooperative(consumer)) {
+            (true, true) => consumer.incremental_assign(tpl),
+            (true, false) => consumer.assign(tpl),
+            (false, true) => consumer.incremental_unassign(tpl),
+            (false, false) => consumer.unassign(),
+        };
+
+        if let Err(err) = updated {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka rebalance failed to update the assignment: {err}");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka rebalance failed to update the assignment: {err}");
+        }
+    }
+
+    /// Runs once the assignment was updated, from `post_rebalance` or the deferred assignment.
+    fn rebalanced<C: Consumer<Self>>(&self, consumer: &C, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Assign(tpl) = rebalance {
+            if let Some(acks) = &self.acks {
+                acks.assigned(tpl);
+            }
+
+            self.rebalance_events.assigned(consumer, tpl);
+        }
+
+        if let Some(hooks) = &self.hooks {
+            hooks.post_rebalance(rebalance);
+        }
+    }
+
+    fn pause_unreset<C: Consumer<Self>>(&self, consumer: &C, tpl: &TopicPartitionList) {
+        if let Err(err) = consumer.pause(tpl) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka failed to pause the partitions left unreset: {err}");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka failed to pause the partitions left unreset: {err}");
+        }
+    }
+
+    fn offset_reset_failed(&self, err: KafkaError) {
+        self.error(
+            err.clone(),
+            "offset reset of the assigned partitions failed",
+        );
+
+        *self
+            .offset_reset_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(err);
+    }
+
+    /// Assigned partitions of the topics overriding `auto_offset_reset`.
+    fn overridden(&self, tpl: &TopicPartitionList) -> TopicPartitionList {
+        let mut overridden = TopicPartitionList::new();
+        for elem in tpl.elements() {
+            if self.offset_reset(elem.topic()).is_some() {
+                overridden.add_partition(elem.topic(), elem.partition());
+            }
+        }
+
+        overridden
+    }
+
+    fn offset_reset(&self, topic: &str) -> Option<Offset> {
+        match self.offset_resets.get(topic)? {
+            AutoOffsetReset::Earliest => Some(Offset::Beginning),
+            AutoOffsetReset::Latest => Some(Offset::End),
+            AutoOffsetReset::None => None,
+        }
+    }
+
+    /// Starts the `overridden` partitions without a committed offset at the beginning or
+    /// the end of their topic, rewriting the assignment before anything is fetched.
+    fn reset_offsets(
+        &self,
+        consumer: &BaseConsumer<Self>,
+        overridden: &TopicPartitionList,
+        tpl: &mut TopicPartitionList,
+    ) -> KafkaResult<()> {
+        let committed = consumer.committed_offsets(overridden.clone(), OFFSET_RESET_TIMEOUT)?;
+        for elem in committed.elements() {
+            if let (Offset::Invalid, Some(offset)) =
+                (elem.offset(), self.offset_reset(elem.topic()))
+            {
+                tpl.set_partition_offset(elem.topic(), elem.partition(), offset)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ClientContext for KafkaCallbackContext {
+    fn error(&self, error: KafkaError, reason: &str) {
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            code = ?error.rdkafka_error_code(),
+            reason,
+            "Kafka global error occured: {error}"
+        );
+
+        #[cfg(not(feature = "tracing"))]
+        log::error!("Kafka global error occured: {error}, reason: {reason}");
+
+        if let Some(handler) = &self.on_error {
+            (handler.0)(&error, reason);
+        }
+
+        if let Some(hooks) = &self.hooks {
+            hooks.error(&error, reason);
+        }
+    }
+
+    /// Forwards the librdkafka logs, already filtered by the configured `log_level`.
+    fn log(&self, level: RDKafkaLogLevel, fac: &str, log_message: &str) {
+        forward_log(level, fac, log_message);
+
+        if let Some(hooks) = &self.hooks {
+            hooks.log(level, fac, log_message);
+        }
+    }
+
+    fn stats(&self, statistics: Statistics) {
+        if let Some(hooks) = &self.hooks {
+            hooks.stats(&statistics);
+        }
+
+        self.statistics.send_replace(Some(Arc::new(statistics)));
+    }
+}
+
+impl ConsumerContext for KafkaCallbackContext {
+    /// Mirrors the default rebalance of rdkafka, rewriting the offsets of the topics
+    /// overriding `auto_offset_reset` before the partitions are assigned.
+    fn rebalance(
+        &self,
+        base_consumer: &BaseConsumer<Self>,
+        err: RDKafkaRespErr,
+        tpl: &mut TopicPartitionList,
+    ) {
+        let assign = matches!(err, RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS);
+        let overridden = if assign && !self.offset_resets.is_empty() {
+            self.overridden(tpl)
+        } else {
+            TopicPartitionList::new()
+        };
+
+        let reset = if overridden.count() > 0 {
+            self.reset_offsets(base_consumer, &overridden, tpl)
+        } else {
+            Ok(())
+        };
+
+        let rebalance = match err {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => Rebalance::Assign(tpl),
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => Rebalance::Revoke(tpl),
+            _ => Rebalance::Error(KafkaError::Rebalance(err.into())),
+        };
+
+        self.pre_rebalance(base_consumer, &rebalance);
+
+        if let Some(deferred) = &self.deferred_assignments {
+            if assign {
+                // assigned by the consumer once it loaded their offsets from its store
+                let paused = match reset {
+                    Ok(()) => TopicPartitionList::new(),
+                    Err(..) => overridden,
+                };
+
+                if let Err(err) = deferred.defer(tpl, &paused) {
+                    self.error(err, "deferring the assigned partitions failed");
+                }
+
+                if let Err(err) = reset {
+                    self.offset_reset_failed(err);
+                }
+
+                return;
+            }
+
+            // the partitions still deferred were never handed to librdkafka
+            if self.cooperative(base_consumer) {
+                self.update_assignment(base_consumer, false, &deferred.revoke(tpl));
+            } else {
+                deferred.clear();
+                self.update_assignment(base_consumer, false, tpl);
+            }
+        } else {
+            self.update_assignment(base_consumer, assign, tpl);
+        }
+
+        self.post_rebalance(base_consumer, &rebalance);
+
+        // paused after `post_rebalance`, which resumes the assignment once quiesced
+        if let Err(err) = reset {
+            self.pause_unreset(base_consumer, &overridden);
+            self.offset_reset_failed(err);
+        }
+    }
+
+    fn pre_rebalance(&self, base_consumer: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        if let Some(hooks) = &self.hooks {
+            hooks.pre_rebalance(rebalance);
+        }
+
+        if let Rebalance::Revoke(tpl) = rebalance {
+            self.rebalance_events.revoking(base_consumer, tpl);
+
+            if let Some(acks) = &self.acks {
+                acks.revoke(base_consumer, tpl);
+            }
+        }
+    }
+
+    fn post_rebalance(&self, base_consumer: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        self.rebalanced(base_consumer, rebalance);
+    }
+
+    fn commit_callback(&self, result: KafkaResult<()>, offsets: &TopicPartitionList) {
+        if let Some(hooks) = &self.hooks {
+            hooks.commit(&result, offsets);
+        }
+
+        for interceptor in &self.interceptors {
+            interceptor.on_commit(&result, offsets);
+        }
+    }
+}
+
+/// Forwards the deliveries of a `ThreadedProducer` to the channel sent with the message.
+impl ProducerContext for KafkaCallbackContext {
+    type DeliveryOpaque = Box<oneshot::Sender<OwnedDeliveryResult>>;
+
+    fn delivery(&self, result: &DeliveryResult<'_>, tx: Self::DeliveryOpaque) {
+        let result = match result {
+            Ok(msg) => Ok(Delivery {
+                partition: msg.partition(),
+                offset: msg.offset(),
+                timestamp: msg.timestamp(),
+            }),
+            Err((error, msg)) => Err((error.clone(), msg.detach())),
+        };
+
+        // the sender stops waiting when its send is dropped
+        let _ = tx.send(result);
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn forward_log(level: RDKafkaLogLevel, fac: &str, log_message: &str) {
+    match level {
+        RDKafkaLogLevel::Emerg
+        | RDKafkaLogLevel::Alert
+        | RDKafkaLogLevel::Critical
+        | RDKafkaLogLevel::Error => {
+            tracing::error!(target: "librdkafka", facility = fac, "{log_message}")
+        }
+        RDKafkaLogLevel::Warning => {
+            tracing::warn!(target: "librdkafka", facility = fac, "{log_message}")
+        }
+        RDKafkaLogLevel::Notice | RDKafkaLogLevel::Info => {
+            tracing::info!(target: "librdkafka", facility = fac, "{log_message}")
+        }
+        RDKafkaLogLevel::Debug => {
+            tracing::debug!(target: "librdkafka", facility = fac, "{log_message}")
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+fn forward_log(level: RDKafkaLogLevel, fac: &str, log_message: &str) {
+    let level = match level {
+        RDKafkaLogLevel::Emerg
+        | RDKafkaLogLevel::Alert
+        | RDKafkaLogLevel::Critical
+        | RDKafkaLogLevel::Error => log::Level::Error,
+        RDKafkaLogLevel::Warning => log::Level::Warn,
+        RDKafkaLogLevel::Notice | RDKafkaLogLevel::Info => log::Level::Info,
+        RDKafkaLogLevel::Debug => log::Level::Debug,
+    };
+
+    log::log!(target: "librdkafka", level, "librdkafka: {fac} {log_message}");
+}
@@ -1,6 +1,11 @@
-use rdkafka::error::KafkaError;
+use std::time::Duration;
+
+use bytes::Bytes;
+use rdkafka::{error::KafkaError, types::RDKafkaErrorCode};
 use thiserror::Error;
 
+use crate::config::Acks;
+
 #[derive(Error, Debug)]
 pub enum Error<E> {
     #[error("No connection: Attempting to send or receive without an established connection")]
@@ -9,6 +14,450 @@ pub enum Error<E> {
     #[error("Kafka error: {0}")]
     KafkaError(#[from] KafkaError),
 
+    #[error("Failed to create the Kafka client: {0}")]
+    Connect(KafkaError),
+
+    #[error("Failed to subscribe to `{topic}`: {source}")]
+    Subscribe { topic: String, source: KafkaError },
+
+    #[error("Failed to receive a message: {0}")]
+    Receive(KafkaError),
+
+    #[error("Failed to deliver a message to `{topic}`: {source}")]
+    Deliver { topic: String, source: KafkaError },
+
+    #[error("Admin error: {0}")]
+    AdminError(AdminError),
+
     #[error("Message encode/decode error: {0}")]
     MessageCodecError(E),
+
+    #[error("Topic `{topic}` is missing or has {found} partitions, at least {required} required")]
+    TopicMissing {
+        topic: String,
+        found: usize,
+        required: usize,
+    },
+
+    #[error("Message key encode/decode error: {0}")]
+    KeyCodecError(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Invalid configuration: {0:?}")]
+    InvalidConfig(Vec<ConfigError>),
+
+    #[error("Message to `{topic}` rejected: {rejection}")]
+    Rejected {
+        topic: String,
+        rejection: crate::producer::Rejection,
+    },
+
+    #[error("Circuit breaker is open, retry in {retry_after:?}")]
+    CircuitOpen { retry_after: Duration },
+
+    #[error("Offset store error: {0}")]
+    OffsetStore(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("{}", .0.error)]
+    DecodeFailed(Box<DecodeFailure<E>>),
+
+    #[error("{} (topic `{topic}`, partition {partition}, offset {offset})", .error)]
+    At {
+        topic: String,
+        partition: i32,
+        offset: i64,
+        error: Box<Error<E>>,
+    },
+}
+
+impl<E> From<AdminError> for Error<E> {
+    /// Keeps configuration errors distinct so the reconnect loops still fail fast on them.
+    fn from(err: AdminError) -> Self {
+        match err {
+            AdminError::InvalidConfig(errors) => Error::InvalidConfig(errors),
+            err => Error::AdminError(err),
+        }
+    }
+}
+
+impl<E> Error<E> {
+    /// Attaches the coordinates of the message the error happened on.
+    pub fn at(self, topic: impl Into<String>, partition: i32, offset: i64) -> Self {
+        Error::At {
+            topic: topic.into(),
+            partition,
+            offset,
+            error: Box::new(self),
+        }
+    }
+
+    /// Returns the `(topic, partition, offset)` of the message the error happened on.
+    pub fn coordinates(&self) -> Option<(&str, i32, i64)> {
+        match self {
+            Error::At {
+                topic,
+                partition,
+                offset,
+                ..
+            } => Some((topic, *partition, *offset)),
+            _ => None,
+        }
+    }
+
+    /// Returns the message the consumer failed to decode, if this is a decode error.
+    pub fn decode_failure(&self) -> Option<&DecodeFailure<E>> {
+        match self {
+            Error::At { error, .. } => error.decode_failure(),
+            Error::DecodeFailed(failure) => Some(failure),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its message coordinates and raw payload.
+    pub fn root(&self) -> &Error<E> {
+        match self {
+            Error::At { error, .. } => error.root(),
+            Error::DecodeFailed(failure) => failure.error.root(),
+            err => err,
+        }
+    }
+
+    /// Whether retrying the operation may succeed: transient broker, network and timeout
+    /// errors, a full producer queue or an open circuit. Codec errors are never retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self.root() {
+            Error::NoConnection | Error::CircuitOpen { .. } => true,
+            Error::AdminError(AdminError::Topic { code, .. } | AdminError::Group { code, .. }) => {
+                is_retryable_code(*code)
+            }
+            err => err.kafka_error().is_some_and(|err| match err {
+                KafkaError::Transaction(err) => err.is_retriable(),
+                err => err.rdkafka_error_code().is_some_and(is_retryable_code),
+            }),
+        }
+    }
+
+    /// Whether the client can not recover and must be rebuilt or the application stopped:
+    /// invalid configuration, authentication and authorization failures, fenced producers
+    /// and errors librdkafka reports as fatal.
+    pub fn is_fatal(&self) -> bool {
+        match self.root() {
+            Error::InvalidConfig(..) | Error::AdminError(AdminError::InvalidConfig(..)) => true,
+            err => err.kafka_error().is_some_and(|err| match err {
+                KafkaError::Transaction(err) => err.is_fatal(),
+                err => err.rdkafka_error_code().is_some_and(is_fatal_code),
+            }),
+        }
+    }
+
+    fn kafka_error(&self) -> Option<&KafkaError> {
+        match self {
+            Error::KafkaError(err)
+            | Error::Connect(err)
+            | Error::Receive(err)
+            | Error::Subscribe { source: err, .. }
+            | Error::Deliver { source: err, .. }
+            | Error::AdminError(AdminError::KafkaError(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn is_retryable_code(code: RDKafkaErrorCode) -> bool {
+    matches!(
+        code,
+        RDKafkaErrorCode::QueueFull
+            | RDKafkaErrorCode::MessageTimedOut
+            | RDKafkaErrorCode::RequestTimedOut
+            | RDKafkaErrorCode::OperationTimedOut
+            | RDKafkaErrorCode::TimedOutQueue
+            | RDKafkaErrorCode::BrokerTransportFailure
+            | RDKafkaErrorCode::AllBrokersDown
+            | RDKafkaErrorCode::NetworkException
+            | RDKafkaErrorCode::LeaderNotAvailable
+            | RDKafkaErrorCode::PreferredLeaderNotAvailable
+            | RDKafkaErrorCode::NotLeaderForPartition
+            | RDKafkaErrorCode::FencedLeaderEpoch
+            | RDKafkaErrorCode::UnknownLeaderEpoch
+            | RDKafkaErrorCode::NotEnoughReplicas
+            | RDKafkaErrorCode::NotEnoughReplicasAfterAppend
+            | RDKafkaErrorCode::KafkaStorageError
+            | RDKafkaErrorCode::OffsetNotAvailable
+            | RDKafkaErrorCode::CoordinatorNotAvailable
+            | RDKafkaErrorCode::CoordinatorLoadInProgress
+            | RDKafkaErrorCode::NotCoordinator
+            | RDKafkaErrorCode::NotController
+            | RDKafkaErrorCode::RebalanceInProgress
+            | RDKafkaErrorCode::ThrottlingQuotaExceeded
+            | RDKafkaErrorCode::Retry
+    )
+}
+
+fn is_fatal_code(code: RDKafkaErrorCode) -> bool {
+    matches!(
+        code,
+        RDKafkaErrorCode::Fatal
+            | RDKafkaErrorCode::InvalidConfig
+            | RDKafkaErrorCode::Authentication
+            | RDKafkaErrorCode::SaslAuthenticationFailed
+            | RDKafkaErrorCode::SSL
+            | RDKafkaErrorCode::TopicAuthorizationFailed
+            | RDKafkaErrorCode::GroupAuthorizationFailed
+            | RDKafkaErrorCode::ClusterAuthorizationFailed
+            | RDKafkaErrorCode::TransactionalIdAuthorizationFailed
+            | RDKafkaErrorCode::ProducerFenced
+            | RDKafkaErrorCode::InvalidProducerEpoch
+            | RDKafkaErrorCode::UnsupportedVersion
+    )
+}
+
+/// A consumed message that could not be decoded, kept as received so it can be
+/// inspected or forwarded to a dead letter topic.
+#[derive(Debug)]
+pub struct DecodeFailure<E> {
+    /// The raw message payload.
+    pub raw: Option<Bytes>,
+    /// The raw message key.
+    pub key: Option<Bytes>,
+    /// The message or key codec error.
+    pub error: Error<E>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Invalid value `{value}` for `{key}`")]
+    InvalidValue { key: String, value: String },
+
+    #[error("Unknown config option `{0}`")]
+    UnknownOption(String),
+
+    #[error("Invalid connection url")]
+    InvalidUrl,
+
+    #[error("No brokers configured")]
+    NoBrokers,
+
+    #[error("Group id is required for consumers")]
+    EmptyGroupId,
+
+    #[error("Idempotent producer requires acks `all`, got `{0}`")]
+    IdempotenceRequiresAcksAll(Acks),
+
+    #[error("Ack handles require `commit_on_revoke` to be set")]
+    AckRequiresCommitOnRevoke,
+
+    #[error("Max message size {size} is below the minimum of {min} bytes")]
+    MessageSizeTooSmall { size: u32, min: u32 },
+
+    #[error("`{0}` and `{1}` can not both be set")]
+    ConflictingOptions(&'static str, &'static str),
+
+    #[error("Secret `{secret}` is unavailable: {reason}")]
+    SecretUnavailable { secret: String, reason: String },
+}
+
+impl ConfigError {
+    pub(crate) fn invalid_value(key: impl Into<String>, value: impl Into<String>) -> Self {
+        ConfigError::InvalidValue {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AdminError {
+    #[error("Kafka error: {0}")]
+    KafkaError(#[from] KafkaError),
+
+    #[error("Invalid configuration: {0:?}")]
+    InvalidConfig(Vec<ConfigError>),
+
+    #[error("Operation on topic `{topic}` failed: {code}")]
+    Topic {
+        topic: String,
+        code: RDKafkaErrorCode,
+    },
+
+    #[error("Operation on consumer group `{group}` failed: {code}")]
+    Group {
+        group: String,
+        code: RDKafkaErrorCode,
+    },
+
+    #[error("ACL operation failed: {code}: {message}")]
+    Acl {
+        code: RDKafkaErrorCode,
+        message: String,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum ClaimCheckError<E> {
+    #[error("Message encode/decode error: {0}")]
+    Codec(E),
+
+    #[error("Blob store error: {0}")]
+    Store(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Invalid claim check envelope")]
+    InvalidEnvelope,
+}
+
+#[derive(Error, Debug)]
+pub enum DebeziumError<E> {
+    #[error("Message encode/decode error: {0}")]
+    Codec(E),
+
+    #[error("Unsupported Debezium operation `{0}`")]
+    UnsupportedOp(String),
+
+    #[error("Debezium `{0}` event without `after` state")]
+    MissingAfter(&'static str),
+}
+
+#[cfg(feature = "schema-registry")]
+#[derive(Error, Debug)]
+pub enum SchemaRegistryError {
+    #[error("Schema registry request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Schema registry responded {status} with error {code}: {message}")]
+    Api {
+        status: u16,
+        code: i32,
+        message: String,
+    },
+
+    #[error("Invalid schema registry url `{0}`")]
+    InvalidUrl(String),
+
+    #[error("Schema registry config error: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("Schema is not cached and can not be fetched outside of a multi-threaded runtime")]
+    RuntimeUnavailable,
+}
+
+#[cfg(feature = "schema-registry")]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WireFormatError {
+    #[error("Payload is too short for the schema registry header")]
+    Truncated,
+
+    #[error("Unknown magic byte {0}")]
+    UnknownMagicByte(u8),
+
+    #[error("Invalid Protobuf message indexes")]
+    InvalidMessageIndexes,
+}
+
+#[cfg(feature = "avro")]
+#[derive(Error, Debug)]
+pub enum AvroCodecError {
+    #[error("Avro error: {0}")]
+    Avro(#[from] apache_avro::Error),
+
+    #[error("Wire format error: {0}")]
+    WireFormat(#[from] WireFormatError),
+
+    #[error("Schema registry error: {0}")]
+    Registry(#[from] SchemaRegistryError),
+
+    #[error("Schema {0} is not an Avro schema")]
+    NotAvro(u32),
+}
+
+#[cfg(feature = "protobuf")]
+#[derive(Error, Debug)]
+pub enum ProtoCodecError {
+    #[error("Protobuf encode error: {0}")]
+    Encode(#[from] prost::EncodeError),
+
+    #[error("Protobuf decode error: {0}")]
+    Decode(#[from] prost::DecodeError),
+
+    #[error("Wire format error: {0}")]
+    WireFormat(#[from] WireFormatError),
+
+    #[error("Schema registry error: {0}")]
+    Registry(#[from] SchemaRegistryError),
+}
+
+#[cfg(feature = "json-schema")]
+#[derive(Error, Debug)]
+pub enum JsonSchemaCodecError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid JSON schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Payload does not match the JSON schema: {0:?}")]
+    Invalid(Vec<String>),
+
+    #[error("Wire format error: {0}")]
+    WireFormat(#[from] WireFormatError),
+
+    #[error("Schema registry error: {0}")]
+    Registry(#[from] SchemaRegistryError),
+}
+
+#[cfg(feature = "bincode")]
+#[derive(Error, Debug)]
+pub enum BincodeCodecError {
+    #[error("Bincode encode error: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+
+    #[error("Bincode decode error: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+
+    #[error("Payload is empty, missing the version byte")]
+    Truncated,
+
+    #[error("Incompatible payload version {found}, expected {expected}")]
+    VersionMismatch { expected: u8, found: u8 },
+}
+
+#[cfg(feature = "encryption")]
+#[derive(Error, Debug)]
+pub enum EncryptionError<E> {
+    #[error("Message encode/decode error: {0}")]
+    Codec(E),
+
+    #[error("Key provider error: {0}")]
+    KeyProvider(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Unknown encryption key `{0}`")]
+    UnknownKey(String),
+
+    #[error("Encryption key id `{0}` is longer than 255 bytes")]
+    KeyIdTooLong(String),
+
+    #[error("Encrypted payload is truncated")]
+    Truncated,
+
+    #[error("Failed to encrypt payload")]
+    Encrypt,
+
+    #[error("Failed to decrypt payload: wrong key or corrupted data")]
+    Decrypt,
+}
+
+#[cfg(feature = "json")]
+#[derive(Error, Debug)]
+pub enum FixtureError {
+    #[error("Fixture IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to encode a fixture record: {0}")]
+    Encode(serde_json::Error),
+
+    #[error("Invalid fixture record at line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    #[error("Fixture has no more records")]
+    Exhausted,
 }
@@ -0,0 +1,194 @@
+//! Capture of consumed messages into JSON lines fixtures and their replay, for deterministic
+//! tests of pipelines against samples of real traffic.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+use bytes::Bytes;
+#[cfg(feature = "flowly")]
+use flowly::Service;
+use futures::future::BoxFuture;
+#[cfg(feature = "flowly")]
+use futures::{FutureExt, Stream};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{Message, error::FixtureError, io::MessageSource};
+
+#[derive(Serialize)]
+struct RecordRef<'a, M, K> {
+    key: Option<&'a K>,
+    ts_ms_utc: Option<i64>,
+    payload: Option<&'a M>,
+    partition: i32,
+    headers: Option<&'a [(String, Vec<u8>)]>,
+}
+
+#[derive(Deserialize)]
+struct Record<M, K> {
+    key: Option<K>,
+    ts_ms_utc: Option<i64>,
+    payload: Option<M>,
+    partition: i32,
+    headers: Option<Vec<(String, Vec<u8>)>>,
+}
+
+/// Writes the messages passing through it to a fixture, one JSON record per line.
+pub struct Recorder<W = BufWriter<File>> {
+    writer: W,
+}
+
+impl Recorder {
+    /// Creates a recorder writing to the file at `path`, truncating it.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Appends `msg` to the fixture.
+    pub fn record<M: Serialize, K: Serialize>(
+        &mut self,
+        msg: &Message<M, K>,
+    ) -> Result<(), FixtureError> {
+        let record = RecordRef {
+            key: msg.key.as_ref(),
+            ts_ms_utc: msg.ts_ms_utc,
+            payload: msg.payload.as_ref(),
+            partition: msg.partition,
+            headers: msg.headers.as_deref(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &record).map_err(FixtureError::Encode)?;
+        self.writer.write_all(b"\n")?;
+
+        Ok(())
+    }
+
+    /// Flushes the records written so far.
+    pub fn flush(&mut self) -> Result<(), FixtureError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, K, W> Service<Message<M, K>> for Recorder<W>
+where
+    M: Serialize + Send,
+    K: Serialize + Send,
+    W: Write + Send,
+{
+    type Out = Result<Message<M, K>, FixtureError>;
+
+    fn handle(
+        &mut self,
+        input: Message<M, K>,
+        _cx: &flowly::Context,
+    ) -> impl Stream<Item = Self::Out> + Send {
+        let res = self.record(&input).map(|_| input);
+        async move { res }.into_stream()
+    }
+}
+
+/// Reads the messages of a fixture written by a [`Recorder`] back, in order.
+pub struct ReplaySource<M, K = Bytes, R = BufReader<File>> {
+    reader: R,
+    line: usize,
+    buffer: String,
+    _m: PhantomData<(M, K)>,
+}
+
+impl<M, K> ReplaySource<M, K> {
+    /// Opens the fixture at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<M, K, R: BufRead> ReplaySource<M, K, R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            buffer: String::new(),
+            _m: PhantomData,
+        }
+    }
+
+    /// Returns the next message of the fixture, `None` once all of them were replayed.
+    pub fn next_message(&mut self) -> Result<Option<Message<M, K>>, FixtureError>
+    where
+        M: DeserializeOwned,
+        K: DeserializeOwned,
+    {
+        loop {
+            self.buffer.clear();
+            self.line += 1;
+
+            if self.reader.read_line(&mut self.buffer)? == 0 {
+                return Ok(None);
+            }
+
+            if self.buffer.trim().is_empty() {
+                continue;
+            }
+
+            let record: Record<M, K> =
+                serde_json::from_str(&self.buffer).map_err(|source| FixtureError::Json {
+                    line: self.line,
+                    source,
+                })?;
+
+            return Ok(Some(Message {
+                key: record.key,
+                ts_ms_utc: record.ts_ms_utc,
+                payload: record.payload,
+                partition: record.partition,
+                headers: record.headers,
+            }));
+        }
+    }
+}
+
+impl<M, K, R> MessageSource<Message<M, K>> for ReplaySource<M, K, R>
+where
+    M: DeserializeOwned + Send,
+    K: DeserializeOwned + Send,
+    R: BufRead + Send,
+{
+    type Error = FixtureError;
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<Message<M, K>, Self::Error>> {
+        let res = self
+            .next_message()
+            .and_then(|msg| msg.ok_or(FixtureError::Exhausted));
+
+        Box::pin(async move { res })
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, K, R, I> Service<I> for ReplaySource<M, K, R>
+where
+    M: DeserializeOwned + Send,
+    K: DeserializeOwned + Send,
+    R: BufRead + Send,
+    I: Send,
+{
+    type Out = Result<Message<M, K>, FixtureError>;
+
+    fn handle(&mut self, _input: I, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async_stream::stream! {
+            while let Some(msg) = self.next_message().transpose() {
+                yield msg;
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, SystemTime};
+
+use rdkafka::{
+    Offset,
+    consumer::{Consumer, StreamConsumer},
+};
+
+use crate::{KafkaCallbackContext, runtime::Runtime};
+
+/// How long the brokers may take to answer a health check.
+pub(crate) const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Snapshot of the Kafka connectivity of a consumer or producer, meant to back liveness
+/// and readiness endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Health {
+    /// A client is created and was not dropped after a fatal error.
+    pub connected: bool,
+
+    /// When the last message was received by a consumer or delivered by a producer.
+    pub last_activity: Option<SystemTime>,
+
+    /// The brokers answered a metadata request within the health check timeout.
+    pub brokers_reachable: bool,
+
+    /// Messages between the position and the high watermark summed over the assigned
+    /// partitions, always `None` for producers.
+    pub lag: Option<i64>,
+}
+
+impl Health {
+    pub(crate) fn disconnected(last_activity: Option<SystemTime>) -> Self {
+        Self {
+            connected: false,
+            last_activity,
+            brokers_reachable: false,
+            lag: None,
+        }
+    }
+
+    /// Whether the client is connected and the brokers are reachable.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.connected && self.brokers_reachable
+    }
+}
+
+/// Sums the lag of the assigned partitions, partitions without a position yet are
+/// skipped.
+pub(crate) fn consumer_lag(
+    consumer: &StreamConsumer<KafkaCallbackContext, Runtime>,
+) -> Option<i64> {
+    let position = consumer.position().ok()?;
+    let mut lag = 0;
+
+    for elem in position.elements() {
+        if let Offset::Offset(offset) = elem.offset() {
+            let (_, high) = consumer
+                .fetch_watermarks(elem.topic(), elem.partition(), HEALTH_CHECK_TIMEOUT)
+                .ok()?;
+
+            lag += (high - offset).max(0);
+        }
+    }
+
+    Some(lag)
+}
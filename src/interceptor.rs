@@ -0,0 +1,140 @@
+//! Interceptors observing and decorating the records of the clients, like the producer and
+//! consumer interceptors of the Java client.
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rdkafka::{
+    TopicPartitionList,
+    error::{KafkaError, KafkaResult},
+    message::{BorrowedMessage, OwnedHeaders},
+    producer::future_producer::Delivery,
+};
+
+/// Header stamped by a [`LatencyTracker`] with the milliseconds since the epoch at which the
+/// message was sent.
+pub const PRODUCED_AT_HEADER: &str = "flowly.produced.at";
+
+/// Callbacks around the messages of a producer, see
+/// [`KafkaProducer::with_interceptor`](crate::producer::KafkaProducer::with_interceptor).
+///
+/// Every method defaults to a no-op, interceptors run in the order they were added.
+#[allow(unused_variables)]
+pub trait ProducerInterceptor: Send + Sync + 'static {
+    /// Runs before a message is produced to `topic`, the `headers` pushed are sent in
+    /// addition to the headers of the message, e.g. a tenant id.
+    fn on_send(&self, topic: &str, headers: &mut Vec<(String, Vec<u8>)>) {}
+
+    /// Runs once a message produced to `topic` was acknowledged or failed.
+    fn on_ack(&self, topic: &str, result: Result<&Delivery, &KafkaError>) {}
+
+    /// Runs after [`on_ack`](Self::on_ack) with the record as it was sent and how long its
+    /// delivery took.
+    fn on_delivery(
+        &self,
+        record: &SentRecord<'_>,
+        result: Result<&Delivery, &KafkaError>,
+        elapsed: Duration,
+    ) {
+    }
+}
+
+/// A record as it was sent to the brokers, see [`ProducerInterceptor::on_delivery`].
+#[derive(Debug, Clone, Copy)]
+pub struct SentRecord<'a> {
+    pub topic: &'a str,
+    pub key: Option<&'a [u8]>,
+    pub payload_len: usize,
+
+    /// The headers of the message along with the ones added by the interceptors.
+    pub headers: Option<&'a OwnedHeaders>,
+}
+
+/// Callbacks around the messages of a consumer, see
+/// [`KafkaConsumer::with_interceptor`](crate::consumer::KafkaConsumer::with_interceptor).
+///
+/// Every method defaults to a no-op, interceptors run in the order they were added.
+#[allow(unused_variables)]
+pub trait ConsumerInterceptor: Send + Sync + 'static {
+    /// Runs for every received message before it is decoded.
+    fn on_consume(&self, msg: &BorrowedMessage<'_>) {}
+
+    /// Runs after offsets were committed, automatically or not. Called from librdkafka
+    /// threads, it must return quickly.
+    fn on_commit(&self, result: &KafkaResult<()>, offsets: &TopicPartitionList) {}
+}
+
+impl fmt::Debug for dyn ProducerInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProducerInterceptor")
+    }
+}
+
+impl fmt::Debug for dyn ConsumerInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConsumerInterceptor")
+    }
+}
+
+/// Measures how long messages take from the producer to the consumer.
+///
+/// Added to a producer it stamps the sent messages with the [`PRODUCED_AT_HEADER`]. Added
+/// to a consumer it records the produce to consume latency of the stamped messages and the
+/// broker to consume latency of topics with `LogAppendTime` timestamps, as the
+/// `kafka_consumer_e2e_latency_seconds` and `kafka_consumer_broker_latency_seconds`
+/// histograms of the `metrics` feature. Both sides rely on the clocks of the hosts being
+/// in sync.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyTracker;
+
+impl LatencyTracker {
+    #[inline]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ProducerInterceptor for LatencyTracker {
+    fn on_send(&self, _topic: &str, headers: &mut Vec<(String, Vec<u8>)>) {
+        headers.push((
+            PRODUCED_AT_HEADER.to_string(),
+            now_ms().to_string().into_bytes(),
+        ));
+    }
+}
+
+impl ConsumerInterceptor for LatencyTracker {
+    #[cfg(feature = "metrics")]
+    fn on_consume(&self, msg: &BorrowedMessage<'_>) {
+        use rdkafka::{Message as _, Timestamp, message::Headers as _};
+
+        let now = now_ms();
+        let elapsed = |ts: i64| Duration::from_millis((now - ts).max(0) as u64);
+
+        let produced = msg
+            .headers()
+            .and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|header| header.key == PRODUCED_AT_HEADER)
+            })
+            .and_then(|header| std::str::from_utf8(header.value?).ok()?.parse().ok())
+            .map(elapsed);
+
+        let appended = match msg.timestamp() {
+            Timestamp::LogAppendTime(ts) => Some(elapsed(ts)),
+            _ => None,
+        };
+
+        crate::telemetry::message_latency(msg.topic(), msg.partition(), produced, appended);
+    }
+}
+
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,96 @@
+//! Object safe traits over the message clients, so application code can be written against
+//! them and run with the Kafka clients, the `test-util` mocks or other backends.
+
+use futures::future::BoxFuture;
+
+use crate::{
+    KafkaMessage, Message,
+    codec::{Decoder, Encoder},
+    consumer::KafkaConsumer,
+    error::Error,
+    producer::KafkaProducer,
+};
+
+/// A client receiving messages, e.g. a subscribed consumer.
+pub trait MessageSource<M>: Send {
+    type Error;
+
+    /// Waits for the next message.
+    fn recv(&mut self) -> BoxFuture<'_, Result<M, Self::Error>>;
+}
+
+/// A client sending messages, e.g. a producer of a topic.
+pub trait MessageSink<M>: Send {
+    type Error;
+
+    /// Sends `msg`, resolving once it is delivered.
+    fn send<'a>(&'a mut self, msg: &'a M) -> BoxFuture<'a, Result<(), Self::Error>>;
+}
+
+impl<M, D, K, KD> MessageSource<Message<M, K>> for KafkaConsumer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    D::Error: Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    M: Send,
+    K: Send,
+{
+    type Error = Error<D::Error>;
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<Message<M, K>, Self::Error>> {
+        Box::pin(KafkaConsumer::recv(self))
+    }
+}
+
+impl<M, E, KE> MessageSink<M> for KafkaProducer<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Error = Error<E::Error>;
+
+    fn send<'a>(&'a mut self, msg: &'a M) -> BoxFuture<'a, Result<(), Self::Error>> {
+        Box::pin(KafkaProducer::send(self, msg))
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl<M, D, K, KD> MessageSource<Message<M, K>> for crate::mock::MockKafkaConsumer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    D::Error: Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    M: Send,
+    K: Send,
+{
+    type Error = Error<D::Error>;
+
+    fn recv(&mut self) -> BoxFuture<'_, Result<Message<M, K>, Self::Error>> {
+        Box::pin(self.recv_subscribed())
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl<M, E, KE> MessageSink<M> for crate::mock::MockKafkaProducer<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Error = Error<E::Error>;
+
+    fn send<'a>(&'a mut self, msg: &'a M) -> BoxFuture<'a, Result<(), Self::Error>> {
+        Box::pin(crate::mock::MockKafkaProducer::send(self, msg))
+    }
+}
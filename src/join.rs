@@ -0,0 +1,197 @@
+//! Keyed join of two message streams within a time window.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::Duration,
+};
+
+use bytes::Bytes;
+#[cfg(feature = "flowly")]
+use flowly::Service;
+use futures::{Stream, StreamExt};
+
+use crate::Message;
+
+/// Input of a [`StreamJoin`], a message of either joined stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinSide<L, R, K = Bytes> {
+    Left(Message<L, K>),
+    Right(Message<R, K>),
+}
+
+/// Merges two streams, e.g. of two consumers, into the input of a [`StreamJoin`].
+pub fn join_input<L, R, K>(
+    left: impl Stream<Item = Message<L, K>> + Send,
+    right: impl Stream<Item = Message<R, K>> + Send,
+) -> impl Stream<Item = JoinSide<L, R, K>> + Send
+where
+    L: Send,
+    R: Send,
+    K: Send,
+{
+    futures::stream::select(left.map(JoinSide::Left), right.map(JoinSide::Right))
+}
+
+/// Pair of messages with the same key and timestamps at most the join window apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joined<L, R, K = Bytes> {
+    pub key: K,
+    pub left: Message<L, K>,
+    pub right: Message<R, K>,
+}
+
+/// Messages of one side waiting for a match, by key, with their timestamps.
+type Buffered<T, K> = HashMap<K, VecDeque<(i64, Message<T, K>)>>;
+
+/// Inner join of two keyed streams: every message is paired with the buffered messages of
+/// the other side with the same key whose timestamps are within `window` of its own.
+///
+/// Pairing is by the broker timestamps rather than the arrival order, so messages arriving
+/// out of order still join as long as they arrive within the grace period. Messages are
+/// buffered until the newest timestamp seen passes theirs by `window` plus the grace
+/// period. Keyless messages are skipped, messages without a timestamp use the current time.
+pub struct StreamJoin<L, R, K = Bytes> {
+    window_ms: i64,
+    grace_ms: i64,
+    left: Buffered<L, K>,
+    right: Buffered<R, K>,
+    watermark: Option<i64>,
+}
+
+impl<L, R, K> StreamJoin<L, R, K>
+where
+    L: Clone,
+    R: Clone,
+    K: Eq + Hash + Clone,
+{
+    /// Creates a join pairing messages whose timestamps differ by at most `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window_ms: window.as_millis() as i64,
+            grace_ms: 0,
+            left: HashMap::new(),
+            right: HashMap::new(),
+            watermark: None,
+        }
+    }
+
+    /// Keeps messages buffered for `grace` longer, so late arrivals still find them.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace` - How far behind the newest timestamp a message may arrive, 0 by default.
+    ///
+    /// # Returns
+    ///
+    /// The join with the grace period set.
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace_ms = grace.as_millis() as i64;
+        self
+    }
+
+    /// Returns the number of buffered messages of both sides.
+    pub fn buffered(&self) -> usize {
+        self.left.values().map(VecDeque::len).sum::<usize>()
+            + self.right.values().map(VecDeque::len).sum::<usize>()
+    }
+
+    /// Buffers `input` and returns its pairs with the buffered messages of the other side.
+    pub fn push(&mut self, input: JoinSide<L, R, K>) -> Vec<Joined<L, R, K>> {
+        let ts = match &input {
+            JoinSide::Left(msg) => msg.ts_ms_utc,
+            JoinSide::Right(msg) => msg.ts_ms_utc,
+        }
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+
+        let watermark = self.watermark.map_or(ts, |x| x.max(ts));
+        self.watermark = Some(watermark);
+        self.expire(watermark - self.window_ms - self.grace_ms);
+
+        // too late to be joined with anything still buffered
+        if ts < watermark - self.window_ms - self.grace_ms {
+            return Vec::new();
+        }
+
+        let window_ms = self.window_ms;
+
+        match input {
+            JoinSide::Left(msg) => {
+                let Some(key) = msg.key.clone() else {
+                    return Vec::new();
+                };
+
+                let joined = self
+                    .right
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(other, _)| (other - ts).abs() <= window_ms)
+                    .map(|(_, right)| Joined {
+                        key: key.clone(),
+                        left: msg.clone(),
+                        right: right.clone(),
+                    })
+                    .collect();
+
+                self.left.entry(key).or_default().push_back((ts, msg));
+
+                joined
+            }
+
+            JoinSide::Right(msg) => {
+                let Some(key) = msg.key.clone() else {
+                    return Vec::new();
+                };
+
+                let joined = self
+                    .left
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(other, _)| (other - ts).abs() <= window_ms)
+                    .map(|(_, left)| Joined {
+                        key: key.clone(),
+                        left: left.clone(),
+                        right: msg.clone(),
+                    })
+                    .collect();
+
+                self.right.entry(key).or_default().push_back((ts, msg));
+
+                joined
+            }
+        }
+    }
+
+    /// Drops the buffered messages older than `ts_ms`.
+    fn expire(&mut self, ts_ms: i64) {
+        self.left.retain(|_, buf| {
+            buf.retain(|(ts, _)| *ts >= ts_ms);
+            !buf.is_empty()
+        });
+
+        self.right.retain(|_, buf| {
+            buf.retain(|(ts, _)| *ts >= ts_ms);
+            !buf.is_empty()
+        });
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<L, R, K> Service<JoinSide<L, R, K>> for StreamJoin<L, R, K>
+where
+    L: Clone + Send,
+    R: Clone + Send,
+    K: Eq + Hash + Clone + Send,
+{
+    type Out = Joined<L, R, K>;
+
+    fn handle(
+        &mut self,
+        input: JoinSide<L, R, K>,
+        _cx: &flowly::Context,
+    ) -> impl Stream<Item = Self::Out> + Send {
+        futures::stream::iter(self.push(input))
+    }
+}
@@ -1,18 +1,86 @@
+pub mod admin;
+#[cfg(feature = "json")]
+pub mod audit;
 pub mod builder;
+pub mod chunking;
+mod circuit;
+pub mod codec;
 pub mod config;
 pub mod consumer;
+pub mod context;
 pub mod error;
+#[cfg(feature = "json")]
+pub mod fixture;
+pub mod health;
+pub mod interceptor;
+pub mod io;
+pub mod join;
 pub mod message;
+mod metadata;
+pub mod mirror;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod offset_store;
+#[cfg(feature = "otel")]
+mod otel;
 pub mod producer;
+mod rate_limit;
+pub mod replay;
+pub mod runtime;
+#[cfg(feature = "schema-registry")]
+pub mod schema_registry;
+pub mod secret;
+pub mod state;
+pub mod statistics;
+pub mod stream;
+#[cfg(feature = "metrics")]
+mod telemetry;
+#[cfg(feature = "testcontainers")]
+pub mod testcontainers;
+pub mod window;
 
 pub use message::{KafkaMessage, Message};
 
-struct KafkaCallbackContext(());
+#[cfg(feature = "derive")]
+pub use flowly_kafka_derive::KafkaMessage;
 
-impl rdkafka::ClientContext for KafkaCallbackContext {
-    fn error(&self, error: rdkafka::error::KafkaError, reason: &str) {
-        log::error!("Kafka global error occured: {error}, reason: {reason}. Restarting app.");
-    }
+/// Items used by the code generated by the derive macros.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use bytes::Bytes;
 }
 
-impl rdkafka::consumer::ConsumerContext for KafkaCallbackContext {}
+use context::KafkaCallbackContext;
+
+/// Returns an id unique across processes of a host and calls within a process, used to
+/// name chunked payloads and stored blobs.
+pub(crate) fn unique_id() -> String {
+    use std::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_nanos())
+        .unwrap_or_default();
+
+    format!(
+        "{:x}-{:x}-{:x}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Runs a blocking librdkafka call on the blocking thread pool.
+pub(crate) async fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    runtime::spawn_blocking(f).await
+}
@@ -1,34 +1,141 @@
+use std::borrow::Cow;
+
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 
 pub trait KafkaMessage {
-    type Key: AsRef<[u8]>;
+    type Key;
     type Value;
 
     fn key(&self) -> Option<Self::Key>;
     fn value(&self) -> Option<&Self::Value>;
     fn ts_ms_utc(&self) -> Option<i64>;
     fn into_value(self) -> Option<Self::Value>;
-    fn headers(&self) -> Option<&[(String, Vec<u8>)]>;
+
+    /// Headers of the message, none by default.
+    fn headers(&self) -> Option<&[(String, Vec<u8>)]> {
+        None
+    }
+
+    /// Topic the message is sent to instead of the topics of the producer, the topics of
+    /// the producer by default.
+    fn topic(&self) -> Option<&str> {
+        None
+    }
+
+    /// Headers built from the fields of the message when it is sent, after
+    /// [`headers`](Self::headers), e.g. the `#[kafka(header = "..")]` fields of the derive
+    /// macro.
+    fn field_headers(&self) -> Vec<(String, Vec<u8>)> {
+        Vec::new()
+    }
+}
+
+/// Value of a `#[kafka(header = "..")]` field of the `KafkaMessage` derive macro, `None`
+/// leaving the header out.
+pub trait HeaderValue {
+    fn header_value(&self) -> Option<Vec<u8>>;
+}
+
+impl HeaderValue for str {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        Some(self.as_bytes().to_vec())
+    }
+}
+
+impl HeaderValue for String {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        Some(self.as_bytes().to_vec())
+    }
+}
+
+impl HeaderValue for [u8] {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        Some(self.to_vec())
+    }
+}
+
+impl HeaderValue for Vec<u8> {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        Some(self.clone())
+    }
+}
+
+impl HeaderValue for Bytes {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        Some(self.to_vec())
+    }
+}
+
+impl<T: HeaderValue + ?Sized> HeaderValue for &T {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        (**self).header_value()
+    }
+}
+
+impl<T: HeaderValue> HeaderValue for Option<T> {
+    #[inline]
+    fn header_value(&self) -> Option<Vec<u8>> {
+        self.as_ref()?.header_value()
+    }
+}
+
+macro_rules! display_header_value {
+    ($($ty:ty),*) => {
+        $(
+            impl HeaderValue for $ty {
+                #[inline]
+                fn header_value(&self) -> Option<Vec<u8>> {
+                    Some(self.to_string().into_bytes())
+                }
+            }
+        )*
+    };
+}
+
+display_header_value!(
+    bool, char, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+
+/// Headers of a message to produce, borrowed from the message when it has no field headers.
+pub(crate) type MessageHeaders<'a> = Cow<'a, [(String, Vec<u8>)]>;
+
+/// Returns the headers of `m` followed by its field headers.
+pub(crate) fn message_headers<M: KafkaMessage + ?Sized>(m: &M) -> Option<MessageHeaders<'_>> {
+    let fields = m.field_headers();
+    if fields.is_empty() {
+        return m.headers().map(Cow::Borrowed);
+    }
+
+    let mut headers = m.headers().map(<[_]>::to_vec).unwrap_or_default();
+    headers.extend(fields);
+
+    Some(Cow::Owned(headers))
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Message<M> {
-    pub key: Option<Bytes>,
+pub struct Message<M, K = Bytes> {
+    pub key: Option<K>,
     pub ts_ms_utc: Option<i64>,
     pub payload: Option<M>,
     pub partition: i32,
     pub headers: Option<Vec<(String, Vec<u8>)>>,
 }
 
-impl<M> Message<M> {
+impl<M, K> Message<M, K> {
     pub fn timestamp(&self) -> Option<DateTime<Utc>> {
         self.ts_ms_utc.and_then(DateTime::from_timestamp_millis)
     }
 }
 
-impl<M> KafkaMessage for Message<M> {
-    type Key = Bytes;
+impl<M, K: Clone> KafkaMessage for Message<M, K> {
+    type Key = K;
     type Value = M;
 
     #[inline]
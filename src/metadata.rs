@@ -0,0 +1,55 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::config::Config;
+
+/// librdkafka default of `topic.metadata.refresh.interval.ms`.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+const DEFAULT_METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Partition counts of topics, an entry is fetched again once it is older than the
+/// topic metadata refresh interval of the config.
+#[derive(Debug, Clone)]
+pub(crate) struct PartitionCache {
+    ttl: Duration,
+    timeout: Duration,
+    entries: HashMap<String, (usize, Instant)>,
+}
+
+impl PartitionCache {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            ttl: config
+                .topic_metadata_refresh_interval_ms
+                .map(|ms| Duration::from_millis(ms.into()))
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL),
+            timeout: config
+                .metadata_request_timeout_ms
+                .map(|ms| Duration::from_millis(ms.into()))
+                .unwrap_or(DEFAULT_METADATA_TIMEOUT),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// How long a metadata request may take.
+    #[inline]
+    pub(crate) fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the cached partition count of `topic` unless it is stale.
+    pub(crate) fn get(&self, topic: &str) -> Option<usize> {
+        self.entries
+            .get(topic)
+            .filter(|(_, fetched_at)| fetched_at.elapsed() < self.ttl)
+            .map(|(count, _)| *count)
+    }
+
+    pub(crate) fn insert(&mut self, topic: &str, count: usize) {
+        self.entries
+            .insert(topic.to_string(), (count, Instant::now()));
+    }
+}
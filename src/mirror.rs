@@ -0,0 +1,260 @@
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+use rdkafka::{
+    Message as _, Offset, TopicPartitionList,
+    consumer::{CommitMode, Consumer, stream_consumer::StreamConsumer},
+    message::BorrowedMessage,
+    producer::{FutureProducer, FutureRecord},
+};
+
+use crate::{
+    KafkaCallbackContext,
+    builder::KafkaBuilder,
+    config::{BackoffPolicy, Config},
+    error::Error,
+    runtime::Runtime,
+};
+
+type TopicMapFn = dyn Fn(&str) -> String + Send + Sync;
+
+/// Replicates topics from one cluster to another, keeping the keys, headers and
+/// timestamps of the records.
+///
+/// Source offsets are committed only after the records up to them were delivered to the
+/// target cluster, every `checkpoint_every` records and before reconnecting, so a restart
+/// resumes from the last checkpoint and may mirror a few records twice but never skips one.
+pub struct KafkaMirror {
+    source: KafkaBuilder,
+    target: KafkaBuilder,
+    consumer: Option<Arc<StreamConsumer<KafkaCallbackContext, Runtime>>>,
+    producer: Option<FutureProducer<KafkaCallbackContext, Runtime>>,
+    topic_map: Option<Arc<TopicMapFn>>,
+    preserve_partitions: bool,
+    checkpoint_every: usize,
+    pending: HashMap<(String, i32), Offset>,
+    uncommitted: usize,
+    reconnect_count: u32,
+    backoff: BackoffPolicy,
+}
+
+impl KafkaMirror {
+    /// Creates a mirror consuming with the `source` config and producing with the `target`
+    /// config, auto commit is always disabled on the source.
+    pub fn new(mut source: Config, target: Config) -> Self {
+        source.auto_commit = Some(false);
+
+        Self {
+            reconnect_count: source.reconnect_count,
+            backoff: source.backoff_policy(),
+            source: KafkaBuilder::new(source),
+            target: KafkaBuilder::new(target),
+            consumer: None,
+            producer: None,
+            topic_map: None,
+            preserve_partitions: false,
+            checkpoint_every: 1000,
+            pending: HashMap::new(),
+            uncommitted: 0,
+        }
+    }
+
+    /// Renames the topics on the target cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Maps a source topic name to the target topic name.
+    ///
+    /// # Returns
+    ///
+    /// The mirror producing to the mapped topics, by default the names are kept.
+    pub fn with_topic_map<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.topic_map = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets whether records keep their source partition on the target cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `preserve_partitions` - Produce to the source partition instead of partitioning by key,
+    ///   the target topics need at least as many partitions.
+    ///
+    /// # Returns
+    ///
+    /// The mirror with the updated setting.
+    pub fn preserve_partitions(mut self, preserve_partitions: bool) -> Self {
+        self.preserve_partitions = preserve_partitions;
+        self
+    }
+
+    /// Sets how many mirrored records trigger a commit of the source offsets.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - The number of records between checkpoints, 1000 by default.
+    ///
+    /// # Returns
+    ///
+    /// The mirror with the updated checkpoint interval.
+    pub fn checkpoint_every(mut self, records: usize) -> Self {
+        self.checkpoint_every = records.max(1);
+        self
+    }
+
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.consumer.is_some() && self.producer.is_some()
+    }
+
+    pub async fn connect(&mut self, topics: &[&str]) -> Result<(), Error<Infallible>> {
+        self.consumer = None;
+        self.producer = None;
+
+        let target_topics = self.target_topics(topics);
+        let target_topics: Vec<&str> = target_topics.iter().map(String::as_str).collect();
+        self.target.ensure_topics(&target_topics).await?;
+
+        let consumer = self.source.build_consumer()?;
+        consumer
+            .subscribe(topics)
+            .map_err(|source| Error::Subscribe {
+                topic: topics.join(","),
+                source,
+            })?;
+
+        self.producer.replace(self.target.build_producer()?);
+        self.consumer.replace(Arc::new(consumer));
+
+        Ok(())
+    }
+
+    /// Mirrors the next record of the source topics to the target cluster.
+    pub async fn mirror_next(&mut self) -> Result<(), Error<Infallible>> {
+        let consumer = self.consumer.clone().ok_or(Error::NoConnection)?;
+        let msg = consumer.recv().await.map_err(Error::Receive)?;
+
+        self.forward(&msg).await?;
+
+        self.pending.insert(
+            (msg.topic().to_string(), msg.partition()),
+            Offset::Offset(msg.offset() + 1),
+        );
+        self.uncommitted += 1;
+
+        if self.uncommitted >= self.checkpoint_every {
+            self.checkpoint()?;
+        }
+
+        Ok(())
+    }
+
+    /// Commits the source offsets of the records delivered so far.
+    pub fn checkpoint(&mut self) -> Result<(), Error<Infallible>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let consumer = self.consumer.as_ref().ok_or(Error::NoConnection)?;
+        let offsets = TopicPartitionList::from_topic_map(&self.pending)?;
+        consumer.commit(&offsets, CommitMode::Async)?;
+
+        self.pending.clear();
+        self.uncommitted = 0;
+
+        Ok(())
+    }
+
+    /// Mirrors `topics` until a non retryable error, reconnecting with the backoff policy of
+    /// the source config up to its `reconnect_count` times in a row (0 means forever).
+    pub async fn run(&mut self, topics: &[&str]) -> Result<(), Error<Infallible>> {
+        let mut failures = 0;
+
+        loop {
+            let res = if self.is_connected() {
+                self.mirror_next().await
+            } else {
+                self.connect(topics).await
+            };
+
+            match res {
+                Ok(()) => failures = 0,
+                Err(err)
+                    if err.is_retryable()
+                        && (self.reconnect_count == 0 || failures < self.reconnect_count) =>
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("kafka mirror error, reconnecting: {err}");
+
+                    #[cfg(not(feature = "tracing"))]
+                    log::warn!("kafka mirror error, reconnecting: {err}");
+
+                    let _ = self.checkpoint();
+                    self.consumer = None;
+                    self.producer = None;
+
+                    crate::runtime::sleep(self.backoff.delay(failures)).await;
+                    failures += 1;
+                }
+                Err(err) => {
+                    let _ = self.checkpoint();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    fn target_topic(&self, topic: &str) -> String {
+        match &self.topic_map {
+            Some(f) => f(topic),
+            None => topic.to_string(),
+        }
+    }
+
+    fn target_topics(&self, topics: &[&str]) -> Vec<String> {
+        topics
+            .iter()
+            .filter(|topic| !topic.starts_with('^'))
+            .map(|topic| self.target_topic(topic))
+            .collect()
+    }
+
+    async fn forward(&self, msg: &BorrowedMessage<'_>) -> Result<(), Error<Infallible>> {
+        let producer = self.producer.as_ref().ok_or(Error::NoConnection)?;
+        let topic = self.target_topic(msg.topic());
+
+        let mut record = FutureRecord::<[u8], [u8]>::to(&topic);
+
+        if let Some(key) = msg.key() {
+            record = record.key(key);
+        }
+
+        if let Some(payload) = msg.payload() {
+            record = record.payload(payload);
+        }
+
+        if let Some(ts) = msg.timestamp().to_millis() {
+            record = record.timestamp(ts);
+        }
+
+        if let Some(headers) = msg.headers() {
+            record = record.headers(headers.detach());
+        }
+
+        if self.preserve_partitions {
+            record = record.partition(msg.partition());
+        }
+
+        match producer
+            .send(record, std::time::Duration::from_secs(0))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err((source, _msg)) => {
+                Err(Error::Deliver { topic, source }.at(msg.topic(), msg.partition(), msg.offset()))
+            }
+        }
+    }
+}
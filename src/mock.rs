@@ -0,0 +1,395 @@
+//! In-memory stand-ins for the consumer and producer, for testing flowly pipelines without
+//! a broker.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "flowly")]
+use flowly::Service;
+use futures::{FutureExt, Stream};
+use tokio::sync::watch;
+
+use crate::{
+    KafkaMessage, Message,
+    codec::{Decoder, Encoder, RawEncoder},
+    error::{DecodeFailure, Error},
+    message::message_headers,
+};
+
+/// A raw record as stored by a [`MockCluster`].
+pub type MockRecord = Message<Bytes, Bytes>;
+
+/// An in-memory cluster holding an append-only log of raw records per topic.
+///
+/// Topics have a single partition 0 and every consumer reads them from the beginning.
+/// Clones share the same topics.
+#[derive(Debug, Clone, Default)]
+pub struct MockCluster {
+    topics: Arc<Mutex<HashMap<String, MockTopic>>>,
+}
+
+#[derive(Debug)]
+struct MockTopic {
+    records: Vec<MockRecord>,
+    len: watch::Sender<usize>,
+}
+
+impl Default for MockTopic {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            len: watch::Sender::new(0),
+        }
+    }
+}
+
+impl MockCluster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a raw record to `topic`, e.g. to feed a [`MockKafkaConsumer`].
+    pub fn send(&self, topic: &str, record: MockRecord) {
+        let mut topics = self.topics.lock().unwrap();
+        let topic = topics.entry(topic.to_string()).or_default();
+
+        topic.records.push(record);
+        topic.len.send_replace(topic.records.len());
+    }
+
+    /// Returns the raw records of `topic`, e.g. the output of a [`MockKafkaProducer`].
+    pub fn records(&self, topic: &str) -> Vec<MockRecord> {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(topic)
+            .map(|topic| topic.records.clone())
+            .unwrap_or_default()
+    }
+
+    /// Waits until one of `topics` has a record past its offset and returns that topic.
+    async fn next_topic(&self, topics: &[String], offsets: &HashMap<String, usize>) -> String {
+        loop {
+            let mut changed = {
+                let mut state = self.topics.lock().unwrap();
+                let mut changed = Vec::with_capacity(topics.len());
+
+                for name in topics {
+                    let topic = state.entry(name.clone()).or_default();
+
+                    if topic.records.len() > offsets.get(name).copied().unwrap_or(0) {
+                        return name.clone();
+                    }
+
+                    changed.push(topic.len.subscribe());
+                }
+
+                changed
+            };
+
+            let _ =
+                futures::future::select_all(changed.iter_mut().map(|len| Box::pin(len.changed())))
+                    .await;
+        }
+    }
+
+    /// Waits for the record at `offset` of `topic`.
+    async fn fetch(&self, topic: &str, offset: usize) -> MockRecord {
+        let mut len = {
+            let mut topics = self.topics.lock().unwrap();
+            let topic = topics.entry(topic.to_string()).or_default();
+
+            if let Some(record) = topic.records.get(offset) {
+                return record.clone();
+            }
+
+            topic.len.subscribe()
+        };
+
+        // the sender lives in the cluster as long as `self`, so it is never closed
+        let _ = len.wait_for(|len| *len > offset).await;
+
+        self.topics.lock().unwrap()[topic].records[offset].clone()
+    }
+}
+
+/// In-memory counterpart of [`KafkaConsumer`](crate::consumer::KafkaConsumer) reading
+/// the topics of a [`MockCluster`] with the same decoding and errors.
+pub struct MockKafkaConsumer<
+    M = Bytes,
+    D: Decoder<M> = crate::codec::BytesDecoder,
+    K = Bytes,
+    KD: Decoder<K> = crate::codec::BytesDecoder,
+> {
+    cluster: MockCluster,
+    decoder: D,
+    key_decoder: KD,
+    topics: Vec<String>,
+    offsets: HashMap<String, usize>,
+    _m: PhantomData<(M, K)>,
+}
+
+impl MockKafkaConsumer {
+    #[inline]
+    pub fn new(cluster: MockCluster) -> Self {
+        Self::new_with_decoder(Default::default(), cluster)
+    }
+}
+
+impl<M, D: Decoder<M>> MockKafkaConsumer<M, D> {
+    pub fn new_with_decoder(decoder: D, cluster: MockCluster) -> Self {
+        Self {
+            cluster,
+            decoder,
+            key_decoder: crate::codec::BytesDecoder,
+            topics: Vec::new(),
+            offsets: HashMap::new(),
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<M, D: Decoder<M>, K, KD: Decoder<K>> MockKafkaConsumer<M, D, K, KD> {
+    /// Decodes message keys with `key_decoder` instead of returning them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_decoder` - The decoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The consumer producing messages with keys of type `K2`.
+    pub fn with_key_decoder<K2, KD2: Decoder<K2>>(
+        self,
+        key_decoder: KD2,
+    ) -> MockKafkaConsumer<M, D, K2, KD2> {
+        MockKafkaConsumer {
+            cluster: self.cluster,
+            decoder: self.decoder,
+            key_decoder,
+            topics: self.topics,
+            offsets: self.offsets,
+            _m: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        !self.topics.is_empty()
+    }
+
+    /// Subscribes to `topics`, read by [`recv_subscribed`](Self::recv_subscribed).
+    pub async fn connect(&mut self, topics: &[&str]) -> Result<(), Error<D::Error>> {
+        self.topics = topics.iter().map(|topic| topic.to_string()).collect();
+        Ok(())
+    }
+
+    /// Waits for the next record of the subscribed topics and decodes it.
+    pub async fn recv_subscribed(&mut self) -> Result<Message<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if self.topics.is_empty() {
+            return Err(Error::NoConnection);
+        }
+
+        let topic = self.cluster.next_topic(&self.topics, &self.offsets).await;
+        self.recv(&topic).await
+    }
+
+    /// Waits for the next record of `topic` and decodes it.
+    pub async fn recv(&mut self, topic: &str) -> Result<Message<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let offset = self.offsets.get(topic).copied().unwrap_or(0);
+        let record = self.cluster.fetch(topic, offset).await;
+        self.offsets.insert(topic.to_string(), offset + 1);
+
+        self.decode(&record).map_err(|error| {
+            Error::DecodeFailed(Box::new(DecodeFailure {
+                raw: record.payload.clone(),
+                key: record.key.clone(),
+                error,
+            }))
+            .at(topic, record.partition, offset as i64)
+        })
+    }
+
+    fn decode(&mut self, record: &MockRecord) -> Result<Message<M, K>, Error<D::Error>>
+    where
+        KD::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let payload = match &record.payload {
+            Some(payload) => Some(
+                self.decoder
+                    .decode(&mut payload.clone())
+                    .map_err(Error::MessageCodecError)?,
+            ),
+            None => None,
+        };
+
+        let key = match &record.key {
+            Some(key) => Some(
+                self.key_decoder
+                    .decode(&mut key.clone())
+                    .map_err(|err| Error::KeyCodecError(Box::new(err)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Message {
+            key,
+            ts_ms_utc: record.ts_ms_utc,
+            payload,
+            partition: record.partition,
+            headers: record.headers.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, D, K, KD, I> Service<I> for MockKafkaConsumer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    D::Error: std::error::Error + Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    I: AsRef<str> + Send,
+    M: Send,
+    K: Send,
+{
+    type Out = Result<Message<M, K>, Error<D::Error>>;
+
+    fn handle(&mut self, input: I, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async_stream::stream! {
+            loop {
+                yield self.recv(input.as_ref()).await;
+            }
+        }
+    }
+}
+
+/// In-memory counterpart of [`KafkaProducer`](crate::producer::KafkaProducer) appending
+/// the encoded messages to a topic of a [`MockCluster`].
+pub struct MockKafkaProducer<M, E, KE = RawEncoder> {
+    cluster: MockCluster,
+    encoder: E,
+    key_encoder: KE,
+    buffer: BytesMut,
+    key_buffer: BytesMut,
+    topic: String,
+    _m: PhantomData<M>,
+}
+
+impl<M, E> MockKafkaProducer<M, E>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+{
+    pub fn new<S: Into<String>>(encoder: E, cluster: MockCluster, topic: S) -> Self {
+        Self {
+            cluster,
+            encoder,
+            key_encoder: RawEncoder,
+            buffer: BytesMut::new(),
+            key_buffer: BytesMut::new(),
+            topic: topic.into(),
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<M, E, KE> MockKafkaProducer<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Serializes message keys with `key_encoder` instead of sending them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_encoder` - The encoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The producer encoding keys with `key_encoder`.
+    pub fn with_key_encoder<KE2>(self, key_encoder: KE2) -> MockKafkaProducer<M, E, KE2>
+    where
+        KE2: Encoder<M::Key>,
+    {
+        MockKafkaProducer {
+            cluster: self.cluster,
+            encoder: self.encoder,
+            key_encoder,
+            buffer: self.buffer,
+            key_buffer: self.key_buffer,
+            topic: self.topic,
+            _m: PhantomData,
+        }
+    }
+
+    pub async fn send(&mut self, m: &M) -> Result<(), Error<E::Error>> {
+        let payload = match m.value() {
+            Some(payload) => {
+                self.buffer.clear();
+                self.encoder
+                    .encode(payload, &mut self.buffer)
+                    .map_err(Error::MessageCodecError)?;
+
+                Some(self.buffer.split().freeze())
+            }
+            None => None,
+        };
+
+        let key = match m.key() {
+            Some(key) => {
+                self.key_buffer.clear();
+                self.key_encoder
+                    .encode(&key, &mut self.key_buffer)
+                    .map_err(|err| Error::KeyCodecError(Box::new(err)))?;
+
+                Some(self.key_buffer.split().freeze())
+            }
+            None => None,
+        };
+
+        self.cluster.send(
+            m.topic().unwrap_or(&self.topic),
+            Message {
+                key,
+                ts_ms_utc: m.ts_ms_utc(),
+                payload,
+                partition: 0,
+                headers: message_headers(m).map(Cow::into_owned),
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E, KE> Service<M> for MockKafkaProducer<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Out = Result<M, Error<E::Error>>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async move { self.send(&input).await.map(|_| input) }.into_stream()
+    }
+}
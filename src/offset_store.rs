@@ -0,0 +1,346 @@
+//! Consumer offsets kept outside of Kafka, e.g. in the database a sink writes to, so they
+//! can be saved in the same transaction as the processed data.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::future::BoxFuture;
+use rdkafka::{
+    Message as _, Offset, TopicPartitionList,
+    consumer::{Consumer, stream_consumer::StreamConsumer},
+    error::KafkaResult,
+    message::BorrowedMessage,
+};
+use tokio::sync::Notify;
+
+use crate::{KafkaCallbackContext, error::Error, runtime::Runtime};
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresOffsetStore;
+#[cfg(feature = "redis")]
+pub use redis::RedisOffsetStore;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Storage of the consumed offsets of a group, see
+/// [`KafkaConsumer::with_offset_store`](crate::consumer::KafkaConsumer::with_offset_store).
+///
+/// Offsets are the next offset to consume of a partition, like the ones committed to Kafka.
+pub trait OffsetStore: Send + Sync + 'static {
+    /// Loads the offset of a partition, `None` when it has no checkpoint yet.
+    fn load<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+    ) -> BoxFuture<'a, Result<Option<i64>, BoxError>>;
+
+    /// Saves the offset of a partition.
+    fn save<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(), BoxError>>;
+}
+
+/// Partitions assigned to a consumer with an [`OffsetStore`], held back from librdkafka until
+/// the consumer loaded their stored offsets so no message is fetched from elsewhere first.
+#[derive(Default)]
+pub(crate) struct DeferredAssignments {
+    pending: Mutex<Option<Deferred>>,
+    changed: Notify,
+}
+
+#[derive(Default)]
+pub(crate) struct Deferred {
+    pub(crate) tpl: TopicPartitionList,
+
+    /// Partitions whose `topic_offset_reset` failed, paused once assigned.
+    pub(crate) paused: TopicPartitionList,
+}
+
+impl DeferredAssignments {
+    fn lock(&self) -> MutexGuard<'_, Option<Deferred>> {
+        self.pending.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Adds the partitions of a rebalance to the pending assignment.
+    pub(crate) fn defer(
+        &self,
+        tpl: &TopicPartitionList,
+        paused: &TopicPartitionList,
+    ) -> KafkaResult<()> {
+        let mut pending = self.lock();
+        let deferred = pending.get_or_insert_default();
+
+        for elem in tpl.elements() {
+            deferred
+                .tpl
+                .add_partition_offset(elem.topic(), elem.partition(), elem.offset())?;
+        }
+
+        for elem in paused.elements() {
+            deferred
+                .paused
+                .add_partition(elem.topic(), elem.partition());
+        }
+
+        drop(pending);
+        self.changed.notify_one();
+
+        Ok(())
+    }
+
+    /// Drops the revoked partitions from the pending assignment, returning the ones
+    /// librdkafka was already assigned.
+    pub(crate) fn revoke(&self, tpl: &TopicPartitionList) -> TopicPartitionList {
+        let mut pending = self.lock();
+        let mut assigned = TopicPartitionList::new();
+
+        let Some(deferred) = pending.as_mut() else {
+            return tpl.clone();
+        };
+
+        for elem in tpl.elements() {
+            if deferred
+                .tpl
+                .find_partition(elem.topic(), elem.partition())
+                .is_none()
+            {
+                assigned.add_partition(elem.topic(), elem.partition());
+            }
+        }
+
+        let kept = |list: &TopicPartitionList| {
+            let mut kept = TopicPartitionList::new();
+            for elem in list.elements() {
+                if tpl.find_partition(elem.topic(), elem.partition()).is_none() {
+                    // offsets of a valid list are valid
+                    let _ =
+                        kept.add_partition_offset(elem.topic(), elem.partition(), elem.offset());
+                }
+            }
+
+            kept
+        };
+
+        deferred.tpl = kept(&deferred.tpl);
+        deferred.paused = kept(&deferred.paused);
+
+        assigned
+    }
+
+    /// Drops the whole pending assignment, e.g. when an eager rebalance revokes everything.
+    pub(crate) fn clear(&self) {
+        self.lock().take();
+    }
+
+    /// Takes the pending assignment.
+    pub(crate) fn take(&self) -> Option<Deferred> {
+        self.lock()
+            .take()
+            .filter(|deferred| deferred.tpl.count() > 0)
+    }
+
+    /// Completes once partitions were deferred since the last wait.
+    pub(crate) async fn changed(&self) {
+        self.changed.notified().await
+    }
+}
+
+/// Position of a consumer reading its offsets from an [`OffsetStore`].
+pub(crate) struct StoreCursor {
+    store: Arc<dyn OffsetStore>,
+    group: String,
+
+    /// Offset of the last returned message, saved once the next one is requested.
+    pending: Option<(String, i32, i64)>,
+}
+
+impl StoreCursor {
+    pub(crate) fn new(store: Arc<dyn OffsetStore>, group: String) -> Self {
+        Self {
+            store,
+            group,
+            pending: None,
+        }
+    }
+
+    /// Saves the offset of the last returned message.
+    pub(crate) async fn checkpoint<E>(&mut self) -> Result<(), Error<E>> {
+        if let Some((topic, partition, offset)) = &self.pending {
+            self.store
+                .save(&self.group, topic, *partition, *offset)
+                .await
+                .map_err(Error::OffsetStore)?;
+        }
+
+        self.pending = None;
+
+        Ok(())
+    }
+
+    /// Assigns the partitions deferred by the rebalances so far, started at their stored
+    /// offsets. The others start at their committed offset or `auto_offset_reset`.
+    pub(crate) async fn assign<E>(
+        &self,
+        consumer: &StreamConsumer<KafkaCallbackContext, Runtime>,
+    ) -> Result<(), Error<E>> {
+        let context = consumer.context();
+        let Some(assignments) = &context.deferred_assignments else {
+            return Ok(());
+        };
+
+        let Some(mut deferred) = assignments.take() else {
+            return Ok(());
+        };
+
+        if let Err(err) = self.load(&mut deferred.tpl).await {
+            // retried on the next receive, the partitions stay unassigned meanwhile
+            let _ = assignments.defer(&deferred.tpl, &deferred.paused);
+            return Err(Error::OffsetStore(err));
+        }
+
+        context.complete_assignment(consumer, &deferred);
+
+        Ok(())
+    }
+
+    async fn load(&self, tpl: &mut TopicPartitionList) -> Result<(), BoxError> {
+        let partitions: Vec<_> = tpl
+            .elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .collect();
+
+        for (topic, partition) in partitions {
+            if let Some(next) = self.store.load(&self.group, &topic, partition).await? {
+                tpl.set_partition_offset(&topic, partition, Offset::Offset(next))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records that `msg` was returned, its offset is saved on the next checkpoint.
+    pub(crate) fn returned(&mut self, msg: &BorrowedMessage<'_>) {
+        self.pending = Some((msg.topic().to_string(), msg.partition(), msg.offset() + 1));
+    }
+}
+
+/// Saves the offset of the last returned message in the background, a drop can not wait.
+impl Drop for StoreCursor {
+    fn drop(&mut self) {
+        let Some((topic, partition, offset)) = self.pending.take() else {
+            return;
+        };
+
+        let store = self.store.clone();
+        let group = std::mem::take(&mut self.group);
+        let spawned = crate::runtime::spawn_detached(async move {
+            if let Err(err) = store.save(&group, &topic, partition, offset).await {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("kafka offset store checkpoint on drop failed: {err}");
+
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("kafka offset store checkpoint on drop failed: {err}");
+            }
+        });
+
+        if !spawned {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("kafka offset store checkpoint on drop skipped outside of a runtime");
+
+            #[cfg(not(feature = "tracing"))]
+            log::warn!("kafka offset store checkpoint on drop skipped outside of a runtime");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partitions(tpl: &TopicPartitionList) -> Vec<(String, i32)> {
+        tpl.elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .collect()
+    }
+
+    fn tpl(list: &[(&str, i32)]) -> TopicPartitionList {
+        let mut tpl = TopicPartitionList::new();
+        for (topic, partition) in list {
+            tpl.add_partition(topic, *partition);
+        }
+
+        tpl
+    }
+
+    #[test]
+    fn deferred_rebalances_are_merged() {
+        let assignments = DeferredAssignments::default();
+        assignments
+            .defer(&tpl(&[("orders", 0)]), &TopicPartitionList::new())
+            .unwrap();
+        assignments
+            .defer(&tpl(&[("orders", 1)]), &tpl(&[("orders", 1)]))
+            .unwrap();
+
+        let deferred = assignments.take().unwrap();
+
+        assert_eq!(
+            partitions(&deferred.tpl),
+            [("orders".to_string(), 0), ("orders".to_string(), 1)]
+        );
+        assert_eq!(partitions(&deferred.paused), [("orders".to_string(), 1)]);
+        assert!(assignments.take().is_none());
+    }
+
+    #[test]
+    fn revoked_partitions_leave_the_deferred_assignment() {
+        let assignments = DeferredAssignments::default();
+        assignments
+            .defer(
+                &tpl(&[("orders", 0), ("orders", 1)]),
+                &TopicPartitionList::new(),
+            )
+            .unwrap();
+
+        let assigned = assignments.revoke(&tpl(&[("orders", 1), ("orders", 2)]));
+
+        // only the partitions librdkafka knows about are unassigned
+        assert_eq!(partitions(&assigned), [("orders".to_string(), 2)]);
+        assert_eq!(
+            partitions(&assignments.take().unwrap().tpl),
+            [("orders".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn fully_revoked_assignment_is_not_taken() {
+        let assignments = DeferredAssignments::default();
+        assignments
+            .defer(&tpl(&[("orders", 0)]), &TopicPartitionList::new())
+            .unwrap();
+        assignments.revoke(&tpl(&[("orders", 0)]));
+
+        assert!(assignments.take().is_none());
+    }
+
+    #[test]
+    fn deferring_wakes_the_consumer() {
+        let assignments = DeferredAssignments::default();
+        assignments
+            .defer(&tpl(&[("orders", 0)]), &TopicPartitionList::new())
+            .unwrap();
+
+        futures::executor::block_on(assignments.changed());
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use tokio_postgres::Client;
+
+use super::{BoxError, OffsetStore};
+
+/// Keeps the offsets in a Postgres table of `(group_id, topic, partition, next_offset)`
+/// rows, see [`create_table`](Self::create_table).
+///
+/// Sinks writing to the same database can upsert the offset row in their own transaction
+/// to process every message exactly once.
+#[derive(Clone)]
+pub struct PostgresOffsetStore {
+    client: Arc<Client>,
+    table: String,
+}
+
+impl PostgresOffsetStore {
+    pub fn new(client: Arc<Client>) -> Self {
+        Self {
+            client,
+            table: "kafka_offsets".to_string(),
+        }
+    }
+
+    /// Sets the table holding the offsets.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table name, `kafka_offsets` by default. It is not escaped.
+    ///
+    /// # Returns
+    ///
+    /// The store with the updated table.
+    pub fn with_table(mut self, table: impl Into<String>) -> Self {
+        self.table = table.into();
+        self
+    }
+
+    /// Creates the offsets table when it does not exist.
+    pub async fn create_table(&self) -> Result<(), tokio_postgres::Error> {
+        self.client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    group_id TEXT NOT NULL,
+                    topic TEXT NOT NULL,
+                    partition INTEGER NOT NULL,
+                    next_offset BIGINT NOT NULL,
+                    PRIMARY KEY (group_id, topic, partition)
+                )",
+                self.table
+            ))
+            .await
+    }
+}
+
+impl OffsetStore for PostgresOffsetStore {
+    fn load<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+    ) -> BoxFuture<'a, Result<Option<i64>, BoxError>> {
+        Box::pin(async move {
+            let row = self
+                .client
+                .query_opt(
+                    &format!(
+                        "SELECT next_offset FROM {} \
+                         WHERE group_id = $1 AND topic = $2 AND partition = $3",
+                        self.table
+                    ),
+                    &[&group, &topic, &partition],
+                )
+                .await?;
+
+            Ok(row.map(|row| row.get(0)))
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(), BoxError>> {
+        Box::pin(async move {
+            self.client
+                .execute(
+                    &format!(
+                        "INSERT INTO {} (group_id, topic, partition, next_offset) \
+                         VALUES ($1, $2, $3, $4) \
+                         ON CONFLICT (group_id, topic, partition) \
+                         DO UPDATE SET next_offset = EXCLUDED.next_offset",
+                        self.table
+                    ),
+                    &[&group, &topic, &partition, &offset],
+                )
+                .await?;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use futures::future::BoxFuture;
+use redis::{AsyncCommands, RedisError, aio::ConnectionManager};
+
+use super::{BoxError, OffsetStore};
+
+/// Keeps the offsets of a group and topic in a Redis hash keyed by partition, under
+/// `{prefix}:{group}:{topic}`.
+#[derive(Clone)]
+pub struct RedisOffsetStore {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisOffsetStore {
+    /// Connects to the Redis server at `url`, e.g. `redis://127.0.0.1/`.
+    pub async fn connect(url: &str) -> Result<Self, RedisError> {
+        let client = redis::Client::open(url)?;
+
+        Ok(Self::new(ConnectionManager::new(client).await?))
+    }
+
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            prefix: "flowly:offsets".to_string(),
+        }
+    }
+
+    /// Sets the prefix of the keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The key prefix, `flowly:offsets` by default.
+    ///
+    /// # Returns
+    ///
+    /// The store with the updated prefix.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, group: &str, topic: &str) -> String {
+        format!("{}:{group}:{topic}", self.prefix)
+    }
+}
+
+impl OffsetStore for RedisOffsetStore {
+    fn load<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+    ) -> BoxFuture<'a, Result<Option<i64>, BoxError>> {
+        let mut conn = self.conn.clone();
+        let key = self.key(group, topic);
+
+        Box::pin(async move {
+            let offset: Option<i64> = conn.hget(key, partition).await?;
+            Ok(offset)
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        group: &'a str,
+        topic: &'a str,
+        partition: i32,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(), BoxError>> {
+        let mut conn = self.conn.clone();
+        let key = self.key(group, topic);
+
+        Box::pin(async move {
+            let _: () = conn.hset(key, partition, offset).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,128 @@
+use opentelemetry::{
+    Context, KeyValue, global,
+    propagation::{Extractor, Injector},
+    trace::{Link, SpanKind, Status, TraceContextExt, Tracer},
+};
+use rdkafka::message::{BorrowedMessage, Headers, Message as _};
+
+use crate::error::Error;
+
+const TRACER_NAME: &str = "flowly-kafka";
+
+/// Starts the span of a produce request as a child of the current OpenTelemetry context
+/// and renders its W3C trace context (`traceparent`, `tracestate`) as message headers.
+pub(crate) fn producer_span(topic: &str) -> (Context, Vec<(String, String)>) {
+    let tracer = global::tracer(TRACER_NAME);
+
+    let span = tracer
+        .span_builder(format!("send {topic}"))
+        .with_kind(SpanKind::Producer)
+        .with_attributes([
+            KeyValue::new("messaging.system", "kafka"),
+            KeyValue::new("messaging.operation.type", "send"),
+            KeyValue::new("messaging.destination.name", topic.to_string()),
+        ])
+        .start(&tracer);
+
+    let cx = Context::current_with_span(span);
+    let mut headers = HeaderInjector(Vec::new());
+
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut headers));
+
+    (cx, headers.0)
+}
+
+/// Starts the span of a received message, covering its decoding. The span is linked to
+/// the producer span when the message carries a trace context.
+pub(crate) fn consumer_span(msg: &BorrowedMessage<'_>, group: &str) -> Context {
+    let tracer = global::tracer(TRACER_NAME);
+
+    let mut links = Vec::new();
+    if let Some(headers) = msg.headers() {
+        let remote = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(headers))
+        });
+
+        let span_context = remote.span().span_context().clone();
+        if span_context.is_valid() {
+            links.push(Link::with_context(span_context));
+        }
+    }
+
+    let span = tracer
+        .span_builder(format!("receive {}", msg.topic()))
+        .with_kind(SpanKind::Consumer)
+        .with_links(links)
+        .with_attributes([
+            KeyValue::new("messaging.system", "kafka"),
+            KeyValue::new("messaging.operation.type", "receive"),
+            KeyValue::new("messaging.destination.name", msg.topic().to_string()),
+            KeyValue::new(
+                "messaging.destination.partition.id",
+                msg.partition().to_string(),
+            ),
+            KeyValue::new("messaging.kafka.offset", msg.offset()),
+            KeyValue::new("messaging.consumer.group.name", group.to_string()),
+            KeyValue::new("messaging.message.body.size", msg.payload_len() as i64),
+        ])
+        .start(&tracer);
+
+    Context::current_with_span(span)
+}
+
+/// Ends the span of `cx`, marking it as failed with the kind of the error.
+pub(crate) fn end_span<T, E>(cx: &Context, result: &Result<T, Error<E>>) {
+    let span = cx.span();
+
+    if let Err(err) = result {
+        let kind = error_kind(err);
+
+        span.set_attribute(KeyValue::new("error.type", kind));
+        span.set_status(Status::error(kind));
+    }
+
+    span.end();
+}
+
+fn error_kind<E>(err: &Error<E>) -> &'static str {
+    match err.root() {
+        Error::NoConnection => "no_connection",
+        Error::KafkaError(..) => "kafka",
+        Error::Connect(..) => "connect",
+        Error::Subscribe { .. } => "subscribe",
+        Error::Receive(..) => "receive",
+        Error::Deliver { .. } => "deliver",
+        Error::AdminError(..) => "admin",
+        Error::MessageCodecError(..) => "message_codec",
+        Error::TopicMissing { .. } => "topic_missing",
+        Error::KeyCodecError(..) => "key_codec",
+        Error::InvalidConfig(..) => "invalid_config",
+        Error::Rejected { .. } => "rejected",
+        Error::CircuitOpen { .. } => "circuit_open",
+        Error::OffsetStore(..) => "offset_store",
+        Error::At { .. } | Error::DecodeFailed(..) => unreachable!("not a root error"),
+    }
+}
+
+struct HeaderInjector(Vec<(String, String)>);
+
+impl Injector for HeaderInjector {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.push((key.to_string(), value));
+    }
+}
+
+struct HeaderExtractor<'a, H>(&'a H);
+
+impl<H: Headers> Extractor for HeaderExtractor<'_, H> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|header| header.key == key)
+            .and_then(|header| std::str::from_utf8(header.value?).ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.iter().map(|header| header.key).collect()
+    }
+}
@@ -1,27 +1,77 @@
-use std::marker::PhantomData;
+use std::{
+    borrow::Cow,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use bytes::BytesMut;
-use flowly::{Encoder, Service};
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "flowly")]
+use flowly::Service;
+use futures::Sink;
+#[cfg(feature = "flowly")]
 use futures::{FutureExt, Stream};
 use rdkafka::{
     error::KafkaError,
-    message::{Header as RdkHeader, OwnedHeaders},
-    producer::{FutureProducer, FutureRecord},
+    message::{Header as RdkHeader, Message as _, OwnedHeaders},
+    producer::FutureRecord,
+    types::RDKafkaErrorCode,
 };
+use tokio::sync::Semaphore;
 
 use crate::{
-    KafkaCallbackContext, KafkaMessage, builder::KafkaBuilder, config::Config, error::Error,
+    KafkaMessage, blocking,
+    builder::KafkaBuilder,
+    circuit::CircuitBreaker,
+    codec::{Encoder, OwnedEncoder, RawEncoder},
+    config::{BackoffPolicy, Config, QueueFullPolicy},
+    error::Error,
+    health::{HEALTH_CHECK_TIMEOUT, Health},
+    interceptor::{ProducerInterceptor, SentRecord},
+    message::{MessageHeaders, message_headers},
+    metadata::PartitionCache,
+    rate_limit::RateLimiter,
+    statistics::StatisticsReceiver,
 };
 
+mod client;
+mod failover;
+mod partitioner;
+mod record;
+mod router;
+mod validator;
+
+pub use failover::FailoverProducer;
+pub use partitioner::{Partitioner, RoundRobinPartitioner, StickyPartitioner};
+pub use record::{Record, RecordMetadata};
+pub use router::{HeaderRouter, Route, TopicRouter};
+pub use validator::{EncodedRecord, REJECTION_HEADER, Rejection, Validator};
+
+use client::ProducerClient;
+
 #[derive(Clone)]
-pub struct KafkaProducer<M, E> {
+pub struct KafkaProducer<M, E, KE = RawEncoder> {
     encoder: E,
+    key_encoder: KE,
     buffer: BytesMut,
+    key_buffer: BytesMut,
     builder: KafkaBuilder,
-    inner: Option<FutureProducer<KafkaCallbackContext>>,
-    topic: String,
+    inner: Option<ProducerClient>,
+    last_delivery: Option<SystemTime>,
+    topic: Arc<str>,
+    broadcast: Option<Arc<[String]>>,
+    interceptors: Vec<Arc<dyn ProducerInterceptor>>,
+    partitioner: Option<Arc<dyn Partitioner>>,
+    validator: Option<Arc<dyn Validator>>,
+    rejects_topic: Option<Arc<str>>,
+    partitions: PartitionCache,
     reconnect_count: u32,
-    reconnect_sleep_ms: u32,
+    backoff: BackoffPolicy,
+    breaker: CircuitBreaker,
+    limiter: RateLimiter,
+    /// Semaphore of the outstanding deliveries and its number of permits.
+    in_flight: Option<(Arc<Semaphore>, u32)>,
+    queue_full: QueueFullPolicy,
     _m: PhantomData<M>,
 }
 
@@ -33,137 +83,856 @@ where
     pub fn new<S: Into<String>>(encoder: E, config: Config, topic: S) -> Self {
         Self {
             encoder,
+            key_encoder: RawEncoder,
             reconnect_count: config.reconnect_count,
-            reconnect_sleep_ms: config.reconnect_sleep_ms,
+            backoff: config.backoff_policy(),
+            breaker: CircuitBreaker::new(config.circuit_breaker),
+            limiter: RateLimiter::new(config.rate_limit),
+            in_flight: None,
+            queue_full: config.on_queue_full,
+            partitions: PartitionCache::new(&config),
             builder: KafkaBuilder::new(config),
             buffer: BytesMut::new(),
+            key_buffer: BytesMut::new(),
             inner: None,
-            topic: topic.into(),
+            last_delivery: None,
+            topic: topic.into().into(),
+            broadcast: None,
+            interceptors: Vec::new(),
+            partitioner: None,
+            validator: None,
+            rejects_topic: None,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<M, E, KE> KafkaProducer<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Serializes message keys with `key_encoder` instead of sending them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_encoder` - The encoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The producer encoding keys with `key_encoder`.
+    pub fn with_key_encoder<KE2>(self, key_encoder: KE2) -> KafkaProducer<M, E, KE2>
+    where
+        KE2: Encoder<M::Key>,
+    {
+        KafkaProducer {
+            encoder: self.encoder,
+            key_encoder,
+            buffer: self.buffer,
+            key_buffer: self.key_buffer,
+            builder: self.builder,
+            inner: self.inner,
+            last_delivery: self.last_delivery,
+            topic: self.topic,
+            broadcast: self.broadcast,
+            interceptors: self.interceptors,
+            partitioner: self.partitioner,
+            validator: self.validator,
+            rejects_topic: self.rejects_topic,
+            partitions: self.partitions,
+            reconnect_count: self.reconnect_count,
+            backoff: self.backoff,
+            breaker: self.breaker,
+            limiter: self.limiter,
+            in_flight: self.in_flight,
+            queue_full: self.queue_full,
             _m: PhantomData,
         }
     }
 
+    /// Produces every message to each of `topics` instead of the topic of the producer,
+    /// encoding it once, e.g. to dual-write during a topic migration.
+    ///
+    /// A send fails with the first failed delivery, the message may still have been
+    /// delivered to the other topics.
+    ///
+    /// # Arguments
+    ///
+    /// * `topics` - The topics every message is produced to.
+    ///
+    /// # Returns
+    ///
+    /// The producer in broadcast mode.
+    pub fn broadcast<I, S>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.broadcast = Some(topics.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Caps the number of deliveries awaited at once, shared by the clones of the producer,
+    /// so sends wait for a slot instead of growing the librdkafka queue without bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_in_flight` - The maximum number of outstanding deliveries, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The producer with the in-flight limit set.
+    pub fn max_in_flight(mut self, max_in_flight: u32) -> Self {
+        let permits = max_in_flight.max(1);
+        self.in_flight = Some((Arc::new(Semaphore::new(permits as usize)), permits));
+        self
+    }
+
+    /// Adds an interceptor seeing every sent message and its acknowledgement.
+    ///
+    /// # Arguments
+    ///
+    /// * `interceptor` - The interceptor, run after the ones added before.
+    ///
+    /// # Returns
+    ///
+    /// The producer with the interceptor added.
+    pub fn with_interceptor<I: ProducerInterceptor>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Picks the partitions of the messages with `partitioner` instead of the librdkafka
+    /// `partitioner`, looking up the partition counts of the topics in the metadata cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `partitioner` - The partitioner, e.g. a [`StickyPartitioner`] or a
+    ///   [`RoundRobinPartitioner`].
+    ///
+    /// # Returns
+    ///
+    /// The producer with the partitioner set.
+    pub fn with_partitioner<P: Partitioner>(mut self, partitioner: P) -> Self {
+        self.partitioner = Some(Arc::new(partitioner));
+        self
+    }
+
+    /// Checks every record with `validator` once encoded, failing the sends of the rejected
+    /// ones with [`Error::Rejected`] instead of enqueueing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `validator` - The validator, e.g. a closure checking the encoded payload.
+    ///
+    /// # Returns
+    ///
+    /// The producer with the validator set.
+    pub fn with_validator<V: Validator>(mut self, validator: V) -> Self {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Sends the records rejected by the validator to `topic` instead of dropping them,
+    /// with the reason in the [`REJECTION_HEADER`]. Their sends still fail with
+    /// [`Error::Rejected`] once delivered there.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - The topic of the rejected records, e.g. a dead letter queue.
+    ///
+    /// # Returns
+    ///
+    /// The producer with the rejects topic set.
+    pub fn reject_to<S: Into<String>>(mut self, topic: S) -> Self {
+        self.rejects_topic = Some(topic.into().into());
+        self
+    }
+
     #[inline]
     pub fn is_connected(&self) -> bool {
         self.inner.is_some()
     }
 
+    /// Returns the topics messages are produced to.
+    fn topics(&self) -> Vec<&str> {
+        match &self.broadcast {
+            Some(topics) => topics.iter().map(String::as_str).collect(),
+            None => vec![&*self.topic],
+        }
+    }
+
+    /// Verifies that the topics exist with at least `min_partitions` partitions, failing
+    /// with [`Error::TopicMissing`] instead of letting the brokers auto-create them.
+    ///
+    /// Meant to be called once before [`connect`](Self::connect), it never creates topics.
+    pub async fn preflight(&self, min_partitions: usize) -> Result<(), Error<E::Error>> {
+        self.builder.preflight(&self.topics(), min_partitions).await
+    }
+
     pub async fn connect(&mut self) -> Result<(), Error<E::Error>> {
+        self.breaker.check()?;
+
+        let res = self.try_connect().await;
+        self.breaker.record(&res);
+
+        res
+    }
+
+    async fn try_connect(&mut self) -> Result<(), Error<E::Error>> {
         self.inner = None;
-        self.inner.replace(self.builder.build_producer()?);
+        self.builder.ensure_topics(&self.topics()).await?;
+        self.inner.replace(ProducerClient::build(&self.builder)?);
         Ok(())
     }
 
+    /// Returns the number of partitions of `topic`, cached for
+    /// `topic_metadata_refresh_interval_ms` (5 minutes by default).
+    pub async fn partition_count(&mut self, topic: &str) -> Result<usize, Error<E::Error>> {
+        if let Some(count) = self.partitions.get(topic) {
+            return Ok(count);
+        }
+
+        let producer = self.inner.clone().ok_or(Error::NoConnection)?;
+        let timeout = self.partitions.timeout();
+        let name = topic.to_string();
+
+        let count = blocking(move || {
+            let metadata = producer.fetch_metadata(Some(&name), timeout)?;
+
+            Ok::<_, KafkaError>(
+                metadata
+                    .topics()
+                    .iter()
+                    .find(|topic| topic.name() == name && topic.error().is_none())
+                    .map_or(0, |topic| topic.partitions().len()),
+            )
+        })
+        .await?;
+
+        if count == 0 {
+            return Err(Error::TopicMissing {
+                topic: topic.to_string(),
+                found: 0,
+                required: 1,
+            });
+        }
+
+        self.partitions.insert(topic, count);
+
+        Ok(count)
+    }
+
+    /// Subscribes to the librdkafka statistics of this client, only emitted when
+    /// `statistics_interval_ms` is set.
+    #[inline]
+    pub fn statistics(&self) -> StatisticsReceiver {
+        self.builder.statistics()
+    }
+
+    /// Checks the connectivity of the producer with a metadata request to the brokers on
+    /// the blocking thread pool.
+    pub async fn health(&self) -> Health {
+        let Some(producer) = self.inner.clone() else {
+            return Health::disconnected(self.last_delivery);
+        };
+
+        let brokers_reachable =
+            blocking(move || producer.fetch_metadata(None, HEALTH_CHECK_TIMEOUT).is_ok()).await;
+
+        Health {
+            connected: true,
+            last_activity: self.last_delivery,
+            brokers_reachable,
+            lag: None,
+        }
+    }
+
+    /// Sends `m` to its [`topic`](KafkaMessage::topic) if it has one, otherwise to the
+    /// topics of the producer.
     pub async fn send(&mut self, m: &M) -> Result<(), Error<E::Error>> {
-        let producer = self.inner.as_mut().ok_or(Error::NoConnection)?;
+        self.send_encoded(m.topic(), |this| this.encode(m))
+            .await
+            .map(|_| ())
+    }
+
+    /// Sends `m` to `topic` instead of the topic of the producer, sharing its client.
+    ///
+    /// The topic is not created on [`connect`](Self::connect), even with
+    /// `ensure_topics` set.
+    pub async fn send_to(&mut self, topic: &str, m: &M) -> Result<(), Error<E::Error>> {
+        self.send_encoded(Some(topic), |this| this.encode(m))
+            .await
+            .map(|_| ())
+    }
+
+    /// Sends `m` to `partition` of `topic`, bypassing the partitioner, see
+    /// [`send_to`](Self::send_to).
+    pub async fn send_to_partition(
+        &mut self,
+        topic: &str,
+        partition: i32,
+        m: &M,
+    ) -> Result<(), Error<E::Error>> {
+        self.send_encoded(Some(topic), |this| {
+            this.encode(m).map(|record| Encoded {
+                partition: Some(partition),
+                ..record
+            })
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Sends a [`Record`] built for this send only, to its topic and, when set, partition,
+    /// with its headers and timestamp.
+    ///
+    /// The topic is not created on [`connect`](Self::connect), even with
+    /// `ensure_topics` set.
+    pub async fn send_record(
+        &mut self,
+        record: Record<M::Value, M::Key>,
+    ) -> Result<(), Error<E::Error>> {
+        let Record {
+            topic,
+            key,
+            value,
+            headers,
+            timestamp,
+            partition,
+        } = record;
+
+        self.send_encoded(Some(&topic), |this| {
+            Ok(Encoded {
+                payload: this.encode_value(value.as_ref())?,
+                key: this.encode_key(key)?,
+                ts_ms_utc: timestamp,
+                headers: (!headers.is_empty()).then_some(Cow::Owned(headers)),
+                partition,
+            })
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Sends `m` to its [`topic`](KafkaMessage::topic) if it has one, otherwise to the
+    /// topic of the producer, and returns it once delivered with the metadata of its record,
+    /// e.g. to persist the offset it was written at.
+    ///
+    /// The broadcast topics of the producer are ignored.
+    pub async fn send_and_confirm(&mut self, m: M) -> Result<(M, RecordMetadata), Error<E::Error>> {
+        let topic = m
+            .topic()
+            .map_or_else(|| self.topic.to_string(), str::to_string);
+
+        let metadata = self
+            .send_encoded(Some(&topic), |this| this.encode(&m))
+            .await?
+            .into_iter()
+            .next()
+            .expect("delivered to a single topic");
+
+        Ok((m, metadata))
+    }
+
+    /// Sends the record returned by `encode` to `topic`, or to the topics of the producer.
+    async fn send_encoded<'a, F>(
+        &mut self,
+        topic: Option<&str>,
+        encode: F,
+    ) -> Result<Vec<RecordMetadata>, Error<E::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<Encoded<'a>, Error<E::Error>>,
+    {
+        let broadcast = self.broadcast.clone();
+        let default = self.topic.clone();
+        let topics: Vec<&str> = match (topic, &broadcast) {
+            (Some(topic), _) => vec![topic],
+            (None, Some(topics)) => topics.iter().map(String::as_str).collect(),
+            (None, None) => vec![&*default],
+        };
+
+        self.breaker.check()?;
+        self.limiter.ready().await;
+
+        #[cfg(feature = "otel")]
+        let (span, trace_headers) = crate::otel::producer_span(&topics.join(","));
+        #[cfg(not(feature = "otel"))]
+        let trace_headers = Vec::new();
+
+        let res = self.produce(&topics, encode, &trace_headers).await;
+        self.breaker.record(&res);
+
+        #[cfg(feature = "otel")]
+        crate::otel::end_span(&span, &res);
+
+        res
+    }
+
+    /// Encodes the value and key of `m`, splitting them off the buffers so the buffers
+    /// reclaim their memory once the previous message was delivered instead of allocating.
+    fn encode<'a>(&mut self, m: &'a M) -> Result<Encoded<'a>, Error<E::Error>> {
+        Ok(Encoded {
+            payload: self.encode_value(m.value())?,
+            key: self.encode_key(m.key())?,
+            ts_ms_utc: m.ts_ms_utc(),
+            headers: message_headers(m),
+            partition: None,
+        })
+    }
+
+    fn encode_value(&mut self, value: Option<&M::Value>) -> Result<Option<Bytes>, Error<E::Error>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
 
         self.buffer.clear();
+        self.encoder
+            .encode(value, &mut self.buffer)
+            .map_err(Error::MessageCodecError)?;
 
-        if let Some(payload) = m.value() {
-            self.encoder
-                .encode(payload, &mut self.buffer)
-                .map_err(Error::MessageCodecError)?;
-        }
+        Ok(Some(self.buffer.split().freeze()))
+    }
 
-        let record = FutureRecord::to(&self.topic);
-        let key = m.key();
-        let key = key.as_ref();
-        let record = if let Some(key) = key {
-            record.key(key.as_ref())
-        } else {
-            record
+    fn encode_key(&mut self, key: Option<M::Key>) -> Result<Option<Bytes>, Error<E::Error>> {
+        let Some(key) = key else {
+            return Ok(None);
         };
 
-        let record = if m.value().is_some() {
-            record.payload(self.buffer.as_ref())
-        } else {
-            record
+        self.key_buffer.clear();
+        self.key_encoder
+            .encode(&key, &mut self.key_buffer)
+            .map_err(|err| Error::KeyCodecError(Box::new(err)))?;
+
+        Ok(Some(self.key_buffer.split().freeze()))
+    }
+
+    /// Encodes a record once and delivers it to every topic of `topics` concurrently,
+    /// returning the metadata of the deliveries or the first delivery error.
+    async fn produce<'a, F>(
+        &mut self,
+        topics: &[&str],
+        encode: F,
+        trace_headers: &[(String, String)],
+    ) -> Result<Vec<RecordMetadata>, Error<E::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<Encoded<'a>, Error<E::Error>>,
+    {
+        if !self.is_connected() {
+            return Err(Error::NoConnection);
+        }
+
+        let _permits = match self.in_flight.clone() {
+            Some((in_flight, permits)) => {
+                let count = (topics.len() as u32).min(permits);
+                Some(
+                    in_flight
+                        .acquire_many_owned(count)
+                        .await
+                        .expect("the in-flight semaphore is never closed"),
+                )
+            }
+            None => None,
         };
 
-        let record = if let Some(ts) = m.ts_ms_utc() {
-            record.timestamp(ts)
-        } else {
-            record
+        #[cfg(feature = "metrics")]
+        let encoding = std::time::Instant::now();
+
+        let Encoded {
+            payload,
+            key,
+            ts_ms_utc,
+            headers: msg_headers,
+            partition,
+        } = encode(self)?;
+
+        #[cfg(feature = "metrics")]
+        for topic in topics {
+            crate::telemetry::encode_duration(topic, encoding.elapsed());
+        }
+
+        let rejected = self.validator.as_ref().and_then(|validator| {
+            topics.iter().find_map(|topic| {
+                let record = EncodedRecord {
+                    topic,
+                    key: key.as_deref(),
+                    payload: payload.as_deref(),
+                    headers: msg_headers.as_deref().unwrap_or_default(),
+                };
+
+                validator
+                    .validate(&record)
+                    .err()
+                    .map(|rejection| (topic.to_string(), rejection))
+            })
+        });
+
+        // rejected records go to the rejects topic only, partitioned like any other record
+        let rejects_topic = self.rejects_topic.clone();
+        let rejects_topics;
+        let (topics, partition) = match (&rejected, &rejects_topic) {
+            (None, _) => (topics, partition),
+            (Some((topic, rejection)), None) => {
+                return Err(Error::Rejected {
+                    topic: topic.clone(),
+                    rejection: rejection.clone(),
+                });
+            }
+            (Some(..), Some(rejects_topic)) => {
+                rejects_topics = [&**rejects_topic];
+                (&rejects_topics[..], None)
+            }
         };
 
-        let record = if let Some(headers) = m.headers() {
+        let payload_len = payload.as_ref().map_or(0, Bytes::len);
+        self.limiter
+            .record(topics.len(), payload_len * topics.len());
+
+        let mut partitions = Vec::with_capacity(topics.len());
+        if let Some(partition) = partition {
+            partitions.resize(topics.len(), Some(partition));
+        } else if let Some(partitioner) = self.partitioner.clone() {
+            for topic in topics {
+                let count = self.partition_count(topic).await?;
+                partitions.push(partitioner.partition(topic, key.as_deref(), payload_len, count));
+            }
+        }
+
+        let producer = self.inner.as_ref().ok_or(Error::NoConnection)?;
+
+        let headers = if msg_headers.is_some() || !trace_headers.is_empty() || rejected.is_some() {
             let mut rdk_headers = OwnedHeaders::new();
-            for (k, v) in headers {
+            if let Some((_, rejection)) = &rejected {
+                rdk_headers = rdk_headers.insert(RdkHeader {
+                    key: REJECTION_HEADER,
+                    value: Some(rejection.reason.as_bytes()),
+                });
+            }
+            for (k, v) in msg_headers.as_deref().unwrap_or_default() {
                 rdk_headers = rdk_headers.insert(RdkHeader {
                     key: k.as_ref(),
                     value: Some(v.as_slice()),
                 });
             }
-            record.headers(rdk_headers)
+            for (k, v) in trace_headers {
+                rdk_headers = rdk_headers.insert(RdkHeader {
+                    key: k.as_ref(),
+                    value: Some(v.as_bytes()),
+                });
+            }
+            Some(rdk_headers)
         } else {
-            record
+            None
+        };
+
+        let topic_headers: Vec<_> = topics
+            .iter()
+            .map(|topic| {
+                let mut extra = Vec::new();
+                for interceptor in &self.interceptors {
+                    interceptor.on_send(topic, &mut extra);
+                }
+
+                if extra.is_empty() {
+                    return headers.clone();
+                }
+
+                let mut rdk_headers = headers.clone().unwrap_or_default();
+                for (k, v) in &extra {
+                    rdk_headers = rdk_headers.insert(RdkHeader {
+                        key: k.as_ref(),
+                        value: Some(v.as_slice()),
+                    });
+                }
+                Some(rdk_headers)
+            })
+            .collect();
+
+        let record_to = |topic, headers: &Option<OwnedHeaders>, partition: Option<i32>| {
+            let record = FutureRecord::to(topic);
+            let record = if let Some(partition) = partition {
+                record.partition(partition)
+            } else {
+                record
+            };
+            let record = if let Some(key) = &key {
+                record.key(key.as_ref())
+            } else {
+                record
+            };
+
+            let record = if let Some(payload) = &payload {
+                record.payload(payload.as_ref())
+            } else {
+                record
+            };
+
+            let record = if let Some(ts) = ts_ms_utc {
+                record.timestamp(ts)
+            } else {
+                record
+            };
+
+            if let Some(headers) = headers {
+                record.headers(headers.clone())
+            } else {
+                record
+            }
         };
+        let record_to = &record_to;
+        let queue_full = self.queue_full;
+        let config = self.builder.config();
+        let send_timeout = Duration::from_millis(config.send_timeout_ms.unwrap_or(0).into());
+        let delivery_timeout = config
+            .delivery_timeout_ms
+            .map(|x| Duration::from_millis(x.into()));
 
-        let res = producer
-            .send(record, std::time::Duration::from_secs(0))
-            .await;
+        let started = std::time::Instant::now();
 
-        match res {
-            Ok(_) => Ok(()),
-            Err((err, _msg)) => Err(err.into()),
+        let partitions = &partitions;
+        let results = futures::future::join_all(topics.iter().zip(&topic_headers).enumerate().map(
+            |(i, (topic, headers))| async move {
+                let partition = partitions.get(i).copied().flatten();
+                let enqueued = std::time::Instant::now();
+                let mut attempt = 0;
+
+                loop {
+                    let res = producer
+                        .send(
+                            record_to(*topic, headers, partition),
+                            send_timeout,
+                            delivery_timeout,
+                        )
+                        .await;
+
+                    match (&res, queue_full) {
+                        (
+                            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)),
+                            QueueFullPolicy::Retry {
+                                timeout_ms,
+                                backoff,
+                                poll,
+                            },
+                        ) if enqueued.elapsed() < Duration::from_millis(timeout_ms.into()) => {
+                            if poll {
+                                producer.poll(Duration::ZERO);
+                            }
+
+                            crate::runtime::sleep(backoff.delay(attempt)).await;
+                            attempt += 1;
+                        }
+                        _ => break res,
+                    }
+                }
+            },
+        ))
+        .await;
+
+        let mut error = None;
+        let mut delivered = Vec::with_capacity(topics.len());
+
+        for ((topic, headers), res) in topics.iter().zip(&topic_headers).zip(results) {
+            let elapsed = started.elapsed();
+
+            #[cfg(feature = "metrics")]
+            crate::telemetry::message_sent(topic, payload_len, elapsed, res.is_ok());
+
+            let record = SentRecord {
+                topic,
+                key: key.as_deref(),
+                payload_len,
+                headers: headers.as_ref(),
+            };
+
+            for interceptor in &self.interceptors {
+                let result = res.as_ref().map_err(|(err, _)| err);
+                interceptor.on_ack(topic, result);
+                interceptor.on_delivery(&record, result, elapsed);
+            }
+
+            match res {
+                Ok(delivery) => {
+                    self.last_delivery = Some(SystemTime::now());
+                    delivered.push(RecordMetadata {
+                        topic: topic.to_string(),
+                        partition: delivery.partition,
+                        offset: delivery.offset,
+                        timestamp: delivery.timestamp.to_millis(),
+                    });
+                }
+                Err((source, msg)) if error.is_none() => {
+                    let err = Error::Deliver {
+                        topic: topic.to_string(),
+                        source,
+                    };
+
+                    // a message failing before reaching a broker has no partition or offset
+                    error = Some(if msg.partition() >= 0 && msg.offset() >= 0 {
+                        err.at(msg.topic(), msg.partition(), msg.offset())
+                    } else {
+                        err
+                    });
+                }
+                Err(..) => (),
+            }
+        }
+
+        match (error, rejected) {
+            (Some(err), _) => Err(err),
+            (None, Some((topic, rejection))) => Err(Error::Rejected { topic, rejection }),
+            (None, None) => Ok(delivered),
         }
     }
 }
 
-impl<M, E> Service<M> for KafkaProducer<M, E>
+impl<M, E, KE> KafkaProducer<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value> + OwnedEncoder<M::Value, Error = <E as Encoder<M::Value>>::Error>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Sends `m` like [`send`](Self::send), moving its value into the encoder so values
+    /// holding their bytes already, e.g. `Bytes`, are sent without being copied.
+    pub async fn send_owned(&mut self, m: M) -> Result<(), Error<<E as Encoder<M::Value>>::Error>> {
+        let topic = m.topic().map(str::to_string);
+
+        self.send_encoded(topic.as_deref(), move |this| this.encode_owned(m))
+            .await
+            .map(|_| ())
+    }
+
+    fn encode_owned(
+        &mut self,
+        m: M,
+    ) -> Result<Encoded<'static>, Error<<E as Encoder<M::Value>>::Error>> {
+        let key = m.key();
+        let ts_ms_utc = m.ts_ms_utc();
+        let headers = message_headers(&m).map(|x| Cow::Owned(x.into_owned()));
+
+        let payload = match m.into_value() {
+            Some(payload) => {
+                self.buffer.clear();
+                Some(
+                    OwnedEncoder::encode_owned(&mut self.encoder, payload, &mut self.buffer)
+                        .map_err(Error::MessageCodecError)?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Encoded {
+            payload,
+            key: self.encode_key(key)?,
+            ts_ms_utc,
+            headers,
+            partition: None,
+        })
+    }
+}
+
+/// Encoded message ready to be produced.
+struct Encoded<'a> {
+    payload: Option<Bytes>,
+    key: Option<Bytes>,
+    ts_ms_utc: Option<i64>,
+    headers: Option<MessageHeaders<'a>>,
+
+    /// Partition of the record on every topic, taking precedence over the partitioner.
+    partition: Option<i32>,
+}
+
+impl<M, E, KE> KafkaProducer<M, E, KE>
 where
     M: KafkaMessage + Send + Sync,
     M::Key: Send,
     M::Value: Send,
     E: Encoder<M::Value> + Send,
     E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
 {
-    type Out = Result<M, Error<E::Error>>;
+    /// Sends `input` like the flowly `Service` of the producer, connecting and reconnecting
+    /// as needed, and returns it once delivered.
+    pub async fn send_with_retry(&mut self, input: M) -> Result<M, Error<E::Error>> {
+        let mut reconnect_counter = if self.reconnect_count == 0 {
+            u64::MAX
+        } else {
+            self.reconnect_count as u64
+        };
 
-    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
-        async move {
-            let mut reconnect_counter = if self.reconnect_count == 0 {
-                u64::MAX
-            } else {
-                self.reconnect_count as u64
-            };
+        let mut error = None;
+        let mut failures = 0;
 
-            let mut error = None;
-
-            while reconnect_counter > 0 {
-                if !self.is_connected() {
-                    match self.connect().await {
-                        Ok(..) => (),
-                        Err(err) => {
-                            error.replace(err);
-                            reconnect_counter -= 1;
-                            tokio::time::sleep(std::time::Duration::from_millis(
-                                self.reconnect_sleep_ms as _,
-                            ))
-                            .await;
-                            continue;
-                        }
+        while reconnect_counter > 0 {
+            if !self.is_connected() {
+                match self.connect().await {
+                    Ok(..) => (),
+                    Err(err @ (Error::InvalidConfig(..) | Error::CircuitOpen { .. })) => {
+                        return Err(err);
                     }
-                }
-
-                match self.send(&input).await {
-                    Ok(..) => return Ok(input),
-                    Err(Error::KafkaError(KafkaError::Transaction(e))) if e.is_fatal() => {
-                        error.replace(Error::KafkaError(KafkaError::Transaction(e)));
+                    Err(err) => {
+                        error.replace(err);
                         reconnect_counter -= 1;
-                        self.inner = None;
-                        tokio::time::sleep(std::time::Duration::from_millis(
-                            self.reconnect_sleep_ms as _,
-                        ))
-                        .await;
+                        crate::runtime::sleep(self.backoff.delay(failures)).await;
+                        failures += 1;
                         continue;
                     }
-                    Err(err) => return Err(err),
                 }
             }
 
-            Err(error.unwrap())
+            match self.send(&input).await {
+                Ok(..) => return Ok(input),
+                Err(err)
+                    if matches!(
+                        err.root(),
+                        Error::Deliver { source: KafkaError::Transaction(e), .. } if e.is_fatal()
+                    ) =>
+                {
+                    error.replace(err);
+                    reconnect_counter -= 1;
+                    self.inner = None;
+                    crate::runtime::sleep(self.backoff.delay(failures)).await;
+                    failures += 1;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
         }
-        .into_stream()
+
+        Err(error.unwrap())
+    }
+
+    /// Turns the producer into a sink sending every message with
+    /// [`send_with_retry`](Self::send_with_retry), for applications not using flowly.
+    pub fn into_sink(self) -> impl Sink<M, Error = Error<E::Error>> + Send
+    where
+        Self: Send,
+    {
+        futures::sink::unfold(self, |mut this, input| async move {
+            this.send_with_retry(input).await?;
+            Ok(this)
+        })
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E, KE> Service<M> for KafkaProducer<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Out = Result<M, Error<E::Error>>;
+
+    #[inline]
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        self.send_with_retry(input).into_stream()
     }
 }
@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use rdkafka::{
+    error::{KafkaError, KafkaResult},
+    message::{OwnedMessage, Timestamp, ToBytes},
+    metadata::Metadata,
+    producer::{
+        BaseRecord, FutureProducer, FutureRecord, Producer, ThreadedProducer,
+        future_producer::OwnedDeliveryResult,
+    },
+    types::RDKafkaErrorCode,
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    KafkaCallbackContext, builder::KafkaBuilder, config::ProducerKind, error::Error,
+    runtime::Runtime,
+};
+
+/// Delay between two attempts to enqueue a message while the queue is full, like the
+/// `FutureProducer`.
+const QUEUE_FULL_DELAY: Duration = Duration::from_millis(100);
+
+/// librdkafka client of a producer, picked by [`Config::producer_kind`](crate::config::Config).
+#[derive(Clone)]
+pub(crate) enum ProducerClient {
+    Future(FutureProducer<KafkaCallbackContext, Runtime>),
+    Threaded(ThreadedProducer<KafkaCallbackContext>),
+}
+
+impl ProducerClient {
+    pub(crate) fn build<E>(builder: &KafkaBuilder) -> Result<Self, Error<E>> {
+        match builder.config().producer_kind {
+            ProducerKind::Future => builder.build_producer().map(Self::Future),
+            ProducerKind::Threaded => builder.build_threaded_producer().map(Self::Threaded),
+        }
+    }
+
+    pub(crate) fn fetch_metadata(
+        &self,
+        topic: Option<&str>,
+        timeout: Duration,
+    ) -> KafkaResult<Metadata> {
+        match self {
+            Self::Future(producer) => producer.client().fetch_metadata(topic, timeout),
+            Self::Threaded(producer) => producer.client().fetch_metadata(topic, timeout),
+        }
+    }
+
+    /// Serves the pending delivery callbacks.
+    pub(crate) fn poll(&self, timeout: Duration) {
+        match self {
+            Self::Future(producer) => producer.poll(timeout),
+            Self::Threaded(producer) => producer.poll(timeout),
+        }
+    }
+
+    /// Enqueues `record`, waiting up to `queue_timeout` for room in the queue, and waits for
+    /// its delivery for up to `delivery_timeout`.
+    pub(crate) async fn send(
+        &self,
+        record: FutureRecord<'_, [u8], [u8]>,
+        queue_timeout: Duration,
+        delivery_timeout: Option<Duration>,
+    ) -> OwnedDeliveryResult {
+        let topic = record.topic;
+        let delivery = self.deliver(record, queue_timeout);
+
+        match delivery_timeout {
+            Some(timeout) => match crate::runtime::timeout(timeout, delivery).await {
+                Some(result) => result,
+                None => Err((
+                    KafkaError::MessageProduction(RDKafkaErrorCode::MessageTimedOut),
+                    undelivered(topic),
+                )),
+            },
+            None => delivery.await,
+        }
+    }
+
+    async fn deliver(
+        &self,
+        record: FutureRecord<'_, [u8], [u8]>,
+        queue_timeout: Duration,
+    ) -> OwnedDeliveryResult {
+        let producer = match self {
+            Self::Future(producer) => return producer.send(record, queue_timeout).await,
+            Self::Threaded(producer) => producer,
+        };
+
+        let enqueued = Instant::now();
+        let topic = record.topic;
+        let (tx, rx) = oneshot::channel();
+        let mut record = BaseRecord {
+            topic,
+            partition: record.partition,
+            payload: record.payload,
+            key: record.key,
+            timestamp: record.timestamp,
+            headers: record.headers,
+            delivery_opaque: Box::new(tx),
+        };
+
+        loop {
+            match producer.send(record) {
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), rejected))
+                    if enqueued.elapsed() < queue_timeout =>
+                {
+                    record = rejected;
+                    crate::runtime::sleep(QUEUE_FULL_DELAY).await;
+                }
+
+                // the delivery callback always runs, unless the client is gone
+                Ok(()) => {
+                    break match rx.await {
+                        Ok(result) => result,
+                        Err(_) => Err((KafkaError::Canceled, undelivered(topic))),
+                    };
+                }
+
+                Err((error, record)) => {
+                    break Err((
+                        error,
+                        OwnedMessage::new(
+                            record.payload.map(|x| x.to_bytes().to_vec()),
+                            record.key.map(|x| x.to_bytes().to_vec()),
+                            topic.to_owned(),
+                            record
+                                .timestamp
+                                .map_or(Timestamp::NotAvailable, Timestamp::CreateTime),
+                            record.partition.unwrap_or(-1),
+                            -1,
+                            record.headers,
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Stand-in for a message whose delivery result was not received.
+fn undelivered(topic: &str) -> OwnedMessage {
+    OwnedMessage::new(
+        None,
+        None,
+        topic.to_owned(),
+        Timestamp::NotAvailable,
+        -1,
+        -1,
+        None,
+    )
+}
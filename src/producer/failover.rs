@@ -0,0 +1,192 @@
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "flowly")]
+use flowly::Service;
+#[cfg(feature = "flowly")]
+use futures::{FutureExt, Stream};
+
+use crate::{
+    KafkaMessage,
+    codec::{Encoder, RawEncoder},
+    config::{CircuitBreakerConfig, Config},
+    error::Error,
+};
+
+use super::KafkaProducer;
+
+/// Circuit breaker of the clusters whose config has none, a failover needs one to decide
+/// when a cluster is down.
+const DEFAULT_CIRCUIT_BREAKER: CircuitBreakerConfig = CircuitBreakerConfig {
+    failure_threshold: 5,
+    cooldown_ms: 30_000,
+};
+
+/// Produces to a primary cluster and fails over to standby clusters while it is down.
+///
+/// A cluster is skipped while its circuit breaker is open. When running on a standby the
+/// primary is probed with the message being sent at most once per probe interval, and the
+/// producer fails back to it as soon as a delivery succeeds.
+pub struct FailoverProducer<M, E, KE = RawEncoder> {
+    clusters: Vec<KafkaProducer<M, E, KE>>,
+    active: usize,
+    probe_interval: Duration,
+    last_probe: Instant,
+}
+
+impl<M, E> FailoverProducer<M, E>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value> + Clone,
+{
+    /// Creates a producer of `topic` on the `primary` cluster failing over to the `standbys`
+    /// in order. Configs without a circuit breaker get one opening after 5 failures for 30s.
+    pub fn new<S: Into<String>>(
+        encoder: E,
+        primary: Config,
+        standbys: impl IntoIterator<Item = Config>,
+        topic: S,
+    ) -> Self {
+        let topic = topic.into();
+        let mut clusters = std::iter::once(primary).chain(standbys).map(|mut config| {
+            config
+                .circuit_breaker
+                .get_or_insert(DEFAULT_CIRCUIT_BREAKER);
+            KafkaProducer::new(encoder.clone(), config, topic.clone())
+        });
+
+        let primary = clusters
+            .next()
+            .expect("the primary cluster is always present");
+
+        Self::from_producers(primary, clusters.collect())
+    }
+}
+
+impl<M, E, KE> FailoverProducer<M, E, KE> {
+    /// Creates a failover over already configured producers, their configs need a
+    /// circuit breaker for the failover to ever happen.
+    pub fn from_producers(
+        primary: KafkaProducer<M, E, KE>,
+        standbys: Vec<KafkaProducer<M, E, KE>>,
+    ) -> Self {
+        let mut clusters = vec![primary];
+        clusters.extend(standbys);
+
+        Self {
+            clusters,
+            active: 0,
+            probe_interval: Duration::from_millis(DEFAULT_CIRCUIT_BREAKER.cooldown_ms.into()),
+            last_probe: Instant::now(),
+        }
+    }
+
+    /// Sets how often the primary cluster is probed while running on a standby.
+    ///
+    /// # Arguments
+    ///
+    /// * `probe_interval` - The minimum time between two probes, 30 seconds by default.
+    ///
+    /// # Returns
+    ///
+    /// The producer with the updated probe interval.
+    pub fn probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Returns the index of the cluster messages are sent to, 0 being the primary.
+    #[inline]
+    pub fn active_cluster(&self) -> usize {
+        self.active
+    }
+}
+
+impl<M, E, KE> FailoverProducer<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub async fn send(&mut self, m: &M) -> Result<(), Error<E::Error>> {
+        if self.active != 0 && self.last_probe.elapsed() >= self.probe_interval {
+            self.last_probe = Instant::now();
+
+            if send_to(&mut self.clusters[0], m).await.is_ok() {
+                #[cfg(feature = "tracing")]
+                tracing::info!("kafka producer failed back to the primary cluster");
+
+                #[cfg(not(feature = "tracing"))]
+                log::info!("kafka producer failed back to the primary cluster");
+
+                self.active = 0;
+                return Ok(());
+            }
+        }
+
+        let mut error = None;
+
+        for _ in 0..self.clusters.len() {
+            match send_to(&mut self.clusters[self.active], m).await {
+                Ok(()) => return Ok(()),
+                Err(err @ Error::CircuitOpen { .. }) => {
+                    let next = (self.active + 1) % self.clusters.len();
+
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        "kafka producer cluster {} is down, failing over to cluster {next}",
+                        self.active
+                    );
+
+                    #[cfg(not(feature = "tracing"))]
+                    log::warn!(
+                        "kafka producer cluster {} is down, failing over to cluster {next}",
+                        self.active
+                    );
+
+                    self.active = next;
+                    self.last_probe = Instant::now();
+                    error.replace(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(error.unwrap_or(Error::NoConnection))
+    }
+}
+
+async fn send_to<M, E, KE>(
+    producer: &mut KafkaProducer<M, E, KE>,
+    m: &M,
+) -> Result<(), Error<E::Error>>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    if !producer.is_connected() {
+        producer.connect().await?;
+    }
+
+    producer.send(m).await
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E, KE> Service<M> for FailoverProducer<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Out = Result<M, Error<E::Error>>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async move { self.send(&input).await.map(|_| input) }.into_stream()
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Picks the partitions of the messages of a producer instead of the librdkafka
+/// `partitioner`, see [`KafkaProducer::with_partitioner`](super::KafkaProducer::with_partitioner).
+pub trait Partitioner: Send + Sync + 'static {
+    /// Returns the partition of a message to `topic`, `None` leaving it to librdkafka.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - The topic the message is produced to.
+    /// * `key` - The encoded key of the message.
+    /// * `payload_len` - The size of the encoded payload in bytes.
+    /// * `partition_count` - The number of partitions of `topic`, at least 1.
+    fn partition(
+        &self,
+        topic: &str,
+        key: Option<&[u8]>,
+        payload_len: usize,
+        partition_count: usize,
+    ) -> Option<i32>;
+}
+
+/// Partition a sticky partitioner sends the keyless messages of a topic to.
+#[derive(Debug)]
+struct Sticky {
+    partition: i32,
+    since: Instant,
+    bytes: usize,
+}
+
+/// Sends the keyless messages of a topic to one partition until a batch is full or its
+/// linger expired, then moves to another one, like the sticky partitioner of the Java
+/// client. Batches fill up faster than when spreading every message, lowering latency.
+///
+/// Keyed messages are left to librdkafka, so a key keeps mapping to the same partition.
+#[derive(Debug)]
+pub struct StickyPartitioner {
+    batch_size: usize,
+    linger: Duration,
+    sticky: Mutex<HashMap<String, Sticky>>,
+}
+
+impl StickyPartitioner {
+    /// Creates a partitioner moving to another partition once `batch_size` bytes were sent
+    /// to the current one or `linger` elapsed, usually the `batch.size` and `linger.ms` of
+    /// the producer.
+    pub fn new(batch_size: usize, linger: Duration) -> Self {
+        Self {
+            batch_size,
+            linger,
+            sticky: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for StickyPartitioner {
+    /// The librdkafka defaults of `batch.size` and `linger.ms`.
+    fn default() -> Self {
+        Self::new(1_000_000, Duration::from_millis(5))
+    }
+}
+
+impl Partitioner for StickyPartitioner {
+    fn partition(
+        &self,
+        topic: &str,
+        key: Option<&[u8]>,
+        payload_len: usize,
+        partition_count: usize,
+    ) -> Option<i32> {
+        if key.is_some() {
+            return None;
+        }
+
+        let mut sticky = self.sticky.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+
+        if !sticky.contains_key(topic) {
+            sticky.insert(
+                topic.to_string(),
+                Sticky {
+                    partition: next_partition(None, partition_count),
+                    since: now,
+                    bytes: 0,
+                },
+            );
+        }
+
+        let current = sticky.get_mut(topic).expect("inserted above");
+
+        if current.bytes >= self.batch_size
+            || now.duration_since(current.since) >= self.linger
+            || current.partition as usize >= partition_count
+        {
+            *current = Sticky {
+                partition: next_partition(Some(current.partition), partition_count),
+                since: now,
+                bytes: 0,
+            };
+        }
+
+        current.bytes += payload_len;
+
+        Some(current.partition)
+    }
+}
+
+/// Sends the messages of a topic to its partitions in turn, keyed or not, for an even
+/// distribution when no consumer relies on a key mapping to the same partition.
+#[derive(Debug, Default)]
+pub struct RoundRobinPartitioner {
+    counters: Mutex<HashMap<String, usize>>,
+}
+
+impl RoundRobinPartitioner {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Partitioner for RoundRobinPartitioner {
+    fn partition(
+        &self,
+        topic: &str,
+        _key: Option<&[u8]>,
+        _payload_len: usize,
+        partition_count: usize,
+    ) -> Option<i32> {
+        let mut counters = self.counters.lock().unwrap_or_else(|err| err.into_inner());
+
+        if !counters.contains_key(topic) {
+            counters.insert(topic.to_string(), 0);
+        }
+
+        let counter = counters.get_mut(topic).expect("inserted above");
+
+        let partition = *counter % partition_count.max(1);
+        *counter = counter.wrapping_add(1);
+
+        Some(partition as i32)
+    }
+}
+
+/// Picks a random partition, another one than `previous` when there are several.
+fn next_partition(previous: Option<i32>, partition_count: usize) -> i32 {
+    let count = partition_count.max(1) as u64;
+    let random = random_u64();
+
+    match previous {
+        Some(previous) if count > 1 && (previous as u64) < count => {
+            ((previous as u64 + 1 + random % (count - 1)) % count) as i32
+        }
+        _ => (random % count) as i32,
+    }
+}
+
+/// Random enough to spread partitions, without a dependency on a random number generator.
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, RandomState};
+
+    RandomState::new().hash_one(Instant::now())
+}
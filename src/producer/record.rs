@@ -0,0 +1,149 @@
+use bytes::Bytes;
+
+use crate::KafkaMessage;
+
+/// A one-off message to send with
+/// [`KafkaProducer::send_record`](super::KafkaProducer::send_record), for when implementing
+/// [`KafkaMessage`] on a type of its own is overkill.
+///
+/// ```ignore
+/// let record = Record::to("orders")
+///     .key(order.id.clone())
+///     .value(order)
+///     .header("tenant", "acme")
+///     .timestamp(ts_ms);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<V, K = Bytes> {
+    pub topic: String,
+    pub key: Option<K>,
+    pub value: Option<V>,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub timestamp: Option<i64>,
+    pub partition: Option<i32>,
+}
+
+impl<V, K> Record<V, K> {
+    /// Creates an empty record to `topic`.
+    pub fn to<S: Into<String>>(topic: S) -> Self {
+        Self {
+            topic: topic.into(),
+            key: None,
+            value: None,
+            headers: Vec::new(),
+            timestamp: None,
+            partition: None,
+        }
+    }
+
+    /// # Arguments
+    ///
+    /// * `key` - The key of the record, encoded by the key encoder of the producer.
+    ///
+    /// # Returns
+    ///
+    /// The record with the key set.
+    pub fn key(mut self, key: K) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `value` - The value of the record, encoded by the encoder of the producer.
+    ///
+    /// # Returns
+    ///
+    /// The record with the value set.
+    pub fn value(mut self, value: V) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Adds a header after the existing ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the header.
+    /// * `value` - The value of the header.
+    ///
+    /// # Returns
+    ///
+    /// The record with the header added.
+    pub fn header<S: Into<String>, B: Into<Vec<u8>>>(mut self, key: S, value: B) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `ts_ms_utc` - The timestamp of the record in milliseconds since the epoch.
+    ///
+    /// # Returns
+    ///
+    /// The record with the timestamp set, by default it is the time of the send.
+    pub fn timestamp(mut self, ts_ms_utc: i64) -> Self {
+        self.timestamp = Some(ts_ms_utc);
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `partition` - The partition of the record, bypassing the partitioner.
+    ///
+    /// # Returns
+    ///
+    /// The record with the partition set.
+    pub fn partition(mut self, partition: i32) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+}
+
+impl<V, K: Clone> KafkaMessage for Record<V, K> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn key(&self) -> Option<Self::Key> {
+        self.key.clone()
+    }
+
+    #[inline]
+    fn value(&self) -> Option<&Self::Value> {
+        self.value.as_ref()
+    }
+
+    #[inline]
+    fn ts_ms_utc(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    #[inline]
+    fn into_value(self) -> Option<Self::Value> {
+        self.value
+    }
+
+    #[inline]
+    fn headers(&self) -> Option<&[(String, Vec<u8>)]> {
+        (!self.headers.is_empty()).then_some(self.headers.as_slice())
+    }
+
+    #[inline]
+    fn topic(&self) -> Option<&str> {
+        Some(&self.topic)
+    }
+}
+
+/// Where a record was written, see
+/// [`KafkaProducer::send_and_confirm`](super::KafkaProducer::send_and_confirm).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordMetadata {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+
+    /// Timestamp of the record in milliseconds since the epoch, as set by the producer or
+    /// the broker.
+    pub timestamp: Option<i64>,
+}
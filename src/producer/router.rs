@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+#[cfg(feature = "flowly")]
+use flowly::Service;
+#[cfg(feature = "flowly")]
+use futures::{FutureExt, Stream};
+
+use crate::{
+    KafkaMessage,
+    codec::{Encoder, RawEncoder},
+    config::Config,
+    error::Error,
+    message::message_headers,
+};
+
+use super::KafkaProducer;
+
+type Predicate<M> = dyn Fn(&M) -> bool + Send + Sync;
+type Extractor = dyn Fn(&[(String, Vec<u8>)]) -> Route + Send + Sync;
+
+/// Produces every message to the topic of the first rule whose predicate matches it, or to
+/// the default topic, over a single producer client.
+pub struct TopicRouter<M, E, KE = RawEncoder> {
+    producer: KafkaProducer<M, E, KE>,
+    rules: Vec<(Arc<Predicate<M>>, Arc<str>)>,
+}
+
+impl<M, E> TopicRouter<M, E>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+{
+    /// Creates a router producing the unmatched messages to `default_topic`.
+    pub fn new<S: Into<String>>(encoder: E, config: Config, default_topic: S) -> Self {
+        Self::from_producer(KafkaProducer::new(encoder, config, default_topic))
+    }
+}
+
+impl<M, E, KE> TopicRouter<M, E, KE> {
+    /// Creates a router over an already configured producer, whose topic is the default
+    /// topic.
+    pub fn from_producer(producer: KafkaProducer<M, E, KE>) -> Self {
+        Self {
+            producer,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Adds a rule after the existing ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Matches the messages to route.
+    /// * `topic` - The topic of the matched messages.
+    ///
+    /// # Returns
+    ///
+    /// The router with the rule added.
+    pub fn route<F, S>(mut self, predicate: F, topic: S) -> Self
+    where
+        F: Fn(&M) -> bool + Send + Sync + 'static,
+        S: Into<String>,
+    {
+        self.rules.push((Arc::new(predicate), topic.into().into()));
+        self
+    }
+
+    /// Returns the topic `m` is routed to.
+    pub fn topic_for(&self, m: &M) -> &str {
+        self.rules
+            .iter()
+            .find(|(predicate, _)| predicate(m))
+            .map_or(&*self.producer.topic, |(_, topic)| &**topic)
+    }
+}
+
+impl<M, E, KE> TopicRouter<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.producer.is_connected()
+    }
+
+    /// Connects the producer, ensuring the default topic and the topics of the rules when
+    /// `ensure_topics` is set.
+    pub async fn connect(&mut self) -> Result<(), Error<E::Error>> {
+        self.producer.connect().await?;
+
+        let topics: Vec<&str> = self.rules.iter().map(|(_, topic)| &**topic).collect();
+        self.producer.builder.ensure_topics(&topics).await?;
+
+        Ok(())
+    }
+
+    pub async fn send(&mut self, m: &M) -> Result<(), Error<E::Error>> {
+        let topic = match self.rules.iter().find(|(predicate, _)| predicate(m)) {
+            Some((_, topic)) => topic.clone(),
+            None => return self.producer.send(m).await,
+        };
+
+        self.producer.send_to(&topic, m).await
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E, KE> Service<M> for TopicRouter<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Out = Result<M, Error<E::Error>>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async move {
+            if !self.is_connected() {
+                self.connect().await?;
+            }
+
+            self.send(&input).await.map(|_| input)
+        }
+        .into_stream()
+    }
+}
+
+/// Destination of a message read from its headers by a [`HeaderRouter`], the parts left
+/// unset falling back to the producer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Route {
+    pub topic: Option<String>,
+    pub partition: Option<i32>,
+}
+
+impl Route {
+    /// Routes to `topic`, partitioned by the producer.
+    pub fn topic<S: Into<String>>(topic: S) -> Self {
+        Self {
+            topic: Some(topic.into()),
+            partition: None,
+        }
+    }
+
+    /// Routes to `partition` of the topic of the route, or of the producer.
+    ///
+    /// # Arguments
+    ///
+    /// * `partition` - The partition of the message.
+    ///
+    /// # Returns
+    ///
+    /// The route with the partition set.
+    pub fn partition(mut self, partition: i32) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+}
+
+/// Produces every message to the topic and partition extracted from its headers, e.g. a
+/// tenant id header selecting the topic of the tenant, over a single producer client.
+///
+/// Messages without headers are routed by the extractor from an empty list. Topics
+/// extracted are not created on [`connect`](Self::connect), even with `ensure_topics` set.
+pub struct HeaderRouter<M, E, KE = RawEncoder> {
+    producer: KafkaProducer<M, E, KE>,
+    extractor: Arc<Extractor>,
+}
+
+impl<M, E> HeaderRouter<M, E>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+{
+    /// Creates a router producing to `default_topic` when the extractor returns no topic.
+    pub fn new<S, F>(encoder: E, config: Config, default_topic: S, extractor: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&[(String, Vec<u8>)]) -> Route + Send + Sync + 'static,
+    {
+        Self::from_producer(
+            KafkaProducer::new(encoder, config, default_topic),
+            extractor,
+        )
+    }
+}
+
+impl<M, E, KE> HeaderRouter<M, E, KE> {
+    /// Creates a router over an already configured producer, whose topic is the default
+    /// topic.
+    pub fn from_producer<F>(producer: KafkaProducer<M, E, KE>, extractor: F) -> Self
+    where
+        F: Fn(&[(String, Vec<u8>)]) -> Route + Send + Sync + 'static,
+    {
+        Self {
+            producer,
+            extractor: Arc::new(extractor),
+        }
+    }
+}
+
+impl<M, E, KE> HeaderRouter<M, E, KE>
+where
+    M: KafkaMessage,
+    E: Encoder<M::Value>,
+    KE: Encoder<M::Key>,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Returns the route of `m`.
+    pub fn route_for(&self, m: &M) -> Route {
+        (self.extractor)(message_headers(m).as_deref().unwrap_or_default())
+    }
+
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.producer.is_connected()
+    }
+
+    pub async fn connect(&mut self) -> Result<(), Error<E::Error>> {
+        self.producer.connect().await
+    }
+
+    pub async fn send(&mut self, m: &M) -> Result<(), Error<E::Error>> {
+        let Route { topic, partition } = self.route_for(m);
+        let topic = topic.unwrap_or_else(|| self.producer.topic.to_string());
+
+        match partition {
+            Some(partition) => self.producer.send_to_partition(&topic, partition, m).await,
+            None => self.producer.send_to(&topic, m).await,
+        }
+    }
+}
+
+#[cfg(feature = "flowly")]
+impl<M, E, KE> Service<M> for HeaderRouter<M, E, KE>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: Send,
+    M::Value: Send,
+    E: Encoder<M::Value> + Send,
+    E::Error: Send,
+    KE: Encoder<M::Key> + Send,
+    KE::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Out = Result<M, Error<E::Error>>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async move {
+            if !self.is_connected() {
+                self.connect().await?;
+            }
+
+            self.send(&input).await.map(|_| input)
+        }
+        .into_stream()
+    }
+}
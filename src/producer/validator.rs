@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+/// Header of the records sent to the rejects topic, holding the reason of the rejection,
+/// see [`KafkaProducer::reject_to`](super::KafkaProducer::reject_to).
+pub const REJECTION_HEADER: &str = "flowly.rejection";
+
+/// Why a [`Validator`] rejected a record.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{reason}")]
+pub struct Rejection {
+    pub reason: String,
+}
+
+impl Rejection {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// An encoded record about to be sent, see [`Validator`].
+#[derive(Debug, Clone, Copy)]
+pub struct EncodedRecord<'a> {
+    pub topic: &'a str,
+    pub key: Option<&'a [u8]>,
+    pub payload: Option<&'a [u8]>,
+    pub headers: &'a [(String, Vec<u8>)],
+}
+
+/// Checks the records of a producer once encoded and before they are enqueued, so malformed
+/// events are stopped at the edge, see
+/// [`KafkaProducer::with_validator`](super::KafkaProducer::with_validator).
+///
+/// Implemented for closures taking the record.
+pub trait Validator: Send + Sync + 'static {
+    fn validate(&self, record: &EncodedRecord<'_>) -> Result<(), Rejection>;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&EncodedRecord<'_>) -> Result<(), Rejection> + Send + Sync + 'static,
+{
+    #[inline]
+    fn validate(&self, record: &EncodedRecord<'_>) -> Result<(), Rejection> {
+        self(record)
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitConfig;
+
+/// Token bucket holding up to one second worth of its rate, charged after the fact so a
+/// single large message is let through and delays the next ones instead.
+#[derive(Debug, Clone)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.into(),
+            tokens: rate.into(),
+            updated: Instant::now(),
+        }
+    }
+
+    /// Returns how long until the bucket is out of debt.
+    fn wait_time(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Message and byte rate limits of a consumer or producer.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    messages: Option<Bucket>,
+    bytes: Option<Bucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: Option<RateLimitConfig>) -> Self {
+        let config = config.unwrap_or_default();
+
+        Self {
+            messages: config.messages_per_sec.filter(|x| *x > 0).map(Bucket::new),
+            bytes: config.bytes_per_sec.filter(|x| *x > 0).map(Bucket::new),
+        }
+    }
+
+    /// Waits until both limits allow the next message.
+    pub(crate) async fn ready(&mut self) {
+        loop {
+            let now = Instant::now();
+            let wait = [&mut self.messages, &mut self.bytes]
+                .into_iter()
+                .flatten()
+                .map(|bucket| bucket.wait_time(now))
+                .max()
+                .unwrap_or_default();
+
+            if wait.is_zero() {
+                return;
+            }
+
+            crate::runtime::sleep(wait).await;
+        }
+    }
+
+    /// Charges `messages` messages of `bytes` bytes in total.
+    pub(crate) fn record(&mut self, messages: usize, bytes: usize) {
+        if let Some(bucket) = &mut self.messages {
+            bucket.tokens -= messages as f64;
+        }
+
+        if let Some(bucket) = &mut self.bytes {
+            bucket.tokens -= bytes as f64;
+        }
+    }
+}
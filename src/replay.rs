@@ -0,0 +1,293 @@
+//! Replay of the messages of topics produced within a time range, e.g. to reprocess the
+//! traffic of an incident.
+
+use std::{collections::HashMap, marker::PhantomData, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use futures::Stream;
+use rdkafka::{
+    Message as _, Offset, TopicPartitionList,
+    consumer::{Consumer, stream_consumer::StreamConsumer},
+    error::{KafkaError, KafkaResult},
+    types::RDKafkaErrorCode,
+};
+
+use crate::{
+    KafkaCallbackContext, Message, blocking,
+    builder::KafkaBuilder,
+    codec::Decoder,
+    config::Config,
+    consumer::{SharedMessage, decode_message},
+    error::{DecodeFailure, Error},
+    runtime::Runtime,
+};
+
+/// How long a replay waits for the next message or metadata.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads the messages of topics with a timestamp within a range, from offsets looked up by
+/// timestamp, and ends once every partition reached the end of the range.
+///
+/// Partitions are assigned instead of subscribed and offsets are never committed, so a
+/// replay does not disturb the group of its config.
+pub struct Replayer<
+    M = Bytes,
+    D: Decoder<M> = crate::codec::BytesDecoder,
+    K = Bytes,
+    KD: Decoder<K> = crate::codec::BytesDecoder,
+> {
+    builder: KafkaBuilder,
+    decoder: D,
+    key_decoder: KD,
+    decode_headers: bool,
+    _m: PhantomData<(M, K)>,
+}
+
+impl Replayer {
+    #[inline]
+    pub fn new(config: Config) -> Self {
+        Self::new_with_decoder(Default::default(), config)
+    }
+}
+
+impl<M, D: Decoder<M>> Replayer<M, D> {
+    pub fn new_with_decoder(decoder: D, mut config: Config) -> Self {
+        config.auto_commit = Some(false);
+
+        Self {
+            decode_headers: config.decode_headers,
+            builder: KafkaBuilder::new(config),
+            decoder,
+            key_decoder: crate::codec::BytesDecoder,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<M, D, K, KD> Replayer<M, D, K, KD>
+where
+    D: Decoder<M> + Send,
+    KD: Decoder<K> + Send,
+    KD::Error: std::error::Error + Send + Sync + 'static,
+    M: Send,
+    K: Send,
+{
+    /// Decodes message keys with `key_decoder` instead of returning them as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_decoder` - The decoder used for the message keys.
+    ///
+    /// # Returns
+    ///
+    /// The replayer producing messages with keys of type `K2`.
+    pub fn with_key_decoder<K2, KD2: Decoder<K2>>(
+        self,
+        key_decoder: KD2,
+    ) -> Replayer<M, D, K2, KD2> {
+        Replayer {
+            builder: self.builder,
+            decoder: self.decoder,
+            key_decoder,
+            decode_headers: self.decode_headers,
+            _m: PhantomData,
+        }
+    }
+
+    /// Replays the messages of `topics` with a timestamp from `from_ms` to `to_ms`
+    /// inclusive, in milliseconds since the epoch.
+    ///
+    /// Messages are in order within a partition only. The range is looked up by timestamp,
+    /// so with producer timestamps out of order the messages outside of it are skipped.
+    pub fn replay(
+        &mut self,
+        topics: &[&str],
+        from_ms: i64,
+        to_ms: i64,
+    ) -> impl Stream<Item = Result<Message<M, K>, Error<D::Error>>> + Send
+    where
+        D::Error: Send,
+    {
+        let topics: Vec<String> = topics.iter().map(|x| x.to_string()).collect();
+
+        async_stream::stream! {
+            let consumer = match self.builder.build_consumer() {
+                Ok(consumer) => Arc::new(consumer),
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            };
+
+            let ranges = {
+                let consumer = consumer.clone();
+                blocking(move || {
+                    let ranges = replay_ranges(&consumer, &topics, from_ms, to_ms)?;
+
+                    let mut assignment = TopicPartitionList::new();
+                    for ((topic, partition), (start, _)) in &ranges {
+                        assignment.add_partition_offset(topic, *partition, Offset::Offset(*start))?;
+                    }
+                    consumer.assign(&assignment)?;
+
+                    KafkaResult::Ok(ranges)
+                })
+                .await
+            };
+
+            // partitions left to read, up to their end offset excluded
+            let mut remaining: HashMap<(String, i32), i64> = match ranges {
+                Ok(ranges) => ranges.into_iter().map(|(key, (_, end))| (key, end)).collect(),
+                Err(err) => {
+                    yield Err(Error::KafkaError(err));
+                    return;
+                }
+            };
+
+            while !remaining.is_empty() {
+                let msg = match crate::runtime::timeout(REPLAY_TIMEOUT, consumer.recv()).await {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(err)) => {
+                        yield Err(Error::Receive(err));
+                        return;
+                    }
+
+                    // the last offsets may be transaction markers that are never returned
+                    None => {
+                        let before = remaining.len();
+
+                        match consumer.position() {
+                            Ok(position) => {
+                                for elem in position.elements() {
+                                    let key = (elem.topic().to_string(), elem.partition());
+                                    if let (Offset::Offset(position), Some(end)) =
+                                        (elem.offset(), remaining.get(&key))
+                                        && position >= *end
+                                    {
+                                        remaining.remove(&key);
+                                    }
+                                }
+                            }
+                            Err(err) => {
+                                yield Err(Error::KafkaError(err));
+                                return;
+                            }
+                        }
+
+                        if remaining.len() == before {
+                            yield Err(Error::Receive(KafkaError::MessageConsumption(
+                                RDKafkaErrorCode::OperationTimedOut,
+                            )));
+                            return;
+                        }
+
+                        continue;
+                    }
+                };
+
+                let key = (msg.topic().to_string(), msg.partition());
+                let Some(end) = remaining.get(&key).copied() else {
+                    continue;
+                };
+
+                if msg.offset() + 1 >= end {
+                    remaining.remove(&key);
+                }
+
+                if msg.offset() >= end
+                    || !msg
+                        .timestamp()
+                        .to_millis()
+                        .is_some_and(|ts| (from_ms..=to_ms).contains(&ts))
+                {
+                    continue;
+                }
+
+                let msg = SharedMessage::new(msg, &consumer);
+                let payload = msg.payload_bytes();
+                let key = msg.key_bytes();
+
+                let decoded = decode_message(
+                    &mut self.decoder,
+                    &mut self.key_decoder,
+                    self.decode_headers,
+                    &msg,
+                    payload.clone(),
+                    key.clone(),
+                )
+                .map_err(|error| {
+                    Error::DecodeFailed(Box::new(DecodeFailure {
+                        raw: payload,
+                        key,
+                        error,
+                    }))
+                    .at(msg.topic(), msg.partition(), msg.offset())
+                });
+                drop(msg);
+
+                yield decoded;
+            }
+        }
+    }
+}
+
+/// Looks up the offsets of the range of every partition of `topics`, the end offset being
+/// excluded. Partitions without messages in the range are left out.
+fn replay_ranges(
+    consumer: &StreamConsumer<KafkaCallbackContext, Runtime>,
+    topics: &[String],
+    from_ms: i64,
+    to_ms: i64,
+) -> KafkaResult<HashMap<(String, i32), (i64, i64)>> {
+    let mut from = TopicPartitionList::new();
+    let mut to = TopicPartitionList::new();
+    let mut high = HashMap::new();
+
+    for topic in topics {
+        let metadata = consumer.fetch_metadata(Some(topic), REPLAY_TIMEOUT)?;
+
+        for partition in metadata
+            .topics()
+            .iter()
+            .filter(|x| x.name() == topic)
+            .flat_map(|x| x.partitions())
+        {
+            let (_, watermark) =
+                consumer.fetch_watermarks(topic, partition.id(), REPLAY_TIMEOUT)?;
+            high.insert((topic.clone(), partition.id()), watermark);
+
+            from.add_partition_offset(topic, partition.id(), Offset::Offset(from_ms))?;
+            to.add_partition_offset(topic, partition.id(), Offset::Offset(to_ms + 1))?;
+        }
+    }
+
+    // offsets of the first messages at or after the timestamps, the end when there is none
+    let lookup = |tpl: TopicPartitionList| -> KafkaResult<HashMap<(String, i32), i64>> {
+        let offsets = consumer.offsets_for_times(tpl, REPLAY_TIMEOUT)?;
+
+        Ok(offsets
+            .elements()
+            .iter()
+            .map(|elem| {
+                let key = (elem.topic().to_string(), elem.partition());
+                let offset = match elem.offset() {
+                    Offset::Offset(offset) => offset,
+                    _ => high.get(&key).copied().unwrap_or_default(),
+                };
+
+                (key, offset)
+            })
+            .collect())
+    };
+
+    let start = lookup(from)?;
+    let end = lookup(to)?;
+
+    Ok(start
+        .into_iter()
+        .filter_map(|(key, start)| {
+            let end = *end.get(&key)?;
+            (start < end).then_some((key, (start, end)))
+        })
+        .collect())
+}
@@ -0,0 +1,103 @@
+//! Async runtime the clients run on: tokio with the `tokio` feature (default), smol with
+//! the `smol` feature alone.
+
+use std::{future::Future, pin::pin, time::Duration};
+
+use futures::future::Either;
+
+#[cfg(not(any(feature = "tokio", feature = "smol")))]
+compile_error!("flowly-kafka requires either the `tokio` or the `smol` feature");
+
+/// The librdkafka runtime of the stream consumers and future producers.
+#[cfg(feature = "tokio")]
+pub type Runtime = rdkafka::util::TokioRuntime;
+
+/// The librdkafka runtime of the stream consumers and future producers.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub type Runtime = SmolRuntime;
+
+/// [`AsyncRuntime`](rdkafka::util::AsyncRuntime) spawning on the global smol executor.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub struct SmolRuntime;
+
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+impl rdkafka::util::AsyncRuntime for SmolRuntime {
+    type Delay = futures::future::Map<smol::Timer, fn(std::time::Instant)>;
+
+    fn spawn<T>(task: T)
+    where
+        T: Future<Output = ()> + Send + 'static,
+    {
+        smol::spawn(task).detach()
+    }
+
+    fn delay_for(duration: Duration) -> Self::Delay {
+        futures::FutureExt::map(smol::Timer::after(duration), |_| ())
+    }
+}
+
+/// Waits for `duration`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Waits for `duration`.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub(crate) async fn sleep(duration: Duration) {
+    smol::Timer::after(duration).await;
+}
+
+/// Runs a blocking call on the blocking thread pool of the runtime.
+#[cfg(feature = "tokio")]
+pub(crate) async fn spawn_blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+/// Runs a blocking call on the blocking thread pool of the runtime.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub(crate) async fn spawn_blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    smol::unblock(f).await
+}
+
+/// Runs `fut` in the background, `false` when there is no runtime to run it on.
+#[cfg(feature = "tokio")]
+pub(crate) fn spawn_detached<F>(fut: F) -> bool
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return false;
+    };
+
+    handle.spawn(fut);
+    true
+}
+
+/// Runs `fut` in the background, `false` when there is no runtime to run it on.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub(crate) fn spawn_detached<F>(fut: F) -> bool
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    smol::spawn(fut).detach();
+    true
+}
+
+/// Awaits `fut` for at most `duration`, returning `None` once it elapsed.
+pub(crate) async fn timeout<F: Future>(duration: Duration, fut: F) -> Option<F::Output> {
+    match futures::future::select(pin!(fut), pin!(sleep(duration))).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(..) => None,
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use reqwest::{RequestBuilder, Url, header};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{error::SchemaRegistryError, secret::SecretSource};
+
+const CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaRegistryConfig {
+    pub url: String,
+
+    #[serde(default)]
+    pub auth: Option<RegistryAuth>,
+
+    #[serde(default = "SchemaRegistryConfig::default_timeout_ms")]
+    pub timeout_ms: u32,
+}
+
+impl SchemaRegistryConfig {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self {
+            url: url.into(),
+            auth: None,
+            timeout_ms: Self::default_timeout_ms(),
+        }
+    }
+
+    /// Sets the credentials used to authenticate against the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `auth` - Basic auth credentials (also used for API key/secret pairs) or a bearer token.
+    ///
+    /// # Returns
+    ///
+    /// The modified configuration with the credentials set.
+    pub fn auth(mut self, auth: RegistryAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    #[inline]
+    pub fn default_timeout_ms() -> u32 {
+        10_000
+    }
+}
+
+/// Credentials of the schema registry, API keys are passed as basic auth with the key
+/// as username and the secret as password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RegistryAuth {
+    Basic {
+        username: String,
+        password: SecretSource,
+    },
+    Bearer {
+        token: SecretSource,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    #[default]
+    Avro,
+    Protobuf,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SchemaReference {
+    pub name: String,
+    pub subject: String,
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    #[serde(default)]
+    pub schema_type: SchemaType,
+    pub schema: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<SchemaReference>,
+}
+
+impl Schema {
+    pub fn new<S: Into<String>>(schema_type: SchemaType, schema: S) -> Self {
+        Self {
+            schema_type,
+            schema: schema.into(),
+            references: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RegisteredSchema {
+    pub subject: String,
+    pub id: u32,
+    pub version: u32,
+    #[serde(flatten)]
+    pub schema: Schema,
+}
+
+#[derive(Deserialize)]
+struct SchemaId {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct Compatibility {
+    is_compatible: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error_code: i32,
+    message: String,
+}
+
+enum Auth {
+    Basic(String, String),
+    Bearer(String),
+}
+
+struct Inner {
+    http: reqwest::Client,
+    base: Url,
+    auth: Option<Auth>,
+    schemas: RwLock<HashMap<u32, Arc<Schema>>>,
+    ids: RwLock<HashMap<(String, Schema), u32>>,
+}
+
+/// Async client of the Confluent compatible schema registry REST API.
+///
+/// Schemas are cached by id and by subject, so decoders can look them up without a
+/// request once seen. The client is cheap to clone.
+#[derive(Clone)]
+pub struct SchemaRegistryClient {
+    inner: Arc<Inner>,
+}
+
+impl SchemaRegistryClient {
+    pub fn new(config: SchemaRegistryConfig) -> Result<Self, SchemaRegistryError> {
+        let base = Url::parse(&config.url)
+            .ok()
+            .filter(|x| !x.cannot_be_a_base())
+            .ok_or_else(|| SchemaRegistryError::InvalidUrl(config.url.clone()))?;
+
+        let auth = match &config.auth {
+            Some(RegistryAuth::Basic { username, password }) => {
+                Some(Auth::Basic(username.clone(), password.resolve()?))
+            }
+            Some(RegistryAuth::Bearer { token }) => Some(Auth::Bearer(token.resolve()?)),
+            None => None,
+        };
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms as _))
+            .build()?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                http,
+                base,
+                auth,
+                schemas: Default::default(),
+                ids: Default::default(),
+            }),
+        })
+    }
+
+    /// Returns a schema from the cache without contacting the registry.
+    pub fn cached(&self, id: u32) -> Option<Arc<Schema>> {
+        self.inner.schemas.read().unwrap().get(&id).cloned()
+    }
+
+    /// Fetches a schema by its global id.
+    pub async fn schema_by_id(&self, id: u32) -> Result<Arc<Schema>, SchemaRegistryError> {
+        if let Some(schema) = self.cached(id) {
+            return Ok(schema);
+        }
+
+        let url = self.url(&["schemas", "ids", &id.to_string()]);
+        let schema: Schema = self.send(self.inner.http.get(url)).await?;
+
+        Ok(self.insert(id, schema))
+    }
+
+    /// Fetches the latest registered version of a subject.
+    pub async fn latest_schema(
+        &self,
+        subject: &str,
+    ) -> Result<RegisteredSchema, SchemaRegistryError> {
+        self.subject_version(subject, "latest").await
+    }
+
+    /// Fetches a specific version of a subject.
+    pub async fn schema_version(
+        &self,
+        subject: &str,
+        version: u32,
+    ) -> Result<RegisteredSchema, SchemaRegistryError> {
+        self.subject_version(subject, &version.to_string()).await
+    }
+
+    /// Lists the registered subjects.
+    pub async fn subjects(&self) -> Result<Vec<String>, SchemaRegistryError> {
+        let url = self.url(&["subjects"]);
+        self.send(self.inner.http.get(url)).await
+    }
+
+    /// Registers a schema under a subject returning its id, registering an already
+    /// known schema is answered from the cache.
+    pub async fn register(
+        &self,
+        subject: &str,
+        schema: &Schema,
+    ) -> Result<u32, SchemaRegistryError> {
+        let key = (subject.to_string(), schema.clone());
+
+        if let Some(id) = self.inner.ids.read().unwrap().get(&key) {
+            return Ok(*id);
+        }
+
+        let url = self.url(&["subjects", subject, "versions"]);
+        let SchemaId { id } = self.send(self.inner.http.post(url).json(schema)).await?;
+
+        self.inner.ids.write().unwrap().insert(key, id);
+        self.insert(id, schema.clone());
+
+        Ok(id)
+    }
+
+    /// Looks up a schema under a subject without registering it.
+    pub async fn lookup(
+        &self,
+        subject: &str,
+        schema: &Schema,
+    ) -> Result<RegisteredSchema, SchemaRegistryError> {
+        let url = self.url(&["subjects", subject]);
+        let registered: RegisteredSchema =
+            self.send(self.inner.http.post(url).json(schema)).await?;
+
+        self.inner
+            .ids
+            .write()
+            .unwrap()
+            .insert((subject.to_string(), schema.clone()), registered.id);
+
+        Ok(registered)
+    }
+
+    /// Checks a schema against the compatibility rules of the latest version of a subject.
+    pub async fn is_compatible(
+        &self,
+        subject: &str,
+        schema: &Schema,
+    ) -> Result<bool, SchemaRegistryError> {
+        let url = self.url(&["compatibility", "subjects", subject, "versions", "latest"]);
+        let Compatibility { is_compatible } =
+            self.send(self.inner.http.post(url).json(schema)).await?;
+
+        Ok(is_compatible)
+    }
+
+    async fn subject_version(
+        &self,
+        subject: &str,
+        version: &str,
+    ) -> Result<RegisteredSchema, SchemaRegistryError> {
+        let url = self.url(&["subjects", subject, "versions", version]);
+        let registered: RegisteredSchema = self.send(self.inner.http.get(url)).await?;
+
+        self.insert(registered.id, registered.schema.clone());
+
+        Ok(registered)
+    }
+
+    fn insert(&self, id: u32, schema: Schema) -> Arc<Schema> {
+        self.inner
+            .schemas
+            .write()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| Arc::new(schema))
+            .clone()
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.inner.base.clone();
+
+        // checked for `cannot_be_a_base` in `new`
+        url.path_segments_mut()
+            .expect("base url")
+            .pop_if_empty()
+            .extend(segments);
+
+        url
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, SchemaRegistryError> {
+        let request = match &self.inner.auth {
+            Some(Auth::Basic(username, password)) => request.basic_auth(username, Some(password)),
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        };
+
+        let response = request
+            .header(header::ACCEPT, CONTENT_TYPE)
+            .header(header::CONTENT_TYPE, CONTENT_TYPE)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+
+        let error = response.json::<ApiError>().await.unwrap_or(ApiError {
+            error_code: status.as_u16() as _,
+            message: status.to_string(),
+        });
+
+        Err(SchemaRegistryError::Api {
+            status: status.as_u16(),
+            code: error.error_code,
+            message: error.message,
+        })
+    }
+}
+
+/// A schema of an encoder together with the subject it is written under, resolving its id
+/// once either by registering it or by looking it up.
+pub(crate) struct SubjectSchema {
+    pub(crate) registry: SchemaRegistryClient,
+    pub(crate) subject: String,
+    pub(crate) schema: Schema,
+    pub(crate) auto_register: bool,
+    id: Option<u32>,
+}
+
+impl SubjectSchema {
+    pub(crate) fn new(registry: SchemaRegistryClient, subject: String, schema: Schema) -> Self {
+        Self {
+            registry,
+            subject,
+            schema,
+            auto_register: true,
+            id: None,
+        }
+    }
+
+    pub(crate) async fn resolve(&mut self) -> Result<u32, SchemaRegistryError> {
+        if let Some(id) = self.id {
+            return Ok(id);
+        }
+
+        let id = if self.auto_register {
+            self.registry.register(&self.subject, &self.schema).await?
+        } else {
+            self.registry.lookup(&self.subject, &self.schema).await?.id
+        };
+
+        self.id = Some(id);
+
+        Ok(id)
+    }
+
+    pub(crate) fn resolve_blocking(&mut self) -> Result<u32, SchemaRegistryError> {
+        match self.id {
+            Some(id) => Ok(id),
+            None => block_on(self.resolve())?,
+        }
+    }
+}
+
+/// Runs a registry request from synchronous code such as `Encoder`/`Decoder` impls.
+///
+/// Only possible inside a multi-threaded tokio runtime, elsewhere the schema has to be
+/// fetched or registered up front.
+pub(crate) fn block_on<F: Future>(fut: F) -> Result<F::Output, SchemaRegistryError> {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Ok(tokio::task::block_in_place(|| handle.block_on(fut)))
+        }
+        _ => Err(SchemaRegistryError::RuntimeUnavailable),
+    }
+}
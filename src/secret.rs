@@ -0,0 +1,161 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::ConfigError;
+
+const REDACTED: &str = "********";
+
+/// Scheme of a secret read from a file.
+const FILE_SCHEME: &str = "secret+file:";
+
+/// Scheme of a secret read from an environment variable.
+const ENV_SCHEME: &str = "secret+env:";
+
+/// Source of a credential, resolved only when a client is built.
+///
+/// Parsed from strings as `secret+file:/run/secrets/kafka-password`,
+/// `secret+env:KAFKA_PASSWORD` or a literal value otherwise. The schemes are explicit so a
+/// literal like `file:x` or `env:x` is kept as is.
+#[derive(Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// The secret itself.
+    Value(String),
+
+    /// A file containing the secret, e.g. a Docker or Kubernetes secret mount.
+    File(PathBuf),
+
+    /// An environment variable containing the secret.
+    Env(String),
+}
+
+impl SecretSource {
+    /// Reads the secret, trailing newlines of secret files are stripped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::SecretUnavailable` if the file or variable can not be read.
+    pub fn resolve(&self) -> Result<String, ConfigError> {
+        match self {
+            SecretSource::Value(value) => Ok(value.clone()),
+            SecretSource::File(path) => std::fs::read_to_string(path)
+                .map(|x| x.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|err| ConfigError::SecretUnavailable {
+                    secret: self.to_string(),
+                    reason: err.to_string(),
+                }),
+            SecretSource::Env(name) => {
+                std::env::var(name).map_err(|err| ConfigError::SecretUnavailable {
+                    secret: self.to_string(),
+                    reason: err.to_string(),
+                })
+            }
+        }
+    }
+}
+
+impl From<String> for SecretSource {
+    fn from(value: String) -> Self {
+        SecretSource::Value(value)
+    }
+}
+
+impl From<&str> for SecretSource {
+    fn from(value: &str) -> Self {
+        SecretSource::Value(value.to_string())
+    }
+}
+
+impl FromStr for SecretSource {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if let Some(path) = s.strip_prefix(FILE_SCHEME) {
+            SecretSource::File(path.into())
+        } else if let Some(name) = s.strip_prefix(ENV_SCHEME) {
+            SecretSource::Env(name.to_string())
+        } else {
+            SecretSource::Value(s.to_string())
+        })
+    }
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::Value(_) => write!(f, "{REDACTED}"),
+            SecretSource::File(path) => write!(f, "{FILE_SCHEME}{}", path.display()),
+            SecretSource::Env(name) => write!(f, "{ENV_SCHEME}{name}"),
+        }
+    }
+}
+
+impl fmt::Debug for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretSource({self})")
+    }
+}
+
+impl Serialize for SecretSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretSource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> SecretSource {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_explicit_schemes() {
+        assert_eq!(
+            parse("secret+file:/run/secrets/kafka-password"),
+            SecretSource::File("/run/secrets/kafka-password".into())
+        );
+        assert_eq!(
+            parse("secret+env:KAFKA_PASSWORD"),
+            SecretSource::Env("KAFKA_PASSWORD".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_other_prefixes_literal() {
+        for literal in [
+            "file:/etc/passwd",
+            "env:HOME",
+            "hunter2",
+            "",
+            "secret+other:x",
+        ] {
+            assert_eq!(parse(literal), SecretSource::Value(literal.to_string()));
+        }
+    }
+
+    #[test]
+    fn displays_sources_and_redacts_values() {
+        for source in [
+            "secret+file:/run/secrets/kafka-password",
+            "secret+env:KAFKA_PASSWORD",
+        ] {
+            assert_eq!(parse(source).to_string(), source);
+        }
+
+        assert_eq!(parse("env:HOME").to_string(), REDACTED);
+        assert_eq!(
+            format!("{:?}", parse("hunter2")),
+            format!("SecretSource({REDACTED})")
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Key-value state of stateful stages, made fault tolerant by a changelog topic like the
+//! state stores of Kafka Streams.
+
+use std::{
+    collections::{HashMap, hash_map},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use rdkafka::{
+    Message as _, Offset, TopicPartitionList,
+    consumer::Consumer,
+    error::{KafkaError, KafkaResult},
+    producer::{FutureProducer, FutureRecord},
+    types::RDKafkaErrorCode,
+};
+
+use crate::{
+    KafkaCallbackContext,
+    admin::KafkaAdmin,
+    blocking,
+    builder::KafkaBuilder,
+    codec::{Decoder, Encoder},
+    config::{Config, TopicSpec},
+    error::Error,
+    runtime::Runtime,
+};
+
+/// How long restoring waits for the next changelog record or metadata.
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// In-memory map whose mutations are first written to a changelog topic, so it can be
+/// rebuilt after a restart with [`restore`](Self::restore).
+///
+/// The changelog is keyed by the state keys and should be compacted, see
+/// [`create_changelog`](Self::create_changelog). Deleted keys are written as tombstones.
+pub struct StateStore<V, E, D> {
+    builder: KafkaBuilder,
+    changelog: String,
+    producer: Option<FutureProducer<KafkaCallbackContext, Runtime>>,
+    encoder: E,
+    decoder: D,
+    buffer: BytesMut,
+    state: HashMap<String, V>,
+}
+
+#[cfg(feature = "json")]
+impl<V> StateStore<V, crate::codec::JsonEncoder<V>, crate::codec::JsonDecoder<V>>
+where
+    V: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Creates a store keeping its values as JSON in the `changelog` topic.
+    pub fn new_json<S: Into<String>>(config: Config, changelog: S) -> Self {
+        Self::new(config, changelog, Default::default(), Default::default())
+    }
+}
+
+impl<V, E: Encoder<V>, D: Decoder<V>> StateStore<V, E, D> {
+    /// Creates an empty store writing to the `changelog` topic, call
+    /// [`restore`](Self::restore) before using it.
+    pub fn new<S: Into<String>>(config: Config, changelog: S, encoder: E, decoder: D) -> Self {
+        Self {
+            builder: KafkaBuilder::new(config),
+            changelog: changelog.into(),
+            producer: None,
+            encoder,
+            decoder,
+            buffer: BytesMut::new(),
+            state: HashMap::new(),
+        }
+    }
+
+    /// Creates the changelog topic as a compacted topic when it does not exist.
+    pub async fn create_changelog(
+        &self,
+        partitions: i32,
+        replication: i32,
+    ) -> Result<(), Error<E::Error>> {
+        let spec = TopicSpec::new(partitions, replication).config("cleanup.policy", "compact");
+
+        KafkaAdmin::from_builder(self.builder.clone())?
+            .ensure_topics(&[&self.changelog], &spec)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the state with the content of the changelog, returning the number of keys.
+    pub async fn restore(&mut self) -> Result<usize, Error<D::Error>> {
+        let consumer = self
+            .builder
+            .build_group_consumer(&format!("{}-restore", self.builder.config().group_id))?;
+        let topic = self.changelog.clone();
+
+        let records = blocking(move || {
+            let mut records = HashMap::new();
+            let metadata = consumer.fetch_metadata(Some(&topic), RESTORE_TIMEOUT)?;
+
+            let mut assignment = TopicPartitionList::new();
+            let mut remaining = HashMap::new();
+
+            for partition in metadata
+                .topics()
+                .iter()
+                .filter(|x| x.name() == topic)
+                .flat_map(|x| x.partitions())
+            {
+                let (low, high) =
+                    consumer.fetch_watermarks(&topic, partition.id(), RESTORE_TIMEOUT)?;
+
+                if high > low {
+                    assignment.add_partition_offset(&topic, partition.id(), Offset::Beginning)?;
+                    remaining.insert(partition.id(), high);
+                }
+            }
+
+            consumer.assign(&assignment)?;
+
+            while !remaining.is_empty() {
+                match consumer.poll(RESTORE_TIMEOUT) {
+                    Some(msg) => {
+                        let msg = msg?;
+
+                        if let Some(key) = msg.key() {
+                            records.insert(
+                                String::from_utf8_lossy(key).into_owned(),
+                                msg.payload().map(<[u8]>::to_vec),
+                            );
+                        }
+
+                        if remaining
+                            .get(&msg.partition())
+                            .is_some_and(|high| msg.offset() + 1 >= *high)
+                        {
+                            remaining.remove(&msg.partition());
+                        }
+                    }
+
+                    // the last offsets may be transaction markers that are never returned
+                    None => {
+                        let before = remaining.len();
+
+                        for elem in consumer.position()?.elements() {
+                            if let (Offset::Offset(position), Some(high)) =
+                                (elem.offset(), remaining.get(&elem.partition()))
+                                && position >= *high
+                            {
+                                remaining.remove(&elem.partition());
+                            }
+                        }
+
+                        if remaining.len() == before {
+                            return Err(KafkaError::MessageConsumption(
+                                RDKafkaErrorCode::OperationTimedOut,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            KafkaResult::Ok(records)
+        })
+        .await?;
+
+        self.state.clear();
+
+        for (key, value) in records {
+            if let Some(value) = value {
+                let value = self
+                    .decoder
+                    .decode(&mut value.as_slice())
+                    .map_err(Error::MessageCodecError)?;
+
+                self.state.insert(key, value);
+            }
+        }
+
+        Ok(self.state.len())
+    }
+
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.state.get(key)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> hash_map::Iter<'_, String, V> {
+        self.state.iter()
+    }
+
+    /// Writes `value` to the changelog, then to the state once the write is acknowledged.
+    pub async fn put(&mut self, key: impl Into<String>, value: V) -> Result<(), Error<E::Error>> {
+        let key = key.into();
+
+        self.buffer.clear();
+        self.encoder
+            .encode(&value, &mut self.buffer)
+            .map_err(Error::MessageCodecError)?;
+
+        let payload = self.buffer.split().freeze();
+        self.write(&key, Some(&payload)).await?;
+        self.state.insert(key, value);
+
+        Ok(())
+    }
+
+    /// Writes a tombstone of `key` to the changelog, then removes it from the state.
+    pub async fn delete(&mut self, key: &str) -> Result<Option<V>, Error<E::Error>> {
+        self.write(key, None).await?;
+
+        Ok(self.state.remove(key))
+    }
+
+    async fn write<T>(&mut self, key: &str, payload: Option<&[u8]>) -> Result<(), Error<T>> {
+        if self.producer.is_none() {
+            self.producer.replace(self.builder.build_producer()?);
+        }
+
+        let producer = self.producer.as_ref().ok_or(Error::NoConnection)?;
+
+        let record = FutureRecord::<str, [u8]>::to(&self.changelog).key(key);
+        let record = match payload {
+            Some(payload) => record.payload(payload),
+            None => record,
+        };
+
+        producer
+            .send(record, Duration::from_secs(0))
+            .await
+            .map(|_| ())
+            .map_err(|(source, _)| Error::Deliver {
+                topic: self.changelog.clone(),
+                source,
+            })
+    }
+}
@@ -0,0 +1,12 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+pub use rdkafka::statistics::{
+    Broker, ConsumerGroup, ExactlyOnceSemantics, Partition, Statistics, Topic, TopicPartition,
+    Window,
+};
+
+/// Receives the latest statistics of a client, `None` until librdkafka emitted the first
+/// ones, see [`ConfigBuilder::statistics_interval_ms`](crate::config::ConfigBuilder::statistics_interval_ms).
+pub type StatisticsReceiver = watch::Receiver<Option<Arc<Statistics>>>;
@@ -0,0 +1,54 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc;
+
+/// Extension methods for streams of results, such as the output of the consumer.
+pub trait ResultStreamExt<T, E>: Stream<Item = Result<T, E>> + Sized {
+    /// Splits the stream into a stream of the successful items and a channel of the errors.
+    ///
+    /// Errors are forwarded as the returned stream is polled, so downstream stages only see
+    /// `T` while the receiver is handed to an alerting or dead letter sink. Errors are
+    /// dropped once the receiver is closed.
+    fn split_results(self) -> (SplitResults<Self, E>, mpsc::UnboundedReceiver<E>) {
+        let (errors, rx) = mpsc::unbounded_channel();
+
+        (
+            SplitResults {
+                stream: Box::pin(self),
+                errors,
+            },
+            rx,
+        )
+    }
+}
+
+impl<S, T, E> ResultStreamExt<T, E> for S where S: Stream<Item = Result<T, E>> {}
+
+/// Stream of the successful items returned by [`ResultStreamExt::split_results`].
+pub struct SplitResults<S, E> {
+    stream: Pin<Box<S>>,
+    errors: mpsc::UnboundedSender<E>,
+}
+
+impl<S, T, E> Stream for SplitResults<S, E>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            match ready!(self.stream.poll_next_unpin(cx)) {
+                Some(Ok(item)) => return Poll::Ready(Some(item)),
+                Some(Err(err)) => {
+                    let _ = self.errors.send(err);
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
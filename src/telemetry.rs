@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use metrics::{counter, histogram};
+
+/// Counts a message received by a consumer and the size of its payload.
+pub(crate) fn message_received(topic: &str, partition: i32, group: &str, bytes: usize) {
+    let (topic, partition, group) = (topic.to_string(), partition.to_string(), group.to_string());
+
+    counter!(
+        "kafka_consumer_messages_total",
+        "topic" => topic.clone(),
+        "partition" => partition.clone(),
+        "group" => group.clone(),
+    )
+    .increment(1);
+
+    counter!(
+        "kafka_consumer_bytes_total",
+        "topic" => topic,
+        "partition" => partition,
+        "group" => group,
+    )
+    .increment(bytes as u64);
+}
+
+/// Counts a message whose payload or key could not be decoded.
+pub(crate) fn decode_failed(topic: &str, partition: i32, group: &str) {
+    counter!(
+        "kafka_consumer_decode_errors_total",
+        "topic" => topic.to_string(),
+        "partition" => partition.to_string(),
+        "group" => group.to_string(),
+    )
+    .increment(1);
+}
+
+/// Records how long decoding the payload and key of a received message took.
+pub(crate) fn decode_duration(topic: &str, group: &str, elapsed: Duration) {
+    histogram!(
+        "kafka_consumer_decode_duration_seconds",
+        "topic" => topic.to_string(),
+        "group" => group.to_string(),
+    )
+    .record(elapsed);
+}
+
+/// Records how long the consumer stream waited for the downstream stages to ask for the
+/// next message, the stages after it being the bottleneck when it grows.
+pub(crate) fn stream_stalled(topic: &str, group: &str, elapsed: Duration) {
+    histogram!(
+        "kafka_consumer_stall_duration_seconds",
+        "topic" => topic.to_string(),
+        "group" => group.to_string(),
+    )
+    .record(elapsed);
+}
+
+/// Records how long encoding the payload and key of a sent message took.
+pub(crate) fn encode_duration(topic: &str, elapsed: Duration) {
+    histogram!("kafka_producer_encode_duration_seconds", "topic" => topic.to_string())
+        .record(elapsed);
+}
+
+/// Records the produce to consume and broker to consume latencies of a received message.
+pub(crate) fn message_latency(
+    topic: &str,
+    partition: i32,
+    produced: Option<Duration>,
+    appended: Option<Duration>,
+) {
+    let (topic, partition) = (topic.to_string(), partition.to_string());
+
+    if let Some(latency) = produced {
+        histogram!(
+            "kafka_consumer_e2e_latency_seconds",
+            "topic" => topic.clone(),
+            "partition" => partition.clone(),
+        )
+        .record(latency);
+    }
+
+    if let Some(latency) = appended {
+        histogram!(
+            "kafka_consumer_broker_latency_seconds",
+            "topic" => topic,
+            "partition" => partition,
+        )
+        .record(latency);
+    }
+}
+
+/// Records the outcome of a produce request and how long the delivery took.
+pub(crate) fn message_sent(topic: &str, bytes: usize, elapsed: Duration, delivered: bool) {
+    let topic = topic.to_string();
+
+    if delivered {
+        counter!("kafka_producer_messages_total", "topic" => topic.clone()).increment(1);
+        counter!("kafka_producer_bytes_total", "topic" => topic.clone()).increment(bytes as u64);
+    } else {
+        counter!("kafka_producer_errors_total", "topic" => topic.clone()).increment(1);
+    }
+
+    histogram!("kafka_producer_send_duration_seconds", "topic" => topic).record(elapsed);
+}
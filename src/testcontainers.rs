@@ -0,0 +1,54 @@
+//! Single node Kafka cluster in a container for integration tests, requires Docker.
+
+use testcontainers_modules::{
+    kafka::apache::{KAFKA_PORT, Kafka},
+    testcontainers::{ContainerAsync, TestcontainersError, runners::AsyncRunner},
+};
+
+use crate::{
+    admin::KafkaAdmin,
+    config::{AutoOffsetReset, Config, TopicSpec},
+    error::AdminError,
+};
+
+/// A running single node Kafka container, removed when dropped.
+pub struct KafkaTestCluster {
+    brokers: String,
+    _container: ContainerAsync<Kafka>,
+}
+
+impl KafkaTestCluster {
+    /// Starts the container and waits until the broker accepts connections.
+    pub async fn start() -> Result<Self, TestcontainersError> {
+        let container = Kafka::default().start().await?;
+        let port = container.get_host_port_ipv4(KAFKA_PORT).await?;
+
+        Ok(Self {
+            brokers: format!("127.0.0.1:{port}"),
+            _container: container,
+        })
+    }
+
+    /// Returns the bootstrap servers of the cluster.
+    #[inline]
+    pub fn brokers(&self) -> &str {
+        &self.brokers
+    }
+
+    /// Returns a config connected to the cluster with a fresh group id, consuming from the
+    /// earliest offset so tests see the messages produced before they subscribed.
+    pub fn config(&self) -> Config {
+        Config::builder()
+            .brokers_str(&self.brokers)
+            .group_id(format!("flowly-test-{}", crate::unique_id()))
+            .auto_offset_reset(AutoOffsetReset::Earliest)
+            .build()
+    }
+
+    /// Creates `topics` with `partitions` partitions each, existing topics are kept.
+    pub async fn create_topics(&self, topics: &[&str], partitions: i32) -> Result<(), AdminError> {
+        KafkaAdmin::new(self.config())?
+            .ensure_topics(topics, &TopicSpec::new(partitions, 1))
+            .await
+    }
+}
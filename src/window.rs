@@ -0,0 +1,247 @@
+//! Keyed aggregation of consumed messages over event-time windows.
+
+use std::{collections::BTreeSet, marker::PhantomData, time::Duration};
+
+#[cfg(feature = "flowly")]
+use flowly::Service;
+#[cfg(feature = "flowly")]
+use futures::Stream;
+
+use crate::{
+    KafkaMessage,
+    codec::{Decoder, Encoder},
+    error::Error,
+    state::StateStore,
+};
+
+/// Size and spacing of the windows of a [`WindowedAggregate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSpec {
+    size_ms: i64,
+    advance_ms: i64,
+    lateness_ms: i64,
+}
+
+impl WindowSpec {
+    /// Non-overlapping windows of `size`, every message belongs to exactly one window.
+    pub fn tumbling(size: Duration) -> Self {
+        Self::hopping(size, size)
+    }
+
+    /// Windows of `size` starting every `advance`, a message belongs to every window
+    /// covering its timestamp.
+    pub fn hopping(size: Duration, advance: Duration) -> Self {
+        let size_ms = (size.as_millis() as i64).max(1);
+
+        Self {
+            size_ms,
+            advance_ms: (advance.as_millis() as i64).clamp(1, size_ms),
+            lateness_ms: 0,
+        }
+    }
+
+    /// Keeps windows open for `lateness` past their end, so out-of-order messages still
+    /// update them.
+    ///
+    /// # Arguments
+    ///
+    /// * `lateness` - How far behind the newest timestamp a message may be, 0 by default.
+    ///
+    /// # Returns
+    ///
+    /// The spec with the allowed lateness set.
+    pub fn with_lateness(mut self, lateness: Duration) -> Self {
+        self.lateness_ms = lateness.as_millis() as i64;
+        self
+    }
+
+    /// Returns the windows covering `ts_ms`, latest first.
+    fn windows(&self, ts_ms: i64) -> impl Iterator<Item = Window> + '_ {
+        let last = ts_ms - ts_ms.rem_euclid(self.advance_ms);
+
+        (0..)
+            .map(move |i| last - i * self.advance_ms)
+            .take_while(move |start| start + self.size_ms > ts_ms)
+            .map(|start| Window {
+                start_ms: start,
+                end_ms: start + self.size_ms,
+            })
+    }
+}
+
+/// Time range of a window, the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Window {
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+/// Final aggregate of a key in a closed window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Windowed<A> {
+    pub key: String,
+    pub window: Window,
+    pub value: A,
+}
+
+/// Groups messages by key into event-time windows and yields the aggregate of each window
+/// once it closes.
+///
+/// Windows are assigned by `ts_ms_utc`, messages without a timestamp by the current time.
+/// A window closes when the newest timestamp seen passes its end plus the allowed
+/// lateness, messages for closed windows are dropped. Keyless messages are aggregated under
+/// the empty key and messages without a value are skipped.
+///
+/// Open windows are kept in a [`StateStore`], every update is written to its changelog and
+/// closed windows are deleted, so [`restore`](Self::restore) resumes them after a restart.
+pub struct WindowedAggregate<M, A, F, E, D> {
+    store: StateStore<A, E, D>,
+    spec: WindowSpec,
+    aggregator: F,
+    open: BTreeSet<(Window, String)>,
+    watermark: Option<i64>,
+    _m: PhantomData<fn(M)>,
+}
+
+impl<M, A, F, E, D> WindowedAggregate<M, A, F, E, D>
+where
+    M: KafkaMessage,
+    M::Key: AsRef<[u8]>,
+    A: Default + Clone,
+    F: FnMut(&mut A, &M::Value),
+    E: Encoder<A>,
+    D: Decoder<A>,
+{
+    /// Creates an operator folding message values into `A` with `aggregator`, starting
+    /// from `A::default()` for every new window.
+    pub fn new(store: StateStore<A, E, D>, spec: WindowSpec, aggregator: F) -> Self {
+        Self {
+            store,
+            spec,
+            aggregator,
+            open: BTreeSet::new(),
+            watermark: None,
+            _m: PhantomData,
+        }
+    }
+
+    /// Restores the open windows from the changelog of the store, returning their number.
+    pub async fn restore(&mut self) -> Result<usize, Error<D::Error>> {
+        self.store.restore().await?;
+        self.open.clear();
+
+        let size_ms = self.spec.size_ms;
+        self.open.extend(
+            self.store
+                .iter()
+                .filter_map(|(k, _)| parse_state_key(k, size_ms)),
+        );
+
+        self.watermark = self.open.iter().map(|(w, _)| w.start_ms).max();
+
+        Ok(self.open.len())
+    }
+
+    /// Adds `msg` to its windows and returns the windows closed by its timestamp.
+    pub async fn push(&mut self, msg: &M) -> Result<Vec<Windowed<A>>, Error<E::Error>> {
+        let ts = msg
+            .ts_ms_utc()
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let watermark = self.watermark.map_or(ts, |x| x.max(ts));
+        self.watermark = Some(watermark);
+
+        if let Some(value) = msg.value() {
+            let key = msg
+                .key()
+                .map(|k| String::from_utf8_lossy(k.as_ref()).into_owned())
+                .unwrap_or_default();
+
+            let windows: Vec<_> = self
+                .spec
+                .windows(ts)
+                .filter(|w| w.end_ms + self.spec.lateness_ms > watermark)
+                .collect();
+
+            for window in windows {
+                let state_key = state_key(&window, &key);
+                let mut agg = self.store.get(&state_key).cloned().unwrap_or_default();
+                (self.aggregator)(&mut agg, value);
+
+                self.store.put(state_key, agg).await?;
+                self.open.insert((window, key.clone()));
+            }
+        }
+
+        self.close_until(watermark - self.spec.lateness_ms).await
+    }
+
+    /// Closes every open window regardless of the timestamps, e.g. at the end of input.
+    pub async fn flush(&mut self) -> Result<Vec<Windowed<A>>, Error<E::Error>> {
+        self.close_until(i64::MAX).await
+    }
+
+    async fn close_until(&mut self, ts_ms: i64) -> Result<Vec<Windowed<A>>, Error<E::Error>> {
+        let mut closed = Vec::new();
+
+        while let Some((window, key)) = self.open.first().cloned() {
+            if window.end_ms > ts_ms {
+                break;
+            }
+
+            self.open.pop_first();
+
+            if let Some(value) = self.store.delete(&state_key(&window, &key)).await? {
+                closed.push(Windowed { key, window, value });
+            }
+        }
+
+        Ok(closed)
+    }
+}
+
+/// Key of a window of `key` in the state store.
+fn state_key(window: &Window, key: &str) -> String {
+    format!("{}:{}", window.start_ms, key)
+}
+
+/// Splits a state store key into the window of `size_ms` and the message key.
+fn parse_state_key(state_key: &str, size_ms: i64) -> Option<(Window, String)> {
+    let (start, key) = state_key.split_once(':')?;
+    let start_ms = start.parse().ok()?;
+
+    Some((
+        Window {
+            start_ms,
+            end_ms: start_ms + size_ms,
+        },
+        key.to_string(),
+    ))
+}
+
+#[cfg(feature = "flowly")]
+impl<M, A, F, E, D> Service<M> for WindowedAggregate<M, A, F, E, D>
+where
+    M: KafkaMessage + Send + Sync,
+    M::Key: AsRef<[u8]>,
+    M::Value: Sync,
+    A: Default + Clone + Send,
+    F: FnMut(&mut A, &M::Value) + Send,
+    E: Encoder<A> + Send,
+    E::Error: Send,
+    D: Decoder<A> + Send,
+{
+    type Out = Result<Windowed<A>, Error<E::Error>>;
+
+    fn handle(&mut self, input: M, _cx: &flowly::Context) -> impl Stream<Item = Self::Out> + Send {
+        async_stream::stream! {
+            match self.push(&input).await {
+                Ok(closed) => {
+                    for item in closed {
+                        yield Ok(item);
+                    }
+                }
+                Err(err) => yield Err(err),
+            }
+        }
+    }
+}